@@ -1,3 +1,50 @@
+// Every command in `lib.rs`'s `generate_handler!`. Listing them makes Tauri
+// generate an `allow-<command>` permission for each, so a capability has to
+// grant commands by name: the DM window gets them all (`permissions/dm.toml`),
+// the player window only `player_state`.
+const COMMANDS: &[&str] = &[
+    "open_player_window",
+    "save_map_state",
+    "load_map_state",
+    "map_identity",
+    "save_rolls",
+    "load_hexmap",
+    "render_hexmap",
+    "plan_hex_travel",
+    "export_travel_log",
+    "roll_encounter",
+    "roll_dice",
+    "check_dice",
+    "measure_distance",
+    "detect_grid",
+    "load_map_tiles",
+    "template_area",
+    "watch_map",
+    "unwatch_map",
+    "load_uvtt",
+    "list_snapshots",
+    "create_snapshot",
+    "rename_snapshot",
+    "duplicate_snapshot",
+    "delete_snapshot",
+    "restore_snapshot",
+    "start_lan_server",
+    "stop_lan_server",
+    "lan_server_info",
+    "lan_set_map_image",
+    "fog_brush",
+    "fog_rect",
+    "fog_polygon",
+    "reveal_line_of_sight",
+    "sync_player_state",
+    "sync_player_patches",
+    "player_state",
+];
+
 fn main() {
-    tauri_build::build()
+    tauri_build::try_build(
+        tauri_build::Attributes::new()
+            .app_manifest(tauri_build::AppManifest::new().commands(COMMANDS)),
+    )
+    .expect("failed to run tauri-build");
 }
@@ -0,0 +1,379 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+use crate::hexm::{self, Severity};
+use crate::hexrender::{self, RenderOptions};
+use crate::types::WallState;
+use crate::uvtt;
+
+// Headless subcommands of the `vtt` binary, for scripting map prep:
+//
+//   vtt render map.hexm -o map.png --scale 2
+//   vtt convert dungeon.dd2vtt -o dungeon.json
+//   vtt validate campaign/
+//
+// With no arguments the binary starts the app as usual.
+
+#[derive(Parser)]
+#[command(
+    name = "vtt",
+    version,
+    about = "Virtual tabletop. Run without arguments to open the app."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a .hexm map to PNG or SVG (by the output's extension).
+    Render {
+        map: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Output pixels per map pixel.
+        #[arg(long, default_value_t = 1.0)]
+        scale: f64,
+        /// Hide hex labels (the players' version).
+        #[arg(long)]
+        no_labels: bool,
+        /// Draw a terrain legend.
+        #[arg(long)]
+        legend: bool,
+    },
+    /// Convert a .hexm or .dd2vtt/.uvtt map into a cartographer-views bundle
+    /// (.json) the map loader opens directly. UVTT walls, doors and lights
+    /// come along.
+    Convert {
+        map: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Feet per grid cell, for the ruler and spell templates (the app
+        /// assumes 5 when the bundle doesn't say).
+        #[arg(long)]
+        ft_per_cell: Option<f64>,
+    },
+    /// Check .hexm and .dd2vtt/.uvtt files (directories are searched
+    /// recursively). Exits non-zero if any file has errors.
+    Validate {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// Runs a subcommand if one was given. `None` means there were no arguments
+/// and the caller should start the GUI.
+pub fn main() -> Option<ExitCode> {
+    let mut args = std::env::args_os().skip(1).peekable();
+    // macOS used to pass a `-psn_…` process serial number to bundled apps.
+    if args
+        .peek()
+        .is_none_or(|arg| arg.to_string_lossy().starts_with("-psn_"))
+    {
+        return None;
+    }
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Render {
+            map,
+            output,
+            scale,
+            no_labels,
+            legend,
+        } => render(
+            &map,
+            &output,
+            RenderOptions {
+                show_labels: !no_labels,
+                legend,
+                scale,
+            },
+        )
+        .map(|()| true),
+        Command::Convert {
+            map,
+            output,
+            ft_per_cell,
+        } => convert(&map, &output, ft_per_cell).map(|()| true),
+        Command::Validate { paths } => Ok(validate(&paths)),
+    };
+    Some(match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    })
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    std::fs::write(path, bytes).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+// The map of a `.hexm`, with its diagnostics printed. Errors inside the file
+// are reported but don't stop a render, matching the map loader.
+fn load_hexmap(path: &Path) -> Result<hexm::HexMapFile, String> {
+    let report = hexm::load(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    print_diagnostics(path, &report.diagnostics);
+    report
+        .map
+        .ok_or_else(|| format!("{} is not a hex map", path.display()))
+}
+
+fn print_diagnostics(path: &Path, diagnostics: &[hexm::Diagnostic]) {
+    for d in diagnostics {
+        let severity = match d.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        eprintln!("{}:{}: {severity}: {}", path.display(), d.line, d.message);
+    }
+}
+
+fn render(map: &Path, output: &Path, options: RenderOptions) -> Result<(), String> {
+    if !(options.scale.is_finite() && options.scale > 0.0) {
+        return Err(format!("--scale must be positive, got {}", options.scale));
+    }
+    let format = extension(output);
+    if format != "svg" && format != "png" {
+        return Err(format!("can't render to .{format}; use .png or .svg"));
+    }
+    let file = load_hexmap(map)?;
+    if format == "svg" {
+        write(output, hexrender::render_svg(&file, &options).as_bytes())
+    } else {
+        write(
+            output,
+            &hexrender::render_png(&file, &options).map_err(|e| e.to_string())?,
+        )
+    }
+}
+
+// `CartographerBundle` in `src/cartographer.ts`.
+#[derive(Serialize)]
+struct Bundle {
+    format: &'static str,
+    version: u32,
+    grid: BundleGrid,
+    gm: String,
+    player: String,
+    // Our addition to the format, for converted UVTT maps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    walls: Option<WallState>,
+}
+
+#[derive(Serialize)]
+struct BundleGrid {
+    cell_size: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ft_per_cell: Option<f64>,
+}
+
+fn convert(map: &Path, output: &Path, ft_per_cell: Option<f64>) -> Result<(), String> {
+    if ft_per_cell.is_some_and(|ft| !(ft.is_finite() && ft > 0.0)) {
+        return Err(format!(
+            "--ft-per-cell must be positive, got {}",
+            ft_per_cell.unwrap_or_default()
+        ));
+    }
+    let bundle = match extension(map).as_str() {
+        "hexm" => {
+            let file = load_hexmap(map)?;
+            Bundle {
+                format: "cartographer-views",
+                version: 1,
+                grid: BundleGrid {
+                    cell_size: file.radius(),
+                    ft_per_cell,
+                },
+                gm: hexrender::render_svg(&file, &RenderOptions::default()),
+                player: hexrender::render_svg(
+                    &file,
+                    &RenderOptions {
+                        show_labels: false,
+                        ..Default::default()
+                    },
+                ),
+                walls: None,
+            }
+        }
+        "dd2vtt" | "uvtt" => {
+            let parsed = uvtt::load(map).map_err(|e| format!("{}: {e}", map.display()))?;
+            Bundle {
+                format: "cartographer-views",
+                version: 1,
+                grid: BundleGrid {
+                    cell_size: parsed.grid_size,
+                    ft_per_cell,
+                },
+                gm: parsed.image.clone(),
+                player: parsed.image,
+                walls: Some(parsed.walls),
+            }
+        }
+        other => return Err(format!("don't know how to convert .{other} files")),
+    };
+    let json = serde_json::to_string(&bundle).map_err(|e| e.to_string())?;
+    write(output, json.as_bytes())
+}
+
+// Prints one line per problem and a summary; true when nothing had errors.
+fn validate(paths: &[PathBuf]) -> bool {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_maps(path, &mut files);
+    }
+    files.sort();
+    let (mut checked, mut failed) = (0, 0);
+    for file in &files {
+        checked += 1;
+        let ok = match extension(file).as_str() {
+            "dd2vtt" | "uvtt" => match uvtt::load(file) {
+                Ok(_) => true,
+                Err(e) => {
+                    eprintln!("{}: error: {e}", file.display());
+                    false
+                }
+            },
+            _ => match hexm::load(file) {
+                Ok(report) => {
+                    print_diagnostics(file, &report.diagnostics);
+                    !report.has_errors()
+                }
+                Err(e) => {
+                    eprintln!("{}: error: {e}", file.display());
+                    false
+                }
+            },
+        };
+        if !ok {
+            failed += 1;
+        }
+    }
+    println!("{checked} map file(s) checked, {failed} with errors");
+    failed == 0
+}
+
+fn collect_maps(path: &Path, out: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let Ok(entries) = std::fs::read_dir(path) else {
+            eprintln!("{}: error: can't read directory", path.display());
+            return;
+        };
+        for entry in entries.flatten() {
+            let child = entry.path();
+            if child.is_dir() || matches!(extension(&child).as_str(), "hexm" | "dd2vtt" | "uvtt") {
+                collect_maps(&child, out);
+            }
+        }
+    } else {
+        // Named explicitly: checked whatever the extension (as a hex map
+        // unless it's a UVTT file).
+        out.push(path.to_path_buf());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+    use serde_json::Value;
+
+    // The fixtures, copied where the commands can read them as files.
+    fn fixtures(dir: &Path) -> [PathBuf; 3] {
+        let files = [
+            (
+                "valid.hexm",
+                include_str!("../tests/fixtures/hexmaps/valid.hexm"),
+            ),
+            (
+                "broken.hexm",
+                include_str!("../tests/fixtures/hexmaps/broken.hexm"),
+            ),
+            (
+                "small.dd2vtt",
+                include_str!("../tests/fixtures/uvtt/small.dd2vtt"),
+            ),
+        ];
+        files.map(|(name, text)| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            path
+        })
+    }
+
+    fn read_bundle(path: &Path) -> Value {
+        serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn converts_uvtt_with_its_walls_and_scale() {
+        let dir = scratch_dir("convert-uvtt");
+        let [_, _, uvtt] = fixtures(&dir);
+        let output = dir.join("small.json");
+        convert(&uvtt, &output, Some(10.0)).unwrap();
+
+        let bundle = read_bundle(&output);
+        assert_eq!(bundle["format"], "cartographer-views");
+        assert_eq!(
+            bundle["grid"],
+            serde_json::json!({ "cell_size": 70.0, "ft_per_cell": 10.0 })
+        );
+        assert_eq!(bundle["gm"], "data:image/png;base64,iVBORw0KGgo=");
+        assert_eq!(bundle["gm"], bundle["player"]);
+        let walls: WallState = serde_json::from_value(bundle["walls"].clone()).unwrap();
+        assert_eq!(walls.walls.len(), 3);
+        assert!(walls.walls[2].door);
+        assert_eq!(walls.map_lights.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn converts_hex_maps_to_both_views() {
+        let dir = scratch_dir("convert-hexm");
+        let [hexm, ..] = fixtures(&dir);
+        let output = dir.join("valid.json");
+        convert(&hexm, &output, None).unwrap();
+
+        let bundle = read_bundle(&output);
+        assert!(bundle["grid"].get("ft_per_cell").is_none());
+        assert!(bundle.get("walls").is_none());
+        let (gm, player) = (
+            bundle["gm"].as_str().unwrap(),
+            bundle["player"].as_str().unwrap(),
+        );
+        assert!(gm.starts_with("<svg") && player.starts_with("<svg"));
+        assert_ne!(gm, player);
+
+        assert!(convert(&hexm, &output, Some(0.0)).is_err());
+        assert!(convert(&dir.join("map.png"), &output, None)
+            .unwrap_err()
+            .contains(".png"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn validate_fails_only_when_a_map_has_errors() {
+        let dir = scratch_dir("validate");
+        let [hexm, broken, uvtt] = fixtures(&dir);
+        std::fs::write(dir.join("notes.txt"), "not a map").unwrap();
+        assert!(validate(&[hexm, uvtt]));
+        assert!(!validate(std::slice::from_ref(&broken)));
+        // Directories are searched for maps only; the broken one fails them.
+        assert!(!validate(std::slice::from_ref(&dir)));
+        std::fs::remove_file(&broken).unwrap();
+        assert!(validate(std::slice::from_ref(&dir)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,685 @@
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Dice expressions, as typed into the initiative tracker or written in
+// encounter tables:
+//
+//   2d8+1d6+3    terms are added or subtracted
+//   d20, d%      one die; d% is d100
+//   4d6kh3       keep the highest 3 (kl = lowest); 4d6dl1 drops the lowest
+//                (dh = highest)
+//   d20adv       roll the term twice and keep the better total (dis = worse);
+//                "1d20+5 adv" applies it to the first dice term
+//   3d6!         exploding: each max roll adds another die
+//   4dF          fudge dice (-1, 0, +1)
+//
+// Rolls come back with every die so the result can be shown in full. The RNG
+// is a parameter so tests can seed one.
+
+const MAX_DICE: u32 = 1000; // in the whole expression; advantage counts twice
+const MAX_SIDES: u32 = 1_000_000;
+const MAX_EXPLOSIONS: u32 = 100; // per die
+const MAX_LENGTH: usize = 200; // characters; LAN players can send anything
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("can't read dice \"{expression}\": {reason}")]
+pub struct DiceError {
+    pub expression: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Die {
+    Sides(u32),
+    Fudge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keep {
+    Highest(u32),
+    Lowest(u32),
+    // Drops count from the dice actually rolled, so explosions are kept.
+    DropHighest(u32),
+    DropLowest(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Advantage {
+    Advantage,
+    Disadvantage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DiceTerm {
+    count: u32,
+    die: Die,
+    keep: Option<Keep>,
+    explode: bool,
+    advantage: Option<Advantage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Constant(i64),
+    Dice(DiceTerm),
+}
+
+/// A parsed dice expression. Parse with `str::parse`, roll as often as needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiceExpr {
+    terms: Vec<(i64, Term)>, // (sign, term)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DieRoll {
+    pub value: i64,
+    /// False for dice dropped by keep/drop or the losing half of advantage.
+    pub kept: bool,
+    /// Rolled because the die before it exploded.
+    pub exploded: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TermRoll {
+    pub sign: i64, // 1 or -1
+    pub notation: String,
+    pub dice: Vec<DieRoll>, // empty for a constant
+    pub subtotal: i64,      // before the sign
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiceRoll {
+    pub expression: String,
+    pub total: i64,
+    pub terms: Vec<TermRoll>,
+    /// e.g. "4d6kh3 [6, 5, 3, (1)] + 3 = 17"
+    pub breakdown: String,
+}
+
+/// Parses and rolls `expression` in one go.
+pub fn roll<R: Rng + ?Sized>(expression: &str, rng: &mut R) -> Result<DiceRoll, DiceError> {
+    Ok(expression.parse::<DiceExpr>()?.roll(rng))
+}
+
+impl DiceExpr {
+    pub fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> DiceRoll {
+        let terms: Vec<TermRoll> = self
+            .terms
+            .iter()
+            .map(|(sign, term)| term.roll(*sign, rng))
+            .collect();
+        let total = terms.iter().map(|t| t.sign * t.subtotal).sum();
+
+        let mut breakdown = String::new();
+        for (i, term) in terms.iter().enumerate() {
+            match (i, term.sign) {
+                (0, -1) => breakdown.push('-'),
+                (0, _) => {}
+                (_, -1) => breakdown.push_str(" - "),
+                _ => breakdown.push_str(" + "),
+            }
+            breakdown.push_str(&term.notation);
+            if !term.dice.is_empty() {
+                let dice: Vec<String> = term
+                    .dice
+                    .iter()
+                    .map(|d| {
+                        let value = format!("{}{}", d.value, if d.exploded { "!" } else { "" });
+                        if d.kept {
+                            value
+                        } else {
+                            format!("({value})")
+                        }
+                    })
+                    .collect();
+                let _ = write!(breakdown, " [{}]", dice.join(", "));
+            }
+        }
+        let _ = write!(breakdown, " = {total}");
+        DiceRoll {
+            expression: self.to_string(),
+            total,
+            terms,
+            breakdown,
+        }
+    }
+}
+
+impl Term {
+    fn roll<R: Rng + ?Sized>(&self, sign: i64, rng: &mut R) -> TermRoll {
+        let notation = self.to_string();
+        let (dice, subtotal) = match self {
+            Term::Constant(n) => (Vec::new(), *n),
+            Term::Dice(term) => match term.advantage {
+                None => term.roll_once(rng),
+                Some(advantage) => {
+                    let (mut first, a) = term.roll_once(rng);
+                    let (mut second, b) = term.roll_once(rng);
+                    let first_wins = match advantage {
+                        Advantage::Advantage => a >= b,
+                        Advantage::Disadvantage => a <= b,
+                    };
+                    let loser = if first_wins { &mut second } else { &mut first };
+                    loser.iter_mut().for_each(|d| d.kept = false);
+                    first.append(&mut second);
+                    (first, if first_wins { a } else { b })
+                }
+            },
+        };
+        TermRoll {
+            sign,
+            notation,
+            dice,
+            subtotal,
+        }
+    }
+}
+
+impl DiceTerm {
+    fn roll_once<R: Rng + ?Sized>(&self, rng: &mut R) -> (Vec<DieRoll>, i64) {
+        let mut dice = Vec::with_capacity(self.count as usize);
+        for _ in 0..self.count {
+            let mut value = self.roll_die(rng);
+            dice.push(DieRoll {
+                value,
+                kept: true,
+                exploded: false,
+            });
+            if let (true, Die::Sides(sides)) = (self.explode, self.die) {
+                let mut explosions = 0;
+                while value == i64::from(sides) && explosions < MAX_EXPLOSIONS {
+                    value = self.roll_die(rng);
+                    dice.push(DieRoll {
+                        value,
+                        kept: true,
+                        exploded: true,
+                    });
+                    explosions += 1;
+                }
+            }
+        }
+        if let Some(keep) = self.keep {
+            // Stable sort of indices, so equal dice drop right to left.
+            let mut order: Vec<usize> = (0..dice.len()).collect();
+            let rolled = dice.len() as u32;
+            let (n, highest) = match keep {
+                Keep::Highest(n) => (n, true),
+                Keep::Lowest(n) => (n, false),
+                Keep::DropHighest(n) => (rolled.saturating_sub(n), false),
+                Keep::DropLowest(n) => (rolled.saturating_sub(n), true),
+            };
+            order.sort_by_key(|&i| {
+                if highest {
+                    -dice[i].value
+                } else {
+                    dice[i].value
+                }
+            });
+            for &i in order.iter().skip(n as usize) {
+                dice[i].kept = false;
+            }
+        }
+        let subtotal = dice.iter().filter(|d| d.kept).map(|d| d.value).sum();
+        (dice, subtotal)
+    }
+
+    fn roll_die<R: Rng + ?Sized>(&self, rng: &mut R) -> i64 {
+        match self.die {
+            Die::Sides(sides) => i64::from(rng.random_range(1..=sides)),
+            Die::Fudge => rng.random_range(-1..=1),
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let term = match self {
+            Term::Constant(n) => return write!(f, "{n}"),
+            Term::Dice(term) => term,
+        };
+        write!(f, "{}d", term.count)?;
+        match term.die {
+            Die::Sides(sides) => write!(f, "{sides}")?,
+            Die::Fudge => f.write_str("F")?,
+        }
+        if term.explode {
+            f.write_str("!")?;
+        }
+        match term.keep {
+            Some(Keep::Highest(n)) => write!(f, "kh{n}")?,
+            Some(Keep::Lowest(n)) => write!(f, "kl{n}")?,
+            Some(Keep::DropHighest(n)) => write!(f, "dh{n}")?,
+            Some(Keep::DropLowest(n)) => write!(f, "dl{n}")?,
+            None => {}
+        }
+        match term.advantage {
+            Some(Advantage::Advantage) => f.write_str("adv"),
+            Some(Advantage::Disadvantage) => f.write_str("dis"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for DiceExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (sign, term)) in self.terms.iter().enumerate() {
+            match (i, sign) {
+                (0, -1) => f.write_str("-")?,
+                (0, _) => {}
+                (_, -1) => f.write_str("-")?,
+                _ => f.write_str("+")?,
+            }
+            write!(f, "{term}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DiceExpr {
+    type Err = DiceError;
+
+    fn from_str(expression: &str) -> Result<Self, DiceError> {
+        let fail = |reason: &str| DiceError {
+            expression: expression.trim().to_string(),
+            reason: reason.to_string(),
+        };
+        if expression.trim().chars().count() > MAX_LENGTH {
+            // Not echoed back in full.
+            let start: String = expression.trim().chars().take(20).collect();
+            return Err(DiceError {
+                expression: format!("{start}..."),
+                reason: format!("it's longer than {MAX_LENGTH} characters"),
+            });
+        }
+        let lower = expression.trim().to_lowercase();
+
+        // "1d20+5 adv": advantage on the first dice term.
+        let mut trailing = None;
+        let mut body = lower.as_str();
+        for (word, advantage) in [
+            ("advantage", Advantage::Advantage),
+            ("adv", Advantage::Advantage),
+            ("disadvantage", Advantage::Disadvantage),
+            ("dis", Advantage::Disadvantage),
+        ] {
+            if let Some(rest) = body
+                .strip_suffix(word)
+                .filter(|rest| rest.ends_with(char::is_whitespace))
+            {
+                body = rest;
+                trailing = Some(advantage);
+                break;
+            }
+        }
+
+        let chars: Vec<char> = body.chars().filter(|c| !c.is_whitespace()).collect();
+        if chars.is_empty() {
+            return Err(fail("it's empty"));
+        }
+        let mut parser = Parser {
+            chars: &chars,
+            pos: 0,
+        };
+        let mut terms = Vec::new();
+        loop {
+            let sign = match parser.peek() {
+                Some('-') => {
+                    parser.pos += 1;
+                    -1
+                }
+                Some('+') => {
+                    parser.pos += 1;
+                    1
+                }
+                _ if terms.is_empty() => 1,
+                Some(c) => return Err(fail(&format!("unexpected '{c}'"))),
+                None => break,
+            };
+            terms.push((sign, parser.term().map_err(|reason| fail(&reason))?));
+            if parser.peek().is_none() {
+                break;
+            }
+        }
+
+        if let Some(advantage) = trailing {
+            let first = terms.iter_mut().find_map(|(_, term)| match term {
+                Term::Dice(dice) => Some(dice),
+                Term::Constant(_) => None,
+            });
+            match first {
+                Some(dice) if dice.advantage.is_none() => dice.advantage = Some(advantage),
+                Some(_) => return Err(fail("advantage is given twice")),
+                None => return Err(fail("advantage needs a dice term")),
+            }
+        }
+        let dice: u32 = terms
+            .iter()
+            .map(|(_, term)| match term {
+                Term::Dice(dice) if dice.advantage.is_some() => dice.count * 2,
+                Term::Dice(dice) => dice.count,
+                Term::Constant(_) => 0,
+            })
+            .sum();
+        if dice > MAX_DICE {
+            return Err(fail(&format!("roll at most {MAX_DICE} dice in all")));
+        }
+        Ok(DiceExpr { terms })
+    }
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        let n = word.chars().count();
+        if self.chars.len() >= self.pos + n
+            && self.chars[self.pos..self.pos + n]
+                .iter()
+                .copied()
+                .eq(word.chars())
+        {
+            self.pos += n;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn number(&mut self) -> Result<Option<u32>, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        if digits.is_empty() {
+            return Ok(None);
+        }
+        match digits.parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => Err(format!("{digits} is too large")),
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        let count = self.number()?;
+        if !self.eat("d") {
+            return match count {
+                Some(n) => Ok(Term::Constant(i64::from(n))),
+                None => Err(match self.peek() {
+                    Some(c) => format!("unexpected '{c}'"),
+                    None => "it ends with a sign".into(),
+                }),
+            };
+        }
+        let count = count.unwrap_or(1);
+        if count == 0 || count > MAX_DICE {
+            return Err(format!("roll between 1 and {MAX_DICE} dice"));
+        }
+        let die = if self.eat("%") {
+            Die::Sides(100)
+        } else if self.eat("f") {
+            Die::Fudge
+        } else {
+            match self.number()? {
+                Some(sides) if (1..=MAX_SIDES).contains(&sides) => Die::Sides(sides),
+                Some(_) => return Err(format!("dice have between 1 and {MAX_SIDES} sides")),
+                None => return Err("a 'd' needs a number of sides, '%' or 'F'".into()),
+            }
+        };
+
+        let mut term = DiceTerm {
+            count,
+            die,
+            keep: None,
+            explode: false,
+            advantage: None,
+        };
+        loop {
+            if self.eat("!") {
+                if term.explode {
+                    return Err("'!' is given twice".into());
+                }
+                if !matches!(die, Die::Sides(2..)) {
+                    return Err("only dice with 2 or more sides can explode".into());
+                }
+                term.explode = true;
+            } else if self.eat("adv") || self.eat("dis") {
+                if term.advantage.is_some() {
+                    return Err("advantage is given twice".into());
+                }
+                let word: String = self.chars[self.pos - 3..self.pos].iter().collect();
+                term.advantage = Some(if word == "adv" {
+                    Advantage::Advantage
+                } else {
+                    Advantage::Disadvantage
+                });
+            } else if let Some(modifier) = ["kh", "kl", "dh", "dl", "k"]
+                .into_iter()
+                .find(|m| self.eat(m))
+            {
+                if term.keep.is_some() {
+                    return Err("only one keep or drop per term".into());
+                }
+                let n = self
+                    .number()?
+                    .ok_or_else(|| format!("'{modifier}' needs a number"))?;
+                if n > count {
+                    return Err(format!(
+                        "can't {} {n} of {count} dice",
+                        if modifier.starts_with('k') {
+                            "keep"
+                        } else {
+                            "drop"
+                        }
+                    ));
+                }
+                term.keep = Some(match modifier {
+                    "kh" | "k" => Keep::Highest(n),
+                    "kl" => Keep::Lowest(n),
+                    "dh" => Keep::DropHighest(n),
+                    _ => Keep::DropLowest(n),
+                });
+            } else {
+                break;
+            }
+        }
+        Ok(Term::Dice(term))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(42)
+    }
+
+    #[test]
+    fn parses_and_normalises() {
+        let cases = [
+            ("d20", "1d20"),
+            ("2d8 + 1d6 + 3", "2d8+1d6+3"),
+            ("1D20-1", "1d20-1"),
+            ("d%", "1d100"),
+            ("4d6kh3", "4d6kh3"),
+            ("4d6dl1", "4d6dl1"),
+            ("4d6k3", "4d6kh3"),
+            ("2d20dh1", "2d20dh1"),
+            ("3d6!", "3d6!"),
+            ("4dF", "4dF"),
+            ("d20adv+5", "1d20adv+5"),
+            ("1d20+5 adv", "1d20adv+5"),
+            ("1d20 disadvantage", "1d20dis"),
+            ("-1d4+2", "-1d4+2"),
+            ("7", "7"),
+        ];
+        for (input, normal) in cases {
+            assert_eq!(
+                input.parse::<DiceExpr>().unwrap().to_string(),
+                normal,
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_nonsense() {
+        for input in [
+            "",
+            "d",
+            "2d",
+            "1d20+",
+            "1d20++1",
+            "abc",
+            "0d6",
+            "1001d6",
+            "1d6kh2",
+            "3d1!",
+            "1d6!!",
+            "4d6kh3kl1",
+            "5 adv",
+            "1d6x",
+            "99999999999",
+            "1d99999999999",
+            "600d6+600d6",
+            "600d6adv",
+        ] {
+            assert!(
+                input.parse::<DiceExpr>().is_err(),
+                "{input} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn caps_what_one_expression_can_ask_for() {
+        assert!("500d6+500d6".parse::<DiceExpr>().is_ok());
+        assert!("500d6+500d6+1d6".parse::<DiceExpr>().is_err());
+        let long = format!("1d20{}", " ".repeat(MAX_LENGTH));
+        assert!(
+            long.parse::<DiceExpr>().is_ok(),
+            "trailing space is trimmed"
+        );
+        assert!(format!("{long}+1").parse::<DiceExpr>().is_err());
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let mut rng = rng();
+        for _ in 0..200 {
+            let total = roll("2d8+1d6+3", &mut rng).unwrap().total;
+            assert!((6..=25).contains(&total));
+            let fudge = roll("4dF", &mut rng).unwrap().total;
+            assert!((-4..=4).contains(&fudge));
+            let keep = roll("4d6kh3", &mut rng).unwrap();
+            assert_eq!(keep.terms[0].dice.len(), 4);
+            assert_eq!(keep.terms[0].dice.iter().filter(|d| d.kept).count(), 3);
+            assert!((3..=18).contains(&keep.total));
+        }
+    }
+
+    #[test]
+    fn seeded_rolls_repeat() {
+        let a: Vec<_> = (0..5)
+            .scan(rng(), |rng, _| Some(roll("3d6!+1d4", rng).unwrap()))
+            .collect();
+        let b: Vec<_> = (0..5)
+            .scan(rng(), |rng, _| Some(roll("3d6!+1d4", rng).unwrap()))
+            .collect();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn keep_drop_and_advantage_pick_the_right_dice() {
+        let mut rng = rng();
+        for _ in 0..100 {
+            let roll = roll("4d6dl1", &mut rng).unwrap();
+            let dice = &roll.terms[0].dice;
+            let dropped = dice.iter().find(|d| !d.kept).unwrap();
+            assert!(dice.iter().all(|d| d.value >= dropped.value));
+
+            let adv = super::roll("d20adv", &mut rng).unwrap();
+            let [a, b] = [&adv.terms[0].dice[0], &adv.terms[0].dice[1]];
+            assert_eq!(adv.total, a.value.max(b.value));
+            assert!(a.kept != b.kept);
+            let dis = super::roll("d20 dis", &mut rng).unwrap();
+            assert_eq!(
+                dis.total,
+                dis.terms[0].dice.iter().map(|d| d.value).min().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn exploding_dice_add_up() {
+        let mut rng = rng();
+        let mut exploded = false;
+        for _ in 0..200 {
+            let roll = roll("2d4!", &mut rng).unwrap();
+            let dice = &roll.terms[0].dice;
+            assert_eq!(roll.total, dice.iter().map(|d| d.value).sum::<i64>());
+            for pair in dice.windows(2) {
+                if pair[1].exploded {
+                    assert_eq!(pair[0].value, 4);
+                    exploded = true;
+                }
+            }
+        }
+        assert!(exploded);
+    }
+
+    #[test]
+    fn dropping_keeps_explosions() {
+        let mut rng = rng();
+        let mut exploded = 0;
+        for _ in 0..200 {
+            let roll = roll("4d6!dl1", &mut rng).unwrap();
+            let dice = &roll.terms[0].dice;
+            exploded += dice.iter().filter(|d| d.exploded).count();
+            let dropped: Vec<_> = dice.iter().filter(|d| !d.kept).collect();
+            assert_eq!(dropped.len(), 1, "{}", roll.breakdown);
+            assert!(dice.iter().all(|d| d.value >= dropped[0].value));
+            assert_eq!(
+                roll.total,
+                dice.iter().map(|d| d.value).sum::<i64>() - dropped[0].value
+            );
+        }
+        assert!(exploded > 0);
+    }
+
+    #[test]
+    fn breakdown_shows_every_die() {
+        let roll = roll("4d6kh3-1", &mut rng()).unwrap();
+        let dice = &roll.terms[0].dice;
+        let shown: Vec<String> = dice
+            .iter()
+            .map(|d| {
+                if d.kept {
+                    d.value.to_string()
+                } else {
+                    format!("({})", d.value)
+                }
+            })
+            .collect();
+        assert_eq!(
+            roll.breakdown,
+            format!("4d6kh3 [{}] - 1 = {}", shown.join(", "), roll.total)
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::dice::DiceExpr;
+use crate::hexm::{self, HexMapFile};
+
+// Random encounter tables for hex crawls. Tables live in an `encounters`
+// folder next to the map, as any number of `.json`, `.yaml` or `.yml` files:
+//
+//   encounters:                 # which table a hex rolls on
+//     - territory: borderlands  # either key may be left out to match any
+//       terrain: forest
+//       chance: 2 in 6          # optional; no chance = always an encounter
+//       table: forest
+//   tables:
+//     forest:
+//       - { weight: 3, name: Wolves, count: 2d6 }
+//       - { weight: 1, table: forest-rare }   # roll again on another table
+//     forest-rare:
+//       - { name: Dryad, note: Guards the grove at 04.07 }
+//
+// Files are read in name order and merged; a hex uses the rule matching the
+// most of its terrain and territory (the first one on a tie).
+
+pub const FOLDER: &str = "encounters";
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncounterError {
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{path}: {message}")]
+    Parse { path: String, message: String },
+    #[error("table \"{0}\" is defined in more than one file")]
+    DuplicateTable(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("hex {0} is not on the map")]
+    OffMap(String),
+    #[error("no encounter table for {0}; add one under {FOLDER}/ next to the map")]
+    NoRule(String),
+    #[error("tables nest more than {MAX_DEPTH} deep at \"{0}\"")]
+    TooDeep(String),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TableFile {
+    #[serde(default)]
+    pub encounters: Vec<Rule>,
+    #[serde(default)]
+    pub tables: BTreeMap<String, Vec<Entry>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub terrain: Option<String>,
+    #[serde(default)]
+    pub territory: Option<String>,
+    #[serde(default)]
+    pub chance: Option<String>, // "N in M"
+    pub table: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Entry {
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Number appearing, as dice (`2d6`, `1d4+1`, see `dice.rs`) or a plain
+    /// number.
+    #[serde(default, deserialize_with = "dice_or_number")]
+    pub count: Option<String>,
+    /// Roll on this table instead.
+    #[serde(default)]
+    pub table: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+// `count: 2` as well as `count: 2d6`.
+fn dice_or_number<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(i64),
+        Dice(String),
+    }
+    Ok(
+        Option::<Raw>::deserialize(deserializer)?.map(|raw| match raw {
+            Raw::Number(n) => n.to_string(),
+            Raw::Dice(dice) => dice,
+        }),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChanceRoll {
+    pub roll: u32,
+    pub target: u32, // encounter on `roll <= target`
+    pub sides: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Encounter {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count_dice: Option<String>,
+    /// Tables rolled on, outermost first.
+    pub tables: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncounterRoll {
+    pub hex: (i64, i64),
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terrain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub territory: Option<String>,
+    pub table: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chance: Option<ChanceRoll>,
+    /// `None` when the chance roll came up empty.
+    pub encounter: Option<Encounter>,
+    /// One line for the travel log.
+    pub summary: String,
+}
+
+/// Every table file in a campaign's `encounters` folder, merged.
+#[derive(Debug, Clone, Default)]
+pub struct EncounterTables {
+    rules: Vec<Rule>,
+    tables: BTreeMap<String, Vec<Entry>>,
+}
+
+/// The `encounters` folder for the map at `map_path`.
+pub fn folder_for(map_path: &Path) -> PathBuf {
+    map_path.parent().unwrap_or(Path::new(".")).join(FOLDER)
+}
+
+impl EncounterTables {
+    /// Loads and checks every table file in `dir`. A missing folder is no
+    /// tables at all.
+    pub fn load_dir(dir: &Path) -> Result<Self, EncounterError> {
+        let io = |e| EncounterError::Io {
+            path: dir.display().to_string(),
+            source: e,
+        };
+        let mut files = Vec::new();
+        match std::fs::read_dir(dir) {
+            Ok(entries) => {
+                for entry in entries {
+                    let path = entry.map_err(io)?.path();
+                    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+                    if matches!(ext.as_deref(), Some("json" | "yaml" | "yml")) {
+                        files.push(path);
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(io(e)),
+        }
+        files.sort();
+
+        let mut parsed = Vec::new();
+        for path in files {
+            let text = std::fs::read_to_string(&path).map_err(|e| EncounterError::Io {
+                path: path.display().to_string(),
+                source: e,
+            })?;
+            let is_json = path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+            let file = if is_json {
+                serde_json::from_str(&text).map_err(|e| e.to_string())
+            } else {
+                serde_yaml::from_str(&text).map_err(|e| e.to_string())
+            };
+            parsed.push(file.map_err(|message| EncounterError::Parse {
+                path: path.display().to_string(),
+                message,
+            })?);
+        }
+        Self::from_files(parsed)
+    }
+
+    pub fn from_files(files: impl IntoIterator<Item = TableFile>) -> Result<Self, EncounterError> {
+        let mut merged = EncounterTables::default();
+        for file in files {
+            merged.rules.extend(file.encounters);
+            for (name, entries) in file.tables {
+                if merged.tables.insert(name.clone(), entries).is_some() {
+                    return Err(EncounterError::DuplicateTable(name));
+                }
+            }
+        }
+        merged.check()?;
+        Ok(merged)
+    }
+
+    // Everything that would otherwise only fail mid-roll.
+    fn check(&self) -> Result<(), EncounterError> {
+        let invalid = |message: String| Err(EncounterError::Invalid(message));
+        for rule in &self.rules {
+            if !self.tables.contains_key(&rule.table) {
+                return invalid(format!(
+                    "encounter rule uses unknown table \"{}\"",
+                    rule.table
+                ));
+            }
+            if let Some(chance) = &rule.chance {
+                if parse_chance(chance).is_none() {
+                    return invalid(format!("chance \"{chance}\" should look like \"2 in 6\""));
+                }
+            }
+        }
+        for (name, entries) in &self.tables {
+            match entries
+                .iter()
+                .try_fold(0u32, |total, e| total.checked_add(e.weight))
+            {
+                Some(0) => return invalid(format!("table \"{name}\" has no entries with weight")),
+                None => {
+                    return invalid(format!(
+                        "table \"{name}\" has weights adding up to more than {}",
+                        u32::MAX
+                    ))
+                }
+                Some(_) => {}
+            }
+            for entry in entries {
+                match (&entry.name, &entry.table) {
+                    (_, Some(table)) if !self.tables.contains_key(table) => {
+                        return invalid(format!(
+                            "table \"{name}\" refers to unknown table \"{table}\""
+                        ));
+                    }
+                    (None, None) => {
+                        return invalid(format!(
+                            "table \"{name}\" has an entry with neither name nor table"
+                        ))
+                    }
+                    _ => {}
+                }
+                if let Err(e) = entry
+                    .count
+                    .as_deref()
+                    .map(str::parse::<DiceExpr>)
+                    .transpose()
+                {
+                    return invalid(format!("table \"{name}\": {e}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The rule for a hex: the one matching the most of its terrain and
+    /// territory, the earliest on a tie.
+    fn rule_for(&self, terrain: Option<&str>, territory: Option<&str>) -> Option<&Rule> {
+        let matches = |want: &Option<String>, have: Option<&str>| {
+            want.as_deref().is_none_or(|w| Some(w) == have)
+        };
+        self.rules
+            .iter()
+            .filter(|r| matches(&r.terrain, terrain) && matches(&r.territory, territory))
+            .enumerate()
+            .max_by_key(|(i, r)| {
+                (
+                    r.terrain.is_some() as u8 + r.territory.is_some() as u8,
+                    std::cmp::Reverse(*i),
+                )
+            })
+            .map(|(_, r)| r)
+    }
+
+    /// Rolls for an encounter in a hex of this terrain and territory.
+    pub fn roll<R: Rng + ?Sized>(
+        &self,
+        hex: (i64, i64),
+        terrain: Option<&str>,
+        territory: Option<&str>,
+        rng: &mut R,
+    ) -> Result<EncounterRoll, EncounterError> {
+        let Some(rule) = self.rule_for(terrain, territory) else {
+            let what = [terrain, territory]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" / ");
+            return Err(EncounterError::NoRule(if what.is_empty() {
+                hexm::coord(hex.0, hex.1)
+            } else {
+                what
+            }));
+        };
+        let chance = rule
+            .chance
+            .as_deref()
+            .and_then(parse_chance)
+            .map(|(target, sides)| ChanceRoll {
+                roll: rng.random_range(1..=sides),
+                target,
+                sides,
+            });
+        let encounter = match &chance {
+            Some(c) if c.roll > c.target => None,
+            _ => Some(self.roll_table(&rule.table, rng)?),
+        };
+
+        let summary = match (&encounter, &chance) {
+            (Some(e), _) => {
+                let count = e.count.map(|n| format!("{n} × ")).unwrap_or_default();
+                let note = e
+                    .note
+                    .as_deref()
+                    .map(|n| format!(" ({n})"))
+                    .unwrap_or_default();
+                format!("{count}{}{note}", e.name)
+            }
+            (None, Some(c)) => format!(
+                "No encounter (rolled {} on d{}, needed {} or less)",
+                c.roll, c.sides, c.target
+            ),
+            (None, None) => unreachable!("no chance roll means an encounter"),
+        };
+        Ok(EncounterRoll {
+            hex,
+            terrain: terrain.map(Into::into),
+            territory: territory.map(Into::into),
+            table: rule.table.clone(),
+            chance,
+            encounter,
+            summary,
+        })
+    }
+
+    fn roll_table<R: Rng + ?Sized>(
+        &self,
+        table: &str,
+        rng: &mut R,
+    ) -> Result<Encounter, EncounterError> {
+        let mut path = vec![table.to_string()];
+        loop {
+            let name = path.last().expect("path starts non-empty");
+            let entries = &self.tables[name];
+            let total: u32 = entries.iter().map(|e| e.weight).sum(); // fits: checked on load
+            let mut pick = rng.random_range(0..total);
+            let entry = entries
+                .iter()
+                .find(|e| {
+                    let hit = pick < e.weight;
+                    pick = pick.saturating_sub(e.weight);
+                    hit
+                })
+                .expect("pick is below the total weight");
+
+            if let Some(next) = &entry.table {
+                if path.len() >= MAX_DEPTH {
+                    return Err(EncounterError::TooDeep(next.clone()));
+                }
+                path.push(next.clone());
+                continue;
+            }
+            let count = entry.count.as_deref().map(|dice| {
+                let dice: DiceExpr = dice.parse().expect("checked on load");
+                dice.roll(rng).total.max(0) as u32
+            });
+            return Ok(Encounter {
+                name: entry.name.clone().expect("checked on load"),
+                count,
+                count_dice: entry.count.clone(),
+                tables: path,
+                note: entry.note.clone(),
+            });
+        }
+    }
+}
+
+/// Loads the tables next to `map_path` and rolls for hex `hex` of `map`.
+pub fn roll_for_hex<R: Rng + ?Sized>(
+    map_path: &Path,
+    map: &HexMapFile,
+    hex: (i64, i64),
+    rng: &mut R,
+) -> Result<EncounterRoll, EncounterError> {
+    if !map.on_grid(hex.0, hex.1) {
+        return Err(EncounterError::OffMap(hexm::coord(hex.0, hex.1)));
+    }
+    let cell = map.hexes.iter().find(|h| (h.col, h.row) == hex);
+    let terrain = cell
+        .and_then(|c| c.terrain.as_deref())
+        .or(map.default_terrain.as_deref());
+    let territory = cell.and_then(|c| c.territory.as_deref());
+    EncounterTables::load_dir(&folder_for(map_path))?.roll(hex, terrain, territory, rng)
+}
+
+// "2 in 6" -> (2, 6).
+fn parse_chance(text: &str) -> Option<(u32, u32)> {
+    let (target, sides) = text.split_once(" in ")?;
+    let (target, sides) = (target.trim().parse().ok()?, sides.trim().parse().ok()?);
+    (sides > 0 && target <= sides).then_some((target, sides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const TABLES: &str = r#"
+encounters:
+  - { table: wilderness }
+  - { territory: borderlands, chance: 2 in 6, table: borderlands }
+  - { terrain: forest, territory: borderlands, table: forest }
+tables:
+  wilderness:
+    - { name: Bandits, count: 3d6 }
+  borderlands:
+    - { name: Patrol, count: 2 }
+  forest:
+    - { weight: 0, name: Never }
+    - { weight: 5, table: forest-rare }
+  forest-rare:
+    - { name: Dryad, note: guards the grove }
+"#;
+
+    fn tables() -> EncounterTables {
+        EncounterTables::from_files([serde_yaml::from_str(TABLES).unwrap()]).unwrap()
+    }
+
+    #[test]
+    fn most_specific_rule_wins() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let roll = tables()
+            .roll((1, 1), Some("forest"), Some("borderlands"), &mut rng)
+            .unwrap();
+        let encounter = roll.encounter.unwrap();
+        assert_eq!(encounter.name, "Dryad");
+        assert_eq!(encounter.tables, vec!["forest", "forest-rare"]);
+        assert_eq!(roll.summary, "Dryad (guards the grove)");
+
+        let roll = tables()
+            .roll((1, 1), Some("hills"), Some("unsettled"), &mut rng)
+            .unwrap();
+        assert_eq!(roll.table, "wilderness");
+        let count = roll.encounter.unwrap().count.unwrap();
+        assert!((3..=18).contains(&count));
+    }
+
+    #[test]
+    fn chance_roll_can_come_up_empty() {
+        let tables = tables();
+        let mut rng = StdRng::seed_from_u64(1);
+        let rolls: Vec<_> = (0..60)
+            .map(|_| {
+                tables
+                    .roll((2, 3), None, Some("borderlands"), &mut rng)
+                    .unwrap()
+            })
+            .collect();
+        let (hits, misses): (Vec<_>, Vec<_>) = rolls.iter().partition(|r| r.encounter.is_some());
+        assert!(!hits.is_empty() && !misses.is_empty());
+        assert!(hits
+            .iter()
+            .all(|r| r.chance.as_ref().unwrap().roll <= 2 && r.summary == "2 × Patrol"));
+        assert!(misses[0].summary.starts_with("No encounter (rolled "));
+    }
+
+    #[test]
+    fn weights_can_use_the_whole_range() {
+        let yaml =
+            "tables: { a: [{ weight: 4294967294, name: Common }, { weight: 1, name: Rare }] }";
+        let tables = EncounterTables::from_files([serde_yaml::from_str(yaml).unwrap()]).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(tables.roll_table("a", &mut rng).unwrap().name, "Common");
+    }
+
+    #[test]
+    fn bad_tables_fail_on_load() {
+        let load = |yaml: &str| EncounterTables::from_files([serde_yaml::from_str(yaml).unwrap()]);
+        assert!(matches!(
+            load("encounters: [{ table: missing }]"),
+            Err(EncounterError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("tables: { a: [{ table: b }] }"),
+            Err(EncounterError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("tables: { a: [{ name: X, count: 2x6 }] }"),
+            Err(EncounterError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("encounters: [{ chance: 7 in 6, table: a }]\ntables: { a: [{ name: X }] }"),
+            Err(EncounterError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("tables: { a: [{ weight: 4294967295, name: X }, { weight: 1, name: Y }] }"),
+            Err(EncounterError::Invalid(_))
+        ));
+        let loops = load("tables: { a: [{ table: a }] }").unwrap();
+        assert!(matches!(
+            loops.roll_table("a", &mut StdRng::seed_from_u64(0)),
+            Err(EncounterError::TooDeep(_))
+        ));
+    }
+}
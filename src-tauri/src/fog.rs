@@ -0,0 +1,483 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+// Fog of war as a packed bitset (one bit per grid cell, set = fogged,
+// row-major). On the wire and on disk it's `{ cols, rows, rle }`, where
+// `rle` is base64 of LEB128 varint run lengths alternating fogged / revealed
+// cells, starting with a (possibly empty) fogged run. A fully fogged 200x200
+// grid is 4 bytes of runs instead of 200 KB of `true,`.
+//
+// Only save files hold packed fog. The webview keeps it unpacked, as
+// `{ cols, rows, cells: boolean[][] }` (`FogState` here and in
+// `src/types.ts`); that shape is accepted when deserializing too, so pre-v2
+// saves and fog sent by the webview both read transparently.
+
+#[derive(Debug, thiserror::Error)]
+pub enum FogError {
+    #[error("fog has neither `rle` nor `cells`")]
+    Missing,
+    #[error("fog RLE is not valid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("fog RLE ends in the middle of a run length")]
+    Truncated,
+    #[error("fog RLE covers {actual} cells, expected {expected}")]
+    LengthMismatch { expected: u64, actual: u64 },
+    #[error("fog grid of {cols}x{rows} cells is larger than the {MAX_CELLS} allowed")]
+    TooLarge { cols: u32, rows: u32 },
+}
+
+/// The most cells a fog grid may have (2000x2000), checked before anything
+/// is allocated: `cols` and `rows` come from save files and the webview.
+pub const MAX_CELLS: u64 = 4_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "FogRepr", into = "FogRepr")]
+pub struct Fog {
+    cols: u32,
+    rows: u32,
+    bits: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FogRepr {
+    cols: u32,
+    rows: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rle: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cells: Option<Vec<Vec<bool>>>,
+}
+
+impl TryFrom<FogRepr> for Fog {
+    type Error = FogError;
+
+    fn try_from(repr: FogRepr) -> Result<Self, FogError> {
+        match (repr.rle, repr.cells) {
+            (Some(rle), _) => Fog::decode_rle(repr.cols, repr.rows, &rle),
+            (None, Some(cells)) => Fog::from_cells(repr.cols, repr.rows, &cells),
+            (None, None) => Err(FogError::Missing),
+        }
+    }
+}
+
+impl From<Fog> for FogRepr {
+    fn from(fog: Fog) -> Self {
+        FogRepr {
+            cols: fog.cols,
+            rows: fog.rows,
+            rle: Some(fog.encode_rle()),
+            cells: None,
+        }
+    }
+}
+
+/// Fog unpacked, as the webview keeps it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FogState {
+    pub cols: u32,
+    pub rows: u32,
+    pub cells: Vec<Vec<bool>>,
+}
+
+impl From<&Fog> for FogState {
+    fn from(fog: &Fog) -> Self {
+        FogState {
+            cols: fog.cols,
+            rows: fog.rows,
+            cells: fog.to_cells(),
+        }
+    }
+}
+
+impl Fog {
+    pub fn new(cols: u32, rows: u32, fogged: bool) -> Result<Self, FogError> {
+        check_size(cols, rows)?;
+        let len = cols as usize * rows as usize;
+        let mut fog = Fog {
+            cols,
+            rows,
+            bits: vec![0; len.div_ceil(64)],
+        };
+        if fogged {
+            fog.fill_range(0, len, true);
+        }
+        Ok(fog)
+    }
+
+    /// From the `boolean[][]` layout. Missing cells count as fogged.
+    pub fn from_cells(cols: u32, rows: u32, cells: &[Vec<bool>]) -> Result<Self, FogError> {
+        let mut fog = Fog::new(cols, rows, true)?;
+        for (row, values) in cells.iter().enumerate().take(rows as usize) {
+            for (col, &fogged) in values.iter().enumerate().take(cols as usize) {
+                fog.set(row as u32, col as u32, fogged);
+            }
+        }
+        Ok(fog)
+    }
+
+    pub fn to_cells(&self) -> Vec<Vec<bool>> {
+        (0..self.rows)
+            .map(|row| (0..self.cols).map(|col| self.is_fogged(row, col)).collect())
+            .collect()
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    fn index(&self, row: u32, col: u32) -> Option<usize> {
+        (row < self.rows && col < self.cols)
+            .then(|| row as usize * self.cols as usize + col as usize)
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        self.bits[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    /// Cells outside the grid count as fogged.
+    pub fn is_fogged(&self, row: u32, col: u32) -> bool {
+        self.index(row, col).is_none_or(|i| self.bit(i))
+    }
+
+    /// Out-of-range cells are ignored.
+    pub fn set(&mut self, row: u32, col: u32, fogged: bool) {
+        if let Some(i) = self.index(row, col) {
+            if fogged {
+                self.bits[i / 64] |= 1 << (i % 64);
+            } else {
+                self.bits[i / 64] &= !(1 << (i % 64));
+            }
+        }
+    }
+
+    fn fill_range(&mut self, start: usize, end: usize, fogged: bool) {
+        for i in start..end {
+            if fogged {
+                self.bits[i / 64] |= 1 << (i % 64);
+            } else {
+                self.bits[i / 64] &= !(1 << (i % 64));
+            }
+        }
+    }
+
+    pub fn encode_rle(&self) -> String {
+        let len = self.cols as usize * self.rows as usize;
+        let mut bytes = Vec::new();
+        let mut current = true; // runs start with fogged
+        let mut run = 0u64;
+        for i in 0..len {
+            if self.bit(i) != current {
+                write_varint(&mut bytes, run);
+                current = !current;
+                run = 0;
+            }
+            run += 1;
+        }
+        write_varint(&mut bytes, run);
+        BASE64.encode(bytes)
+    }
+
+    pub fn decode_rle(cols: u32, rows: u32, rle: &str) -> Result<Self, FogError> {
+        check_size(cols, rows)?;
+        let bytes = BASE64.decode(rle)?;
+        let expected = cols as u64 * rows as u64;
+        // Read every run and check they add up before allocating the grid.
+        let mut runs = Vec::new();
+        let mut pos = 0usize;
+        let mut total = 0u64;
+        while pos < bytes.len() {
+            let run = read_varint(&bytes, &mut pos)?;
+            total = total.saturating_add(run);
+            if total > expected {
+                return Err(FogError::LengthMismatch {
+                    expected,
+                    actual: total,
+                });
+            }
+            runs.push(run);
+        }
+        if total != expected {
+            return Err(FogError::LengthMismatch {
+                expected,
+                actual: total,
+            });
+        }
+        let mut fog = Fog::new(cols, rows, false)?;
+        let mut at = 0usize;
+        for (i, run) in runs.into_iter().enumerate() {
+            let end = at + run as usize;
+            // Runs alternate fogged / revealed, starting fogged.
+            if i % 2 == 0 {
+                fog.fill_range(at, end, true);
+            }
+            at = end;
+        }
+        Ok(fog)
+    }
+
+    /// Every cell between two corners (inclusive, in either order).
+    pub fn rect(&mut self, from: (i64, i64), to: (i64, i64), reveal: bool) {
+        let (rows, cols) = (self.rows as i64, self.cols as i64);
+        let (r0, r1) = (from.0.min(to.0).max(0), from.0.max(to.0).min(rows - 1));
+        let (c0, c1) = (from.1.min(to.1).max(0), from.1.max(to.1).min(cols - 1));
+        for row in r0..=r1 {
+            for col in c0..=c1 {
+                self.set(row as u32, col as u32, !reveal);
+            }
+        }
+    }
+
+    /// Every cell within `radius` cells of `center` along both axes: the
+    /// square the webview's brush (`modifyFog`) paints.
+    pub fn brush(&mut self, center: (i64, i64), radius: i64, reveal: bool) {
+        let radius = radius.max(0);
+        self.rect(
+            (center.0 - radius, center.1 - radius),
+            (center.0 + radius, center.1 + radius),
+            reveal,
+        );
+    }
+
+    /// Every cell whose centre lies inside the polygon. `points` are in cell
+    /// units: cell (row, col) covers x in [col, col + 1), y in [row, row + 1).
+    pub fn polygon(&mut self, points: &[(f64, f64)], reveal: bool) {
+        if points.len() < 3 {
+            return;
+        }
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+        for &(x, y) in points {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        let rows =
+            (min_y.floor().max(0.0) as i64)..=(max_y.ceil().min(self.rows as f64 - 1.0) as i64);
+        for row in rows {
+            let cols =
+                (min_x.floor().max(0.0) as i64)..=(max_x.ceil().min(self.cols as f64 - 1.0) as i64);
+            for col in cols {
+                if point_in_polygon(col as f64 + 0.5, row as f64 + 0.5, points) {
+                    self.set(row as u32, col as u32, !reveal);
+                }
+            }
+        }
+    }
+}
+
+// Even-odd rule, as `pointInPolygon` in `src/hexmap.ts`.
+fn point_in_polygon(x: f64, y: f64, points: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+    let mut j = points.len() - 1;
+    for (i, &(xi, yi)) in points.iter().enumerate() {
+        let (xj, yj) = points[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn check_size(cols: u32, rows: u32) -> Result<(), FogError> {
+    if cols as u64 * rows as u64 > MAX_CELLS {
+        return Err(FogError::TooLarge { cols, rows });
+    }
+    Ok(())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, FogError> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes.get(*pos).ok_or(FogError::Truncated)?;
+        *pos += 1;
+        if shift >= 64 {
+            return Err(FogError::Truncated);
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rle(bytes: &[u8]) -> String {
+        BASE64.encode(bytes)
+    }
+
+    fn fogged_cells(fog: &Fog) -> Vec<(u32, u32)> {
+        (0..fog.rows())
+            .flat_map(|row| (0..fog.cols()).map(move |col| (row, col)))
+            .filter(|&(row, col)| fog.is_fogged(row, col))
+            .collect()
+    }
+
+    #[test]
+    fn packs_and_unpacks_losslessly() {
+        // 300x300 needs multi-byte run lengths; the scattered cells need
+        // many short runs, including one ending the grid revealed.
+        let mut fog = Fog::new(300, 300, true).unwrap();
+        fog.rect((10, 0), (200, 299), true);
+        for i in 0..300 {
+            fog.set(i, (i * 7) % 300, i % 3 == 0);
+        }
+        fog.set(299, 299, false);
+        for fog in [
+            fog,
+            Fog::new(300, 300, true).unwrap(),
+            Fog::new(3, 2, false).unwrap(),
+            Fog::new(0, 0, true).unwrap(),
+        ] {
+            let packed = serde_json::to_value(&fog).unwrap();
+            assert!(packed.get("cells").is_none());
+            assert_eq!(serde_json::from_value::<Fog>(packed).unwrap(), fog);
+        }
+        assert_eq!(
+            Fog::new(200, 200, true).unwrap().encode_rle(),
+            rle(&[0xc0, 0xb8, 0x02])
+        );
+        assert_eq!(Fog::new(2, 1, false).unwrap().encode_rle(), rle(&[0, 2]));
+    }
+
+    #[test]
+    fn reads_the_unpacked_shape() {
+        let fog: Fog =
+            serde_json::from_value(json!({ "cols": 3, "rows": 2, "cells": [[true, false, true]] }))
+                .unwrap();
+        // The missing second row counts as fogged.
+        assert_eq!(fog.to_cells(), [[true, false, true], [true, true, true]]);
+        assert_eq!(
+            serde_json::to_value(FogState::from(&fog)).unwrap(),
+            json!({ "cols": 3, "rows": 2, "cells": [[true, false, true], [true, true, true]] })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_rle() {
+        assert!(matches!(
+            Fog::decode_rle(2, 2, "not base64!"),
+            Err(FogError::Base64(_))
+        ));
+        // A run length whose last byte still says "more follows".
+        assert!(matches!(
+            Fog::decode_rle(2, 2, &rle(&[1, 0x83])),
+            Err(FogError::Truncated)
+        ));
+        assert!(matches!(
+            Fog::decode_rle(2, 2, &rle(&[1, 2])),
+            Err(FogError::LengthMismatch {
+                expected: 4,
+                actual: 3
+            })
+        ));
+        assert!(matches!(
+            Fog::decode_rle(2, 2, &rle(&[1, 2, 3])),
+            Err(FogError::LengthMismatch {
+                expected: 4,
+                actual: 6
+            })
+        ));
+        assert!(serde_json::from_value::<Fog>(json!({ "cols": 1, "rows": 1 })).is_err());
+    }
+
+    #[test]
+    fn rejects_grids_over_the_cap() {
+        let too_large = |result: Result<Fog, FogError>| {
+            matches!(
+                result,
+                Err(FogError::TooLarge {
+                    cols: 100_000,
+                    rows: 100_000
+                })
+            )
+        };
+        assert!(too_large(Fog::new(100_000, 100_000, true)));
+        assert!(too_large(Fog::from_cells(100_000, 100_000, &[])));
+        assert!(too_large(Fog::decode_rle(100_000, 100_000, &rle(&[0]))));
+        assert!(Fog::new(2000, 2000, true).is_ok());
+    }
+
+    #[test]
+    fn rect_covers_both_corners_and_stops_at_the_edge() {
+        let mut fog = Fog::new(4, 3, true).unwrap();
+        fog.rect((5, 2), (1, -3), true);
+        assert_eq!(
+            fog.to_cells(),
+            [
+                [true; 4],
+                [false, false, false, true],
+                [false, false, false, true]
+            ]
+        );
+        fog.rect((2, 1), (2, 1), false);
+        assert_eq!(
+            fogged_cells(&fog),
+            [(0, 0), (0, 1), (0, 2), (0, 3), (1, 3), (2, 1), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn brush_paints_a_square_around_the_centre() {
+        let mut fog = Fog::new(4, 4, true).unwrap();
+        fog.brush((0, 1), 1, true);
+        assert_eq!(
+            fog.to_cells(),
+            [
+                [false, false, false, true],
+                [false, false, false, true],
+                [true; 4],
+                [true; 4]
+            ]
+        );
+        fog.brush((0, 1), 0, false);
+        assert!(fog.is_fogged(0, 1) && !fog.is_fogged(0, 0));
+    }
+
+    #[test]
+    fn polygon_takes_cells_whose_centre_is_inside() {
+        // A right triangle over the top-left of a 3x3 grid: the centres
+        // (0.5, 0.5), (1.5, 0.5) and (0.5, 1.5) are inside; (1.5, 1.5) and
+        // (2.5, 0.5) are past the hypotenuse.
+        let mut fog = Fog::new(3, 3, true).unwrap();
+        fog.polygon(&[(0.0, 0.0), (2.9, 0.0), (0.0, 2.9)], true);
+        assert_eq!(
+            fog.to_cells(),
+            [
+                [false, false, true],
+                [false, true, true],
+                [true, true, true]
+            ]
+        );
+
+        // Points off the grid are fine; fewer than three do nothing.
+        let mut fog = Fog::new(2, 2, false).unwrap();
+        fog.polygon(&[(-5.0, -5.0), (9.0, -5.0), (9.0, 9.0), (-5.0, 9.0)], false);
+        assert_eq!(fogged_cells(&fog).len(), 4);
+        fog.polygon(&[(0.0, 0.0), (2.0, 2.0)], true);
+        assert_eq!(fogged_cells(&fog).len(), 4);
+    }
+}
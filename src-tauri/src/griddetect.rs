@@ -0,0 +1,315 @@
+use std::f64::consts::TAU;
+use std::path::Path;
+
+use serde::Serialize;
+
+// Finds the square grid drawn on a battle map image.
+//
+// Grid lines are edges that repeat at a fixed spacing across the whole map.
+// Summing the horizontal gradient down every column (and the vertical one
+// along every row) turns them into a spike train per axis; after taking out
+// the slow trend of the artwork, the train's autocorrelation (through an
+// FFT) peaks at the cell size. The peak at a multiple of it pins the size
+// down to a fraction of a pixel, and the phase of the spikes at that period
+// gives the offset.
+
+const MIN_CELL: usize = 10;
+const MAX_CELL: usize = 500;
+// Wider than a grid line's two edges, narrower than most cells.
+const TREND_WINDOW: usize = 31;
+const BLUR: [f64; 5] = [1.0 / 9.0, 2.0 / 9.0, 3.0 / 9.0, 2.0 / 9.0, 1.0 / 9.0];
+// A lag whose correlation is this close to the best one counts as a peak;
+// the smallest such lag is the cell, the rest are its multiples. Kept low
+// because shading in the artwork can make every other line stand out.
+const PEAK_RATIO: f64 = 0.5;
+const MAX_MULTIPLE: usize = 8;
+// Axes whose sizes differ by more than this don't describe the same grid.
+const AXIS_TOLERANCE: f64 = 0.03;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GridError {
+    #[error("could not read the image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("no grid found in the image")]
+    NotFound,
+}
+
+/// A detected grid, in image pixels. The offset is where the grid line
+/// nearest the top-left corner falls (within half a cell of 0).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridEstimate {
+    pub size: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    /// 0 (a guess) to 1 (clean, regular lines on both axes).
+    pub confidence: f64,
+}
+
+pub fn detect_file(path: &Path) -> Result<GridEstimate, GridError> {
+    let image = image::open(path)?.into_luma8();
+    detect(
+        image.as_raw(),
+        image.width() as usize,
+        image.height() as usize,
+    )
+}
+
+/// `luma` is `width` x `height` greyscale pixels, row by row.
+pub fn detect(luma: &[u8], width: usize, height: usize) -> Result<GridEstimate, GridError> {
+    let (cols, rows) = edge_profiles(luma, width, height);
+    let (cols, rows) = (detrend(&cols), detrend(&rows));
+    let (size, confidence) = match (axis_period(&cols), axis_period(&rows)) {
+        (Some(x), Some(y)) if (x.0 - y.0).abs() <= AXIS_TOLERANCE * x.0.max(y.0) => {
+            // Weigh each axis by how sure it is.
+            ((x.0 * x.1 + y.0 * y.1) / (x.1 + y.1), x.1.min(y.1))
+        }
+        (Some(x), Some(y)) => {
+            let best = if x.1 >= y.1 { x } else { y };
+            (best.0, best.1 / 2.0)
+        }
+        (Some(only), None) | (None, Some(only)) => (only.0, only.1 / 2.0),
+        (None, None) => return Err(GridError::NotFound),
+    };
+    Ok(GridEstimate {
+        size: round2(size),
+        offset_x: round2(phase(&cols, size)),
+        offset_y: round2(phase(&rows, size)),
+        confidence: round2(confidence.clamp(0.0, 1.0)),
+    })
+}
+
+// Edge strength summed per column (for vertical lines) and per row. The
+// gradient between pixels i and i+1 sits on the boundary at i+1, so index i
+// of a profile is position i+1.
+fn edge_profiles(luma: &[u8], width: usize, height: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut cols = vec![0.0; width.saturating_sub(1)];
+    let mut rows = vec![0.0; height.saturating_sub(1)];
+    for y in 0..height {
+        let row = &luma[y * width..(y + 1) * width];
+        for x in 0..width {
+            let v = f64::from(row[x]);
+            if x + 1 < width {
+                cols[x] += (f64::from(row[x + 1]) - v).abs();
+            }
+            if y + 1 < height {
+                rows[y] += (f64::from(luma[(y + 1) * width + x]) - v).abs();
+            }
+        }
+    }
+    (cols, rows)
+}
+
+// What stands out above the local average, so shading and large shapes in
+// the artwork don't drown the lines; then blurred a little, so lines that
+// land between pixels (a 52.5px grid) still line up with each other.
+fn detrend(profile: &[f64]) -> Vec<f64> {
+    let n = profile.len();
+    let mut prefix = vec![0.0; n + 1];
+    for (i, v) in profile.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + v;
+    }
+    let half = TREND_WINDOW / 2;
+    let peaks: Vec<f64> = (0..n)
+        .map(|i| {
+            let (lo, hi) = (i.saturating_sub(half), (i + half + 1).min(n));
+            let mean = (prefix[hi] - prefix[lo]) / (hi - lo) as f64;
+            (profile[i] - mean).max(0.0)
+        })
+        .collect();
+    (0..n)
+        .map(|i| {
+            BLUR.iter()
+                .enumerate()
+                .filter_map(|(k, w)| {
+                    (i + k)
+                        .checked_sub(BLUR.len() / 2)
+                        .and_then(|j| peaks.get(j))
+                        .map(|v| v * w)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+// The repeat distance of a profile and how strongly it repeats (0 to 1).
+fn axis_period(profile: &[f64]) -> Option<(f64, f64)> {
+    let n = profile.len();
+    let max_lag = (n / 2).min(MAX_CELL);
+    if max_lag <= MIN_CELL + 1 {
+        return None;
+    }
+    let ac = autocorrelation(profile)?;
+    let peaks: Vec<usize> = (MIN_CELL..max_lag)
+        .filter(|&lag| ac[lag] >= ac[lag - 1] && ac[lag] >= ac[lag + 1])
+        .collect();
+    let best = peaks.iter().map(|&lag| ac[lag]).fold(0.0, f64::max);
+    if best <= 0.0 {
+        return None;
+    }
+    let lag = *peaks.iter().find(|&&lag| ac[lag] >= PEAK_RATIO * best)?;
+    let rough = refine_peak(&ac, lag);
+
+    // The furthest multiple still well inside the profile is the most precise.
+    let mut period = rough;
+    for k in (2..=MAX_MULTIPLE).rev() {
+        let target = (rough * k as f64).round() as usize;
+        if target + 2 >= max_lag {
+            continue;
+        }
+        let peak = (target - 2..=target + 2).max_by(|&a, &b| ac[a].total_cmp(&ac[b]))?;
+        period = refine_peak(&ac, peak) / k as f64;
+        break;
+    }
+    Some((period, ac[lag].min(1.0)))
+}
+
+// Sub-sample position of the peak at `lag` (a parabola through it and its
+// neighbours).
+fn refine_peak(ac: &[f64], lag: usize) -> f64 {
+    let (a, b, c) = (ac[lag - 1], ac[lag], ac[lag + 1]);
+    let denominator = a - 2.0 * b + c;
+    if denominator.abs() < f64::EPSILON {
+        return lag as f64;
+    }
+    lag as f64 + (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+}
+
+// Autocorrelation of the mean-removed profile, 1 at lag 0 and corrected for
+// the shrinking overlap at longer lags. `None` for a flat profile.
+fn autocorrelation(profile: &[f64]) -> Option<Vec<f64>> {
+    let n = profile.len();
+    let mean = profile.iter().sum::<f64>() / n as f64;
+    let size = (2 * n).next_power_of_two();
+    let mut buf: Vec<(f64, f64)> = profile.iter().map(|v| (v - mean, 0.0)).collect();
+    buf.resize(size, (0.0, 0.0));
+    fft(&mut buf, false);
+    for z in &mut buf {
+        *z = (z.0 * z.0 + z.1 * z.1, 0.0);
+    }
+    fft(&mut buf, true);
+    let zero = buf[0].0;
+    if zero <= f64::EPSILON {
+        return None;
+    }
+    Some(
+        (0..n)
+            .map(|lag| buf[lag].0 / zero * n as f64 / (n - lag) as f64)
+            .collect(),
+    )
+}
+
+// In-place radix-2 FFT; `buf.len()` must be a power of two. The inverse is
+// left unscaled.
+fn fft(buf: &mut [(f64, f64)], inverse: bool) {
+    let n = buf.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buf.swap(i, j);
+        }
+    }
+    let mul = |a: (f64, f64), b: (f64, f64)| (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0);
+    let mut len = 2;
+    while len <= n {
+        let angle = if inverse { TAU } else { -TAU } / len as f64;
+        let step = (angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let mut w = (1.0, 0.0);
+            for k in 0..len / 2 {
+                let a = buf[start + k];
+                let b = mul(buf[start + k + len / 2], w);
+                buf[start + k] = (a.0 + b.0, a.1 + b.1);
+                buf[start + k + len / 2] = (a.0 - b.0, a.1 - b.1);
+                w = mul(w, step);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// Where the lines fall modulo `period`: the circular mean of the edge
+// positions, weighted by strength. A line's two edges average to its centre.
+fn phase(profile: &[f64], period: f64) -> f64 {
+    let (mut sin, mut cos) = (0.0, 0.0);
+    for (i, weight) in profile.iter().enumerate() {
+        let angle = TAU * (i + 1) as f64 / period;
+        sin += weight * angle.sin();
+        cos += weight * angle.cos();
+    }
+    let offset = sin.atan2(cos) / TAU * period;
+    if offset > period / 2.0 {
+        offset - period
+    } else {
+        offset
+    }
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A textured map with 2px dark grid lines centred on
+    // `offset + k * size` along both axes.
+    fn grid_image(width: usize, height: usize, size: f64, offset: (f64, f64)) -> Vec<u8> {
+        let on_line = |p: usize, offset: f64| {
+            let d = (p as f64 + 0.5 - offset).rem_euclid(size);
+            d < 1.0 || d > size - 1.0
+        };
+        let mut seed = 12345u32;
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let noise = (seed >> 16) % 40;
+                let shade = 150.0 + 15.0 * (x as f64 / 37.0).sin() + 15.0 * (y as f64 / 53.0).cos();
+                let line = on_line(x, offset.0) || on_line(y, offset.1);
+                pixels.push(if line {
+                    40
+                } else {
+                    (shade as u32 + noise) as u8
+                });
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn finds_size_and_offset() {
+        let pixels = grid_image(1000, 800, 70.0, (13.0, 41.0));
+        let grid = detect(&pixels, 1000, 800).unwrap();
+        assert!((grid.size - 70.0).abs() < 0.3, "{grid:?}");
+        assert!((grid.offset_x - 13.0).abs() < 1.0, "{grid:?}");
+        // 41 is more than half a cell in, so the nearest line is one cell back.
+        assert!((grid.offset_y - (41.0 - 70.0)).abs() < 1.0, "{grid:?}");
+        assert!(grid.confidence > 0.5, "{grid:?}");
+    }
+
+    #[test]
+    fn finds_fractional_sizes() {
+        let pixels = grid_image(900, 900, 52.5, (10.0, 10.0));
+        let grid = detect(&pixels, 900, 900).unwrap();
+        assert!((grid.size - 52.5).abs() < 0.2, "{grid:?}");
+        assert!((grid.offset_x - 10.0).abs() < 1.0, "{grid:?}");
+    }
+
+    #[test]
+    fn is_unsure_without_a_grid() {
+        let pixels = grid_image(600, 600, 1e9, (-1e6, -1e6));
+        match detect(&pixels, 600, 600) {
+            Ok(grid) => assert!(grid.confidence < 0.3, "{grid:?}"),
+            Err(GridError::NotFound) => {}
+            Err(err) => panic!("{err}"),
+        }
+    }
+}
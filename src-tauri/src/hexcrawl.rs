@@ -0,0 +1,441 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hexm::{self, HexCell, HexMapFile};
+use crate::types::PartyState;
+
+// Hex-crawl travel over a `.hexm` map (ACKS wilderness rules, as summarised
+// on the hex tooltip in `src/hexmap.ts`). The fastest path weighs each hex's
+// terrain speed; following a road or navigable river moves at ×3/2 whatever
+// the terrain and needs no navigation throw. The plan is then split into
+// days: how far the party gets, the navigation (get-lost) throw for any
+// off-road stretch, one travel encounter check per hex entered and the camp
+// check for the night by the territory the party stops in.
+
+pub const DEFAULT_HEX_MILES: f64 = 6.0;
+const ROUTE_SPEED: f64 = 1.5;
+
+type Hex = (i64, i64); // 1-based (col, row)
+
+#[derive(Debug, thiserror::Error)]
+pub enum TravelError {
+    #[error("hex {0} is not on the map")]
+    OffMap(String),
+    #[error("hex {0} can't be entered on foot")]
+    Impassable(String),
+    #[error("no overland route from {from} to {to}")]
+    NoRoute { from: String, to: String },
+    #[error("movement must be positive, got {0}")]
+    Movement(f64),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TravelRequest {
+    pub from: (i64, i64),
+    pub to: (i64, i64),
+    /// Exploration movement in feet per turn (120′ for most parties);
+    /// a day's march is a fifth of it in miles.
+    pub movement: f64,
+    #[serde(default)]
+    pub hex_miles: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayPlan {
+    pub day: u32, // 1-based
+    /// Hexes entered by the end of the day, in order.
+    pub entered: Vec<(i64, i64)>,
+    pub miles: f64,
+    /// Navigation throw target (d20) when any of the day's march left the
+    /// roads and rivers; the hardest terrain crossed sets it.
+    pub navigation: Option<u8>,
+    pub travel_checks: u32,
+    /// Where the party camps, and the territory setting the night's checks.
+    pub camp: (i64, i64),
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub territory: Option<String>,
+    pub camp_checks: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TravelPlan {
+    pub path: Vec<(i64, i64)>,
+    pub miles: f64,
+    /// Days of travel, fractional for the last part-day.
+    pub days: f64,
+    pub day_plans: Vec<DayPlan>,
+}
+
+// Speed multiplier and navigation target per terrain key. Terrains the rules
+// don't know travel at ×1 with the easiest throw; water needs a boat.
+fn terrain_rule(terrain: Option<&str>) -> Option<(f64, u8)> {
+    Some(match terrain.unwrap_or_default() {
+        "hills" | "forest" => (2.0 / 3.0, 8),
+        "barrens" | "desert" => (2.0 / 3.0, 6),
+        "mountains" => (0.5, 6),
+        "swamp" => (0.5, 10),
+        "jungle" => (0.5, 14),
+        "water" => return None,
+        _ => (1.0, 6),
+    })
+}
+
+// Camp encounter checks on the `day`th night (JJ Ch 5): civilized lands
+// one in seven nights, borderlands one in three, anywhere wilder (or
+// unclassified) every night.
+fn camp_checks(territory: Option<&str>, day: u32) -> u32 {
+    match territory {
+        Some("civilized") => u32::from(day.is_multiple_of(7)),
+        Some("borderlands") => u32::from(day.is_multiple_of(3)),
+        _ => 1,
+    }
+}
+
+struct Grid<'a> {
+    map: &'a HexMapFile,
+    cells: HashMap<(i64, i64), &'a HexCell>,
+    routes: HashSet<(Hex, Hex)>, // both directions of every road/river step
+}
+
+impl<'a> Grid<'a> {
+    fn new(map: &'a HexMapFile) -> Self {
+        let cells = map.hexes.iter().map(|h| ((h.col, h.row), h)).collect();
+        let mut routes = HashSet::new();
+        for route in &map.roads {
+            for pair in route.path.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                if hexm::hex_distance(map.orientation, a, b) == 1 {
+                    routes.insert((a, b));
+                    routes.insert((b, a));
+                }
+            }
+        }
+        Grid { map, cells, routes }
+    }
+
+    fn terrain(&self, hex: (i64, i64)) -> Option<&'a str> {
+        let cell = self.cells.get(&hex);
+        cell.and_then(|c| c.terrain.as_deref())
+            .or(self.map.default_terrain.as_deref())
+    }
+
+    fn territory(&self, hex: (i64, i64)) -> Option<&'a str> {
+        self.cells.get(&hex).and_then(|c| c.territory.as_deref())
+    }
+
+    fn neighbours(&self, hex: (i64, i64)) -> impl Iterator<Item = (i64, i64)> + '_ {
+        const DIRECTIONS: [(i64, i64, i64); 6] = [
+            (1, -1, 0),
+            (1, 0, -1),
+            (0, 1, -1),
+            (-1, 1, 0),
+            (-1, 0, 1),
+            (0, -1, 1),
+        ];
+        let (x, y, z) = hexm::to_cube(self.map.orientation, hex);
+        DIRECTIONS
+            .iter()
+            .map(move |(dx, dy, dz)| {
+                hexm::from_cube(self.map.orientation, (x + dx, y + dy, z + dz))
+            })
+            .filter(|&(col, row)| self.map.on_grid(col, row))
+    }
+
+    // Miles-at-normal-speed for one step (half of each hex's crossing), or
+    // `None` if it can't be made. Roads and rivers carry the party through
+    // any terrain, water included.
+    fn step(&self, from: (i64, i64), to: (i64, i64), hex_miles: f64) -> Option<Step> {
+        if self.routes.contains(&(from, to)) {
+            return Some(Step {
+                effort: hex_miles / ROUTE_SPEED,
+                navigation: None,
+            });
+        }
+        let (from_speed, _) = terrain_rule(self.terrain(from)).unwrap_or((1.0, 0));
+        let (to_speed, navigation) = terrain_rule(self.terrain(to))?;
+        Some(Step {
+            effort: hex_miles * (0.5 / from_speed + 0.5 / to_speed),
+            navigation: Some(navigation),
+        })
+    }
+}
+
+struct Step {
+    effort: f64,
+    navigation: Option<u8>,
+}
+
+pub fn plan(map: &HexMapFile, request: &TravelRequest) -> Result<TravelPlan, TravelError> {
+    let TravelRequest {
+        from, to, movement, ..
+    } = *request;
+    if !(movement.is_finite() && movement > 0.0) {
+        return Err(TravelError::Movement(movement));
+    }
+    let hex_miles = request
+        .hex_miles
+        .filter(|m| *m > 0.0)
+        .unwrap_or(DEFAULT_HEX_MILES);
+    let miles_per_day = movement / 5.0;
+    for hex in [from, to] {
+        if !map.on_grid(hex.0, hex.1) {
+            return Err(TravelError::OffMap(hexm::coord(hex.0, hex.1)));
+        }
+    }
+    let grid = Grid::new(map);
+    if from != to
+        && !grid
+            .neighbours(to)
+            .any(|n| grid.step(n, to, hex_miles).is_some())
+    {
+        return Err(TravelError::Impassable(hexm::coord(to.0, to.1)));
+    }
+
+    // Dijkstra on effort, kept as integer thousandths of a mile for the heap.
+    let mut best: HashMap<Hex, (u64, Hex)> = HashMap::from([(from, (0, from))]);
+    let mut queue = BinaryHeap::from([Reverse((0u64, from))]);
+    while let Some(Reverse((cost, hex))) = queue.pop() {
+        if hex == to {
+            break;
+        }
+        if best.get(&hex).is_some_and(|&(c, _)| c < cost) {
+            continue;
+        }
+        for next in grid.neighbours(hex) {
+            let Some(step) = grid.step(hex, next, hex_miles) else {
+                continue;
+            };
+            let cost = cost + (step.effort * 1000.0).round() as u64;
+            if best.get(&next).is_none_or(|&(c, _)| cost < c) {
+                best.insert(next, (cost, hex));
+                queue.push(Reverse((cost, next)));
+            }
+        }
+    }
+    if !best.contains_key(&to) {
+        return Err(TravelError::NoRoute {
+            from: hexm::coord(from.0, from.1),
+            to: hexm::coord(to.0, to.1),
+        });
+    }
+    let mut path = vec![to];
+    while let Some(&hex) = path.last().filter(|&&hex| hex != from) {
+        path.push(best[&hex].1);
+    }
+    path.reverse();
+
+    // Walk the path a day at a time; a hex only half-crossed at nightfall is
+    // finished the next morning.
+    let mut day_plans = Vec::new();
+    let mut today = DayPlan::start(1, from, &grid);
+    let mut left = miles_per_day; // effort the party can still spend today
+    let mut days = 0.0;
+    for pair in path.windows(2) {
+        let step = grid
+            .step(pair[0], pair[1], hex_miles)
+            .expect("path steps are passable");
+        let mut remaining = step.effort;
+        while remaining > 1e-9 {
+            if left <= 1e-9 {
+                days += 1.0;
+                let next = DayPlan::start(today.day + 1, today.camp, &grid);
+                day_plans.push(std::mem::replace(&mut today, next).finish(&grid));
+                left = miles_per_day;
+            }
+            let spent = remaining.min(left);
+            today.miles += hex_miles * spent / step.effort;
+            today.navigation = today.navigation.max(step.navigation);
+            remaining -= spent;
+            left -= spent;
+        }
+        today.entered.push(pair[1]);
+        today.camp = pair[1];
+    }
+    days += 1.0 - left / miles_per_day;
+    if from != to {
+        day_plans.push(today.finish(&grid));
+    }
+
+    let miles = hex_miles * (path.len() - 1) as f64;
+    Ok(TravelPlan {
+        path,
+        miles,
+        days,
+        day_plans,
+    })
+}
+
+impl DayPlan {
+    fn start(day: u32, camp: (i64, i64), grid: &Grid) -> Self {
+        DayPlan {
+            day,
+            entered: Vec::new(),
+            miles: 0.0,
+            navigation: None,
+            travel_checks: 0,
+            camp,
+            territory: grid.territory(camp).map(Into::into),
+            camp_checks: 0,
+        }
+    }
+
+    // A day spent wholly inside one hex still gets its travel check.
+    fn finish(mut self, grid: &Grid) -> Self {
+        self.travel_checks = (self.entered.len() as u32).max(1);
+        self.territory = grid.territory(self.camp).map(Into::into);
+        self.camp_checks = camp_checks(self.territory.as_deref(), self.day);
+        self
+    }
+}
+
+/// The party's travel log as Markdown, one section per in-game day with its
+/// moves and then its encounters. Hexes print as `CC.RR`, with their name
+/// from `map` when they have one.
+pub fn travel_log_markdown(map: &HexMapFile, party: &PartyState) -> String {
+    let names: HashMap<(i64, i64), &str> = map
+        .hexes
+        .iter()
+        .filter_map(|h| Some(((h.col, h.row), h.name.as_deref()?)))
+        .collect();
+    let hex = |(col, row): (i64, i64)| match names.get(&(col, row)) {
+        Some(name) => format!("{} ({name})", hexm::coord(col, row)),
+        None => hexm::coord(col, row),
+    };
+    let with_note = |line: String, note: &str| match note.trim() {
+        "" => line,
+        note => format!("{line}: {}", note.replace('\n', " ")),
+    };
+
+    let mut days: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for entry in &party.log {
+        let line = match entry.from {
+            Some(from) => format!("{} → {}", hex(from), hex(entry.to)),
+            None => format!("Start at {}", hex(entry.to)),
+        };
+        days.entry(entry.day)
+            .or_default()
+            .push(with_note(line, &entry.note));
+    }
+    for entry in &party.encounters {
+        let line = with_note(format!("Encounter at {}", hex(entry.hex)), &entry.summary);
+        days.entry(entry.day).or_default().push(line);
+    }
+
+    let mut out = format!(
+        "# Travel log: {}\n",
+        map.title.as_deref().unwrap_or("hex map")
+    );
+    for (day, lines) in &days {
+        let _ = write!(out, "\n## Day {day}\n\n");
+        for line in lines {
+            let _ = writeln!(out, "- {line}");
+        }
+    }
+    if days.is_empty() {
+        out.push_str("\nNo moves yet.\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid() -> HexMapFile {
+        hexm::parse(include_str!("../tests/fixtures/hexmaps/valid.hexm"))
+            .map
+            .unwrap()
+    }
+
+    fn request(from: (i64, i64), to: (i64, i64), movement: f64) -> TravelRequest {
+        TravelRequest {
+            from,
+            to,
+            movement,
+            hex_miles: None,
+        }
+    }
+
+    #[test]
+    fn follows_the_road_and_splits_days() {
+        // King's Road runs 01.01 - 02.01 - 03.02 - 04.02; 24 miles a day at
+        // ×3/2 covers six road hexes, so three is half a day.
+        let plan = plan(&valid(), &request((1, 1), (4, 2), 120.0)).unwrap();
+        assert_eq!(plan.path, vec![(1, 1), (2, 1), (3, 2), (4, 2)]);
+        assert_eq!(plan.miles, 18.0);
+        assert!((plan.days - 0.5).abs() < 1e-9);
+        assert_eq!(plan.day_plans.len(), 1);
+        let day = &plan.day_plans[0];
+        assert_eq!(
+            (day.navigation, day.travel_checks, day.camp),
+            (None, 3, (4, 2))
+        );
+
+        // At 30′ (6 miles a day) the same trip takes two days.
+        let plan = super::plan(&valid(), &request((1, 1), (4, 2), 30.0)).unwrap();
+        assert!((plan.days - 2.0).abs() < 1e-9);
+        let entered: Vec<_> = plan.day_plans.iter().map(|d| d.entered.len()).collect();
+        assert_eq!(entered, vec![1, 2]);
+    }
+
+    #[test]
+    fn off_road_travel_needs_navigation() {
+        // 01.01 is forest (×2/3, 8+) in the borderlands; leaving it for the
+        // grassland at 01.02 costs 4.5 + 3 miles of effort.
+        let plan = plan(&valid(), &request((1, 1), (1, 2), 120.0)).unwrap();
+        let day = &plan.day_plans[0];
+        assert_eq!(day.navigation, Some(6));
+        assert!((plan.days - 7.5 / 24.0).abs() < 1e-9);
+        let back = super::plan(&valid(), &request((1, 2), (1, 1), 120.0)).unwrap();
+        assert_eq!(back.day_plans[0].navigation, Some(8));
+        assert_eq!(back.day_plans[0].territory.as_deref(), Some("borderlands"));
+        assert_eq!(back.day_plans[0].camp_checks, 0);
+    }
+
+    #[test]
+    fn travel_log_as_markdown() {
+        use crate::types::{EncounterLogEntry, TravelLogEntry};
+        let entry = |from, to, day, note: &str| TravelLogEntry {
+            from,
+            to,
+            day,
+            note: note.into(),
+        };
+        let party = PartyState {
+            position: Some((3, 2)),
+            day: 2,
+            log: vec![
+                entry(None, (1, 1), 1, ""),
+                entry(Some((1, 1)), (2, 1), 1, "Bought rope\nat the mill"),
+                entry(Some((2, 1)), (3, 2), 2, " "),
+            ],
+            encounters: vec![EncounterLogEntry {
+                hex: (2, 1),
+                day: 1,
+                summary: "3 × Wolves".into(),
+            }],
+        };
+        assert_eq!(
+            travel_log_markdown(&valid(), &party),
+            "# Travel log: Valid\n\n## Day 1\n\n- Start at 01.01\n- 01.01 → 02.01 (Old Mill): Bought rope at the mill\n- Encounter at 02.01 (Old Mill): 3 × Wolves\n\n## Day 2\n\n- 02.01 (Old Mill) → 03.02\n"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert!(matches!(
+            plan(&valid(), &request((1, 1), (9, 9), 120.0)),
+            Err(TravelError::OffMap(_))
+        ));
+        assert!(matches!(
+            plan(&valid(), &request((1, 1), (2, 2), 0.0)),
+            Err(TravelError::Movement(_))
+        ));
+    }
+}
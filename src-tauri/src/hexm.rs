@@ -0,0 +1,569 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+// `.hexm` hex-map files (see `src/hexmap.ts` for the format and renderer).
+// Parsing is strict about shape (serde reports the line of a missing field or
+// wrong type) and then checks the things serde can't: terrain keys that
+// aren't in `terrains`, hexes listed twice or off the grid, roads through
+// hexes that don't exist. Those come back as line-numbered diagnostics next
+// to the parsed map rather than as a hard failure, so the DM can still open
+// a map with a typo in one hex.
+
+pub const FORMAT: &str = "vtt-hexmap";
+pub const DEFAULT_RADIUS: f64 = 40.0;
+const TERRITORIES: [&str; 4] = ["civilized", "borderlands", "outlands", "unsettled"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HexOrientation {
+    #[default]
+    Flat,
+    Pointy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HexCell {
+    pub col: i64,
+    pub row: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terrain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub territory: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteKind {
+    #[default]
+    Road,
+    River,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "type", default)]
+    pub kind: RouteKind,
+    // [col, row] pairs
+    pub path: Vec<(i64, i64)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObsidianLinks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Mirrors `HexMapFile` in `src/hexmap.ts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HexMapFile {
+    pub format: String,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub orientation: HexOrientation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hex_radius: Option<f64>,
+    pub cols: i64,
+    pub rows: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_terrain: Option<String>,
+    pub terrains: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terrain_labels: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obsidian: Option<ObsidianLinks>,
+    pub hexes: Vec<HexCell>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roads: Vec<Route>,
+}
+
+impl HexMapFile {
+    pub(crate) fn on_grid(&self, col: i64, row: i64) -> bool {
+        (1..=self.cols).contains(&col) && (1..=self.rows).contains(&row)
+    }
+
+    /// Radius in pixels, falling back to the default for a missing or
+    /// non-positive `hexRadius` (as the webview renderer does).
+    pub fn radius(&self) -> f64 {
+        self.hex_radius
+            .filter(|r| *r > 0.0)
+            .unwrap_or(DEFAULT_RADIUS)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize, // 1-based
+    pub message: String,
+}
+
+/// The parsed map (`None` when the file couldn't be read as a hex map at
+/// all) and everything wrong with it, in line order.
+#[derive(Debug, Clone, Serialize)]
+pub struct HexmReport {
+    pub map: Option<HexMapFile>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl HexmReport {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+pub fn load(path: &Path) -> std::io::Result<HexmReport> {
+    Ok(parse(&std::fs::read_to_string(path)?))
+}
+
+pub fn parse(text: &str) -> HexmReport {
+    let map: HexMapFile = match serde_json::from_str(text) {
+        Ok(map) => map,
+        Err(e) => {
+            // serde_json's message already ends in "at line N column M".
+            let message = e.to_string();
+            let message = message
+                .rsplit_once(" at line ")
+                .map_or(message.as_str(), |(m, _)| m)
+                .to_string();
+            let diagnostics = vec![Diagnostic {
+                severity: Severity::Error,
+                line: e.line().max(1),
+                message,
+            }];
+            return HexmReport {
+                map: None,
+                diagnostics,
+            };
+        }
+    };
+    let lines = LineIndex::new(text);
+    let mut diagnostics = check(&map, &lines);
+    diagnostics.sort_by_key(|d| d.line);
+    HexmReport {
+        map: Some(map),
+        diagnostics,
+    }
+}
+
+fn check(map: &HexMapFile, lines: &LineIndex) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let mut report = |severity, path: &str, message: String| {
+        out.push(Diagnostic {
+            severity,
+            line: lines.line(path),
+            message,
+        });
+    };
+    use Severity::{Error, Warning};
+
+    if map.format != FORMAT {
+        report(
+            Error,
+            "/format",
+            format!("format is \"{}\", expected \"{FORMAT}\"", map.format),
+        );
+    }
+    if map.cols < 1 || map.rows < 1 {
+        report(
+            Error,
+            "/cols",
+            format!(
+                "grid is {} x {}; cols and rows must be at least 1",
+                map.cols, map.rows
+            ),
+        );
+    }
+    if map.hex_radius.is_some_and(|r| r <= 0.0) {
+        report(
+            Warning,
+            "/hexRadius",
+            format!("hexRadius must be positive; using {DEFAULT_RADIUS}"),
+        );
+    }
+    if map.terrains.is_empty() {
+        report(
+            Warning,
+            "/terrains",
+            "no terrains defined; every hex is drawn in the fallback color".into(),
+        );
+    }
+    if let Some(terrain) = &map.default_terrain {
+        if !map.terrains.contains_key(terrain) {
+            report(
+                Error,
+                "/defaultTerrain",
+                format!("defaultTerrain \"{terrain}\" is not in terrains"),
+            );
+        }
+    }
+    for key in map.terrain_labels.iter().flat_map(|labels| labels.keys()) {
+        if !map.terrains.contains_key(key) {
+            report(
+                Warning,
+                &format!("/terrainLabels/{key}"),
+                format!("label for unknown terrain \"{key}\""),
+            );
+        }
+    }
+
+    let mut seen: HashMap<(i64, i64), usize> = HashMap::new();
+    for (i, hex) in map.hexes.iter().enumerate() {
+        let path = format!("/hexes/{i}");
+        let coord = coord(hex.col, hex.row);
+        if !map.on_grid(hex.col, hex.row) {
+            report(
+                Error,
+                &path,
+                format!(
+                    "hex {coord} is outside the {} x {} grid",
+                    map.cols, map.rows
+                ),
+            );
+        }
+        if let Some(first) = seen.get(&(hex.col, hex.row)) {
+            report(
+                Error,
+                &path,
+                format!("hex {coord} is listed twice (first on line {first})"),
+            );
+        } else {
+            seen.insert((hex.col, hex.row), lines.line(&path));
+        }
+        match &hex.terrain {
+            Some(terrain) if !map.terrains.contains_key(terrain) => {
+                report(
+                    Error,
+                    &format!("{path}/terrain"),
+                    format!("hex {coord}: unknown terrain \"{terrain}\""),
+                );
+            }
+            None if map.default_terrain.is_none() => {
+                report(
+                    Warning,
+                    &path,
+                    format!("hex {coord} has no terrain and there is no defaultTerrain"),
+                );
+            }
+            _ => {}
+        }
+        if let Some(territory) = &hex.territory {
+            if !TERRITORIES.contains(&territory.as_str()) {
+                report(
+                    Warning,
+                    &format!("{path}/territory"),
+                    format!(
+                        "hex {coord}: unknown territory \"{territory}\" (expected one of {})",
+                        TERRITORIES.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    for (i, route) in map.roads.iter().enumerate() {
+        let path = format!("/roads/{i}/path");
+        let name = route
+            .name
+            .as_deref()
+            .map_or_else(|| format!("route {}", i + 1), |n| format!("\"{n}\""));
+        if route.path.len() < 2 {
+            report(
+                Warning,
+                &path,
+                format!("{name} has fewer than two hexes and won't be drawn"),
+            );
+        }
+        let mut off_grid = HashSet::new();
+        for (j, &(col, row)) in route.path.iter().enumerate() {
+            if !map.on_grid(col, row) {
+                off_grid.insert(j);
+                report(
+                    Error,
+                    &format!("{path}/{j}"),
+                    format!(
+                        "{name} passes through {}, which is not on the map",
+                        coord(col, row)
+                    ),
+                );
+            }
+        }
+        for (j, pair) in route.path.windows(2).enumerate() {
+            let (a, b) = (pair[0], pair[1]);
+            if off_grid.contains(&j) || off_grid.contains(&(j + 1)) {
+                continue;
+            }
+            if hex_distance(map.orientation, a, b) != 1 {
+                report(
+                    Warning,
+                    &format!("{path}/{}", j + 1),
+                    format!(
+                        "{name} jumps from {} to {}, which aren't neighbours",
+                        coord(a.0, a.1),
+                        coord(b.0, b.1)
+                    ),
+                );
+            }
+        }
+    }
+    out
+}
+
+// "CC.RR", as hex keys print coordinates.
+pub(crate) fn coord(col: i64, row: i64) -> String {
+    format!("{col:02}.{row:02}")
+}
+
+// Cube coordinates of the 1-based hex (col, row). Flat-top maps shift odd
+// columns down (odd-q), pointy-top maps shift odd rows right (odd-r),
+// matching `hexCenter`.
+pub(crate) fn to_cube(orientation: HexOrientation, (col, row): (i64, i64)) -> (i64, i64, i64) {
+    let (c, r) = (col - 1, row - 1);
+    let (x, z) = match orientation {
+        HexOrientation::Flat => (c, r - (c - (c & 1)) / 2),
+        HexOrientation::Pointy => (c - (r - (r & 1)) / 2, r),
+    };
+    (x, -x - z, z)
+}
+
+pub(crate) fn from_cube(orientation: HexOrientation, (x, _, z): (i64, i64, i64)) -> (i64, i64) {
+    let (c, r) = match orientation {
+        HexOrientation::Flat => (x, z + (x - (x & 1)) / 2),
+        HexOrientation::Pointy => (x + (z - (z & 1)) / 2, z),
+    };
+    (c + 1, r + 1)
+}
+
+// Steps between two 1-based hexes.
+pub(crate) fn hex_distance(orientation: HexOrientation, a: (i64, i64), b: (i64, i64)) -> i64 {
+    let (a, b) = (to_cube(orientation, a), to_cube(orientation, b));
+    (a.0 - b.0)
+        .abs()
+        .max((a.1 - b.1).abs())
+        .max((a.2 - b.2).abs())
+}
+
+// Line numbers for every value in a JSON document, keyed by JSON pointer
+// ("/hexes/3/terrain"). serde_json only reports positions for errors, so
+// the checks above look their lines up here. Only run on text serde_json
+// has already accepted.
+struct LineIndex {
+    lines: HashMap<String, usize>,
+}
+
+impl LineIndex {
+    fn new(text: &str) -> Self {
+        let mut scanner = Scanner {
+            bytes: text.as_bytes(),
+            pos: 0,
+            line: 1,
+            lines: HashMap::new(),
+        };
+        scanner.value(String::new());
+        LineIndex {
+            lines: scanner.lines,
+        }
+    }
+
+    // The line of `path`, or of its nearest ancestor that has one.
+    fn line(&self, mut path: &str) -> usize {
+        loop {
+            if let Some(&line) = self.lines.get(path) {
+                return line;
+            }
+            match path.rsplit_once('/') {
+                Some((parent, _)) => path = parent,
+                None => return 1,
+            }
+        }
+    }
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+    lines: HashMap<String, usize>,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b) = self.peek() {
+            match b {
+                b'\n' => self.line += 1,
+                b' ' | b'\t' | b'\r' => {}
+                _ => return,
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self, path: String) {
+        self.skip_ws();
+        self.lines.insert(path.clone(), self.line);
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                loop {
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(b'"') => {
+                            let key = self.string();
+                            self.skip_ws();
+                            self.pos += 1; // ':'
+                            self.value(format!("{path}/{key}"));
+                        }
+                        Some(b',') => self.pos += 1,
+                        Some(_) => {
+                            self.pos += 1; // '}'
+                            return;
+                        }
+                        None => return,
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut index = 0;
+                loop {
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(b']') => {
+                            self.pos += 1;
+                            return;
+                        }
+                        Some(b',') => self.pos += 1,
+                        Some(_) => {
+                            self.value(format!("{path}/{index}"));
+                            index += 1;
+                        }
+                        None => return,
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            Some(_) => {
+                while self.peek().is_some_and(|b| {
+                    !matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n')
+                }) {
+                    self.pos += 1;
+                }
+            }
+            None => {}
+        }
+    }
+
+    // A string's raw contents (escapes kept as written; keys with escapes
+    // don't occur in `.hexm` files).
+    fn string(&mut self) -> String {
+        self.pos += 1; // opening quote
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            match b {
+                b'\\' => self.pos += 2,
+                b'"' => break,
+                _ => self.pos += 1,
+            }
+        }
+        let end = self.pos.min(self.bytes.len());
+        self.pos += 1; // closing quote
+        String::from_utf8_lossy(&self.bytes[start..end]).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(report: &HexmReport) -> Vec<(Severity, usize)> {
+        report
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.line))
+            .collect()
+    }
+
+    #[test]
+    fn valid_map_has_no_diagnostics() {
+        let report = parse(include_str!("../tests/fixtures/hexmaps/valid.hexm"));
+        assert_eq!(report.diagnostics, vec![]);
+        let map = report.map.expect("map parses");
+        assert_eq!(
+            (map.cols, map.rows, map.hexes.len(), map.roads.len()),
+            (4, 3, 3, 1)
+        );
+    }
+
+    #[test]
+    fn reports_problems_on_their_lines() {
+        let report = parse(include_str!("../tests/fixtures/hexmaps/broken.hexm"));
+        assert!(report.map.is_some());
+        assert_eq!(
+            summary(&report),
+            vec![
+                (Severity::Error, 7),    // defaultTerrain not in terrains
+                (Severity::Error, 10),   // unknown terrain
+                (Severity::Error, 11),   // off the grid
+                (Severity::Error, 12),   // duplicate
+                (Severity::Warning, 13), // unknown territory
+                (Severity::Error, 16),   // road through a missing hex
+                (Severity::Warning, 17), // road jumps
+            ],
+            "{:#?}",
+            report.diagnostics
+        );
+        assert!(report.diagnostics[3].message.contains("first on line 9"));
+    }
+
+    #[test]
+    fn syntax_errors_are_a_single_diagnostic() {
+        let report = parse("{\n  \"format\": \"vtt-hexmap\",\n  \"cols\": 4,\n  \"rows\": oops\n}");
+        assert!(report.map.is_none());
+        assert_eq!(summary(&report), vec![(Severity::Error, 4)]);
+    }
+
+    #[test]
+    fn neighbours_follow_the_offset_layout() {
+        // Flat-top: odd (1-based even) columns sit half a hex lower.
+        assert_eq!(hex_distance(HexOrientation::Flat, (1, 1), (2, 1)), 1);
+        assert_eq!(hex_distance(HexOrientation::Flat, (2, 1), (3, 2)), 1);
+        assert_eq!(hex_distance(HexOrientation::Flat, (1, 1), (3, 2)), 2);
+        assert_eq!(hex_distance(HexOrientation::Pointy, (1, 2), (1, 1)), 1);
+        assert_eq!(hex_distance(HexOrientation::Pointy, (2, 2), (3, 1)), 1);
+    }
+}
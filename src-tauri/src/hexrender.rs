@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, OnceLock};
+
+use resvg::{tiny_skia, usvg};
+use serde::Deserialize;
+
+use crate::hexm::{HexCell, HexMapFile, HexOrientation, RouteKind};
+
+// Renders a `.hexm` map to SVG (and PNG through resvg). This is the renderer
+// the map loader uses; its output at scale 1 without a legend is the same
+// document the webview's old `renderHexMapSvg` built, so existing maps look
+// unchanged. The golden files in `tests/golden/` pin it down.
+
+const PAD: f64 = 6.0;
+const DEFAULT_BACKGROUND: &str = "#14110d";
+const FALLBACK_FILL: &str = "#3a352c";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    #[error("could not parse the rendered SVG: {0}")]
+    Svg(#[from] usvg::Error),
+    #[error("a {width} x {height} image is too large to rasterize")]
+    TooLarge { width: u32, height: u32 },
+    #[error("could not encode PNG: {0}")]
+    Png(String),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RenderOptions {
+    // Hex labels (the DM's render); players get them hidden.
+    pub show_labels: bool,
+    // A swatch per terrain in the bottom-left corner.
+    pub legend: bool,
+    // Output pixels per map pixel.
+    pub scale: f64,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            show_labels: true,
+            legend: false,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Size of the rendered map in map pixels (before `scale`). Matches
+/// `hexExtent` in `src/hexmap.ts`, which the webview uses for `imageWidth`.
+pub fn map_size(map: &HexMapFile) -> (f64, f64) {
+    let radius = map.radius();
+    let (cols, rows) = (map.cols.max(1), map.rows.max(1));
+    let (mut max_x, mut max_y) = (0f64, 0f64);
+    for (col, row) in [
+        (cols, rows),
+        (cols, 1),
+        (1, rows),
+        (if cols - 1 < 1 { 1 } else { cols }, rows),
+    ] {
+        let (cx, cy) = hex_center(map.orientation, radius, col, row);
+        for (vx, vy) in hex_vertices(map.orientation, radius, cx, cy) {
+            max_x = max_x.max(vx);
+            max_y = max_y.max(vy);
+        }
+    }
+    ((max_x + PAD).ceil(), (max_y + PAD).ceil())
+}
+
+// Centre of the 1-based hex (col, row); see `hexCenter` in `src/hexmap.ts`.
+pub(crate) fn hex_center(
+    orientation: HexOrientation,
+    radius: f64,
+    col: i64,
+    row: i64,
+) -> (f64, f64) {
+    let (c, r) = ((col - 1) as f64, (row - 1) as f64);
+    let sqrt3 = 3f64.sqrt();
+    match orientation {
+        HexOrientation::Pointy => {
+            let w = sqrt3 * radius;
+            (
+                PAD + w / 2.0 + w * (c + 0.5 * ((row - 1) & 1) as f64),
+                PAD + radius + 1.5 * radius * r,
+            )
+        }
+        HexOrientation::Flat => {
+            let h = sqrt3 * radius;
+            (
+                PAD + radius + 1.5 * radius * c,
+                PAD + h / 2.0 + h * (r + 0.5 * ((col - 1) & 1) as f64),
+            )
+        }
+    }
+}
+
+fn hex_vertices(orientation: HexOrientation, radius: f64, cx: f64, cy: f64) -> [(f64, f64); 6] {
+    let start = if orientation == HexOrientation::Pointy {
+        30.0
+    } else {
+        0.0
+    };
+    std::array::from_fn(|i| {
+        let angle = (start + 60.0 * i as f64).to_radians();
+        (cx + radius * angle.cos(), cy + radius * angle.sin())
+    })
+}
+
+pub fn render_svg(map: &HexMapFile, options: &RenderOptions) -> String {
+    let (width, height) = map_size(map);
+    let radius = map.radius();
+    let orientation = map.orientation;
+    let background = map
+        .background
+        .as_deref()
+        .filter(|bg| !bg.is_empty())
+        .unwrap_or(DEFAULT_BACKGROUND);
+
+    let by_coord: HashMap<(i64, i64), &HexCell> = map
+        .hexes
+        .iter()
+        .map(|cell| ((cell.col, cell.row), cell))
+        .collect();
+    let fill_for = |cell: Option<&&HexCell>| {
+        cell.and_then(|c| c.terrain.as_ref())
+            .or(map.default_terrain.as_ref())
+            .and_then(|key| map.terrains.get(key))
+            .filter(|fill| !fill.is_empty())
+            .map_or(FALLBACK_FILL, String::as_str)
+    };
+
+    let mut svg = String::new();
+    let scale = if options.scale > 0.0 {
+        options.scale
+    } else {
+        1.0
+    };
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {width} {height}">"#,
+        width * scale,
+        height * scale
+    );
+    let _ = write!(
+        svg,
+        r#"<rect width="{width}" height="{height}" fill="{background}"/>"#
+    );
+
+    let label_size = (radius * 0.42).round().max(8.0);
+    let mut labels = String::new(); // drawn last so roads don't cover them
+    for col in 1..=map.cols {
+        for row in 1..=map.rows {
+            let cell = by_coord.get(&(col, row));
+            let (cx, cy) = hex_center(orientation, radius, col, row);
+            let points: Vec<String> = hex_vertices(orientation, radius, cx, cy)
+                .iter()
+                .map(|(x, y)| format!("{x:.1},{y:.1}"))
+                .collect();
+            let _ = write!(
+                svg,
+                r##"<polygon points="{}" fill="{}" stroke="#0008" stroke-width="1"/>"##,
+                points.join(" "),
+                fill_for(cell)
+            );
+            if let Some(label) = cell
+                .and_then(|c| c.label.as_deref())
+                .filter(|l| options.show_labels && !l.is_empty())
+            {
+                let _ = write!(
+                    labels,
+                    r##"<text x="{cx:.1}" y="{:.1}" font-family="sans-serif" font-size="{label_size}" font-weight="bold" fill="#fff" text-anchor="middle" paint-order="stroke" stroke="#0009" stroke-width="2">{}</text>"##,
+                    cy + label_size * 0.35,
+                    escape_xml(label)
+                );
+            }
+        }
+    }
+
+    // Roads & rivers: polylines through hex centres, over terrain and under
+    // labels.
+    for route in &map.roads {
+        if route.path.len() < 2 {
+            continue;
+        }
+        let points: Vec<String> = route
+            .path
+            .iter()
+            .map(|&(col, row)| {
+                let (x, y) = hex_center(orientation, radius, col, row);
+                format!("{x:.1},{y:.1}")
+            })
+            .collect();
+        let points = points.join(" ");
+        match route.kind {
+            RouteKind::River => {
+                let _ = write!(
+                    svg,
+                    r##"<polyline points="{points}" fill="none" stroke="#2f5a86" stroke-width="{:.1}" stroke-linecap="round" stroke-linejoin="round" opacity="0.9"/>"##,
+                    radius * 0.22
+                );
+            }
+            RouteKind::Road => {
+                // Dark casing under a dashed tan line.
+                let _ = write!(
+                    svg,
+                    r##"<polyline points="{points}" fill="none" stroke="#2c2415" stroke-width="{:.1}" stroke-linecap="round" stroke-linejoin="round"/>"##,
+                    radius * 0.30
+                );
+                let _ = write!(
+                    svg,
+                    r##"<polyline points="{points}" fill="none" stroke="#decfa0" stroke-width="{:.1}" stroke-linecap="round" stroke-linejoin="round" stroke-dasharray="{:.1} {:.1}"/>"##,
+                    radius * 0.15,
+                    radius * 0.5,
+                    radius * 0.3
+                );
+            }
+        }
+    }
+
+    svg.push_str(&labels);
+
+    if let Some(title) = map.title.as_deref().filter(|t| !t.is_empty()) {
+        let _ = write!(
+            svg,
+            r##"<text x="{}" y="{}" font-family="serif" font-size="18" fill="#eee">{}</text>"##,
+            PAD + 4.0,
+            PAD + 18.0,
+            escape_xml(title)
+        );
+    }
+    if options.legend && !map.terrains.is_empty() {
+        write_legend(&mut svg, map, height);
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+// One swatch + name per terrain, boxed in the bottom-left corner.
+fn write_legend(svg: &mut String, map: &HexMapFile, height: f64) {
+    const ROW: f64 = 16.0;
+    let entries: Vec<(&str, &str)> = map
+        .terrains
+        .iter()
+        .map(|(key, fill)| {
+            let label = map
+                .terrain_labels
+                .as_ref()
+                .and_then(|labels| labels.get(key))
+                .unwrap_or(key);
+            (label.as_str(), fill.as_str())
+        })
+        .collect();
+    let longest = entries
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let box_width = 28.0 + longest as f64 * 7.0;
+    let box_height = 8.0 + entries.len() as f64 * ROW;
+    let (x, y) = (PAD + 4.0, height - PAD - 4.0 - box_height);
+    let _ = write!(
+        svg,
+        r##"<g font-family="sans-serif" font-size="12"><rect x="{x}" y="{y}" width="{box_width}" height="{box_height}" rx="4" fill="#000a"/>"##
+    );
+    for (i, (label, fill)) in entries.iter().enumerate() {
+        let row_y = y + 4.0 + i as f64 * ROW;
+        let _ = write!(
+            svg,
+            r##"<rect x="{}" y="{}" width="12" height="12" fill="{fill}" stroke="#0008"/><text x="{}" y="{}" fill="#eee">{}</text>"##,
+            x + 6.0,
+            row_y + 2.0,
+            x + 22.0,
+            row_y + 12.0,
+            escape_xml(label)
+        );
+    }
+    svg.push_str("</g>");
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// System fonts, loaded once: scanning them takes longer than a render.
+fn fonts() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = usvg::fontdb::Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+pub fn render_png(map: &HexMapFile, options: &RenderOptions) -> Result<Vec<u8>, RenderError> {
+    let svg = render_svg(map, options);
+    let tree = usvg::Tree::from_str(
+        &svg,
+        &usvg::Options {
+            fontdb: fonts(),
+            ..Default::default()
+        },
+    )?;
+    let size = tree.size().to_int_size();
+    let (width, height) = (size.width(), size.height());
+    let mut pixmap =
+        tiny_skia::Pixmap::new(width, height).ok_or(RenderError::TooLarge { width, height })?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap
+        .encode_png()
+        .map_err(|e| RenderError::Png(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hexm;
+
+    fn fixture() -> HexMapFile {
+        hexm::parse(include_str!("../tests/fixtures/hexmaps/valid.hexm"))
+            .map
+            .expect("fixture parses")
+    }
+
+    // Set `UPDATE_GOLDEN=1` to rewrite the golden file after an intended
+    // change, then review the diff.
+    fn assert_golden(name: &str, actual: &str) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, actual).expect("write golden file");
+        }
+        let expected = std::fs::read_to_string(&path).expect("golden file exists");
+        assert_eq!(
+            actual,
+            expected.trim_end(),
+            "{name} differs from the golden file"
+        );
+    }
+
+    #[test]
+    fn dm_svg_matches_golden() {
+        assert_golden(
+            "valid-dm.svg",
+            &render_svg(&fixture(), &RenderOptions::default()),
+        );
+    }
+
+    #[test]
+    fn player_svg_with_legend_matches_golden() {
+        let options = RenderOptions {
+            show_labels: false,
+            legend: true,
+            scale: 2.0,
+        };
+        assert_golden("valid-player-legend.svg", &render_svg(&fixture(), &options));
+    }
+
+    #[test]
+    fn png_is_scaled_and_filled_by_terrain() {
+        let map = fixture();
+        let options = RenderOptions {
+            show_labels: false,
+            legend: false,
+            scale: 0.5,
+        };
+        let png = render_png(&map, &options).expect("renders");
+        let image = tiny_skia::Pixmap::decode_png(&png).expect("valid PNG");
+        let (width, height) = map_size(&map);
+        assert_eq!(
+            (image.width(), image.height()),
+            ((width * 0.5).ceil() as u32, (height * 0.5).ceil() as u32)
+        );
+
+        // Hex 04.03 is grassland (#8fae5a), with nothing drawn over its centre.
+        let (cx, cy) = hex_center(map.orientation, map.radius(), 4, 3);
+        let pixel = image
+            .pixel((cx * 0.5) as u32, (cy * 0.5) as u32)
+            .expect("in bounds")
+            .demultiply();
+        assert_eq!(
+            (pixel.red(), pixel.green(), pixel.blue()),
+            (0x8f, 0xae, 0x5a)
+        );
+    }
+}
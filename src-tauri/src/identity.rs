@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::persistence::{self, PersistError};
+
+const INDEX_FILE: &str = "index.json";
+
+// Serializes read-modify-write cycles on the index; saves and loads arrive
+// as independent async commands.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Content fingerprint of a map file. Two files with the same bytes are the
+/// same map, wherever they live on disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapIdentity {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl MapIdentity {
+    pub fn key(&self) -> String {
+        format!("{}-{}", self.sha256, self.size)
+    }
+}
+
+pub fn fingerprint(path: &Path) -> io::Result<MapIdentity> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok(MapIdentity {
+        sha256: format!("{:x}", hasher.finalize()),
+        size,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PathEntry {
+    save: String,
+    identity: MapIdentity,
+    // Size + mtime when `identity` was computed, so unchanged files aren't
+    // re-hashed on every autosave.
+    modified_ms: u128,
+}
+
+/// `<save dir>/index.json`: finds the save file for a map by content
+/// fingerprint (so moved/renamed maps re-attach) and by last-known path (so
+/// maps edited in place keep their fog).
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MapIndex {
+    by_fingerprint: BTreeMap<String, String>,
+    by_path: BTreeMap<String, PathEntry>,
+}
+
+impl MapIndex {
+    fn load(save_dir: &Path) -> Result<Self, PersistError> {
+        let path = save_dir.join(INDEX_FILE);
+        match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| PersistError::Corrupt { path, source: e }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(PersistError::Io { path, source: e }),
+        }
+    }
+
+    fn store(&self, save_dir: &Path) -> Result<(), PersistError> {
+        let path = save_dir.join(INDEX_FILE);
+        let json = serde_json::to_vec_pretty(self).map_err(|e| PersistError::Corrupt {
+            path: path.clone(),
+            source: e,
+        })?;
+        persistence::write_atomic(&path, &json, 1)
+    }
+
+    // Fingerprint `map_path`, reusing the cached hash when the file's size
+    // and mtime are unchanged. A map that has gone missing mid-session keeps
+    // its last known identity so autosave still has somewhere to write.
+    fn identify(&self, map_path: &str) -> Result<(MapIdentity, u128), PersistError> {
+        let cached = self.by_path.get(map_path);
+        let meta = match fs::metadata(map_path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                return match cached {
+                    Some(entry) => Ok((entry.identity.clone(), entry.modified_ms)),
+                    None => Err(PersistError::Io {
+                        path: map_path.into(),
+                        source: e,
+                    }),
+                }
+            }
+        };
+        let modified_ms = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_millis());
+        if let Some(entry) = cached {
+            if entry.identity.size == meta.len() && entry.modified_ms == modified_ms {
+                return Ok((entry.identity.clone(), modified_ms));
+            }
+        }
+        let identity = fingerprint(Path::new(map_path)).map_err(|e| PersistError::Io {
+            path: map_path.into(),
+            source: e,
+        })?;
+        Ok((identity, modified_ms))
+    }
+
+    // Returns whether anything changed (and the index needs storing).
+    fn remember(
+        &mut self,
+        map_path: &str,
+        identity: MapIdentity,
+        modified_ms: u128,
+        save: &str,
+    ) -> bool {
+        let by_fingerprint = self.by_fingerprint.insert(identity.key(), save.to_string());
+        let entry = PathEntry {
+            save: save.to_string(),
+            identity,
+            modified_ms,
+        };
+        let by_path = self.by_path.insert(map_path.to_string(), entry.clone());
+        by_fingerprint.as_deref() != Some(save) || by_path.as_ref() != Some(&entry)
+    }
+}
+
+// Name for a brand-new save file: readable stem + a prefix of the content
+// hash, which (unlike the old 32-bit path hash) won't collide in practice.
+fn new_save_name(map_path: &str, identity: &MapIdentity) -> String {
+    let stem = Path::new(map_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("map");
+    let safe: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(50)
+        .collect();
+    format!("{}_{}", safe, &identity.sha256[..16])
+}
+
+/// Resolve (and record) the save file for a map. Lookup order:
+/// 1. content fingerprint — the same map, possibly moved or renamed
+/// 2. last-known path — the same map, edited in place
+/// 3. the legacy path-hash file name from before the index existed
+///
+/// and otherwise a fresh name. Returns `None` from step 3 onwards when
+/// `create` is false and no save exists yet.
+pub fn resolve_save_file(
+    save_dir: &Path,
+    map_path: &str,
+    create: bool,
+) -> Result<Option<PathBuf>, PersistError> {
+    let _guard = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut index = MapIndex::load(save_dir)?;
+    let (identity, modified_ms) = index.identify(map_path)?;
+
+    let save = index
+        .by_fingerprint
+        .get(&identity.key())
+        .cloned()
+        .or_else(|| index.by_path.get(map_path).map(|entry| entry.save.clone()))
+        .or_else(|| {
+            let legacy = persistence::save_file_name(map_path);
+            save_dir
+                .join(format!("{}.json", legacy))
+                .exists()
+                .then_some(legacy)
+        });
+    let save = match save {
+        Some(save) => save,
+        None if create => new_save_name(map_path, &identity),
+        None => return Ok(None),
+    };
+
+    if index.remember(map_path, identity, modified_ms, &save) {
+        index.store(save_dir)?;
+    }
+    Ok(Some(save_dir.join(format!("{}.json", save))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    fn path_str(path: &Path) -> &str {
+        path.to_str().unwrap()
+    }
+
+    #[test]
+    fn finds_moved_and_edited_maps() {
+        let dir = scratch_dir("moved");
+        let saves = dir.join("saves");
+        let map = dir.join("cave.png");
+        fs::write(&map, b"cave v1").unwrap();
+        let save = resolve_save_file(&saves, path_str(&map), true)
+            .unwrap()
+            .unwrap();
+        assert!(save
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("cave_"));
+
+        // Moved and renamed: found by content.
+        let moved = dir.join("dungeon-level-1.png");
+        fs::rename(&map, &moved).unwrap();
+        assert_eq!(
+            resolve_save_file(&saves, path_str(&moved), false).unwrap(),
+            Some(save.clone())
+        );
+
+        // Edited in place: found by path.
+        fs::write(&moved, b"cave v2, with a new tunnel").unwrap();
+        assert_eq!(
+            resolve_save_file(&saves, path_str(&moved), false).unwrap(),
+            Some(save.clone())
+        );
+        // ...and now by its new content too, from anywhere.
+        let copy = dir.join("copy.png");
+        fs::copy(&moved, &copy).unwrap();
+        assert_eq!(
+            resolve_save_file(&saves, path_str(&copy), false).unwrap(),
+            Some(save)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn falls_back_to_legacy_names_and_none() {
+        let dir = scratch_dir("legacy");
+        let saves = dir.join("saves");
+        let map = dir.join("old map.jpg");
+        fs::write(&map, b"old").unwrap();
+        assert_eq!(
+            resolve_save_file(&saves, path_str(&map), false).unwrap(),
+            None
+        );
+        // Looking without creating leaves no trace.
+        assert!(!saves.join(INDEX_FILE).exists());
+
+        fs::create_dir_all(&saves).unwrap();
+        let legacy = saves.join(format!(
+            "{}.json",
+            persistence::save_file_name(path_str(&map))
+        ));
+        fs::write(&legacy, b"{}").unwrap();
+        assert_eq!(
+            resolve_save_file(&saves, path_str(&map), false).unwrap(),
+            Some(legacy)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stores_the_index_only_when_it_changes() {
+        let dir = scratch_dir("store");
+        let saves = dir.join("saves");
+        let map = dir.join("keep.png");
+        fs::write(&map, b"keep").unwrap();
+        resolve_save_file(&saves, path_str(&map), true).unwrap();
+        let index = saves.join(INDEX_FILE);
+        let long_ago = UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&index)
+            .unwrap()
+            .set_modified(long_ago)
+            .unwrap();
+
+        resolve_save_file(&saves, path_str(&map), true).unwrap();
+        resolve_save_file(&saves, path_str(&map), false).unwrap();
+        assert_eq!(fs::metadata(&index).unwrap().modified().unwrap(), long_ago);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,720 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+
+use crate::dice::{self, DiceRoll};
+use crate::sync::{
+    FullState, SharedHub, StateDelta, StatePatch, STATE_DELTA_EVENT, STATE_SYNC_EVENT,
+};
+use crate::tiles;
+
+// Optional LAN server: serves the player view to any browser on the local
+// network (phones, tablets, a TV browser) and pushes the same full-state,
+// delta and viewport events the player window gets, over a WebSocket.
+// Clients need the join code shown in the DM view. Players can also roll
+// dice from their browser; the server rolls them and hands the result to the
+// DM window, which adds it to the shared roll history.
+
+pub const VIEWPORT_SYNC_EVENT: &str = "vtt-viewport-sync";
+/// To the DM window: a `PlayerRoll`.
+pub const PLAYER_ROLL_EVENT: &str = "vtt-player-roll";
+/// To the client that asked: why its dice couldn't be rolled.
+pub const ROLL_ERROR_EVENT: &str = "vtt-roll-error";
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+// Unambiguous characters only (no 0/O, 1/I/L) — the code is read off a screen.
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const JOIN_CODE_LEN: usize = 6;
+
+/// A file from the bundled frontend (`index.html`, JS, CSS, ...).
+pub struct Asset {
+    pub bytes: Vec<u8>,
+    pub mime: String,
+}
+
+/// Where the server gets the frontend from; the app uses Tauri's embedded
+/// assets, tests use a stub.
+pub trait AssetSource: Send + Sync + 'static {
+    fn get(&self, path: &str) -> Option<Asset>;
+}
+
+/// A roll made by a LAN player. `by` is the name they gave, if any.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerRoll {
+    pub by: String,
+    pub roll: DiceRoll,
+}
+
+/// Receives player rolls; the app forwards them to the DM window.
+pub type RollHandler = Arc<dyn Fn(PlayerRoll) + Send + Sync>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanInfo {
+    pub port: u16,
+    pub join_code: String,
+    // Addresses to type into a browser, best guess first.
+    pub urls: Vec<String>,
+}
+
+struct MapImage {
+    bytes: Vec<u8>,
+    mime: String,
+    version: u64,
+}
+
+struct Shared {
+    join_code: String,
+    assets: Arc<dyn AssetSource>,
+    hub: SharedHub,
+    on_roll: RollHandler,
+    tx: broadcast::Sender<Arc<str>>,
+    // Last viewport, replayed to each client as it joins (after the state).
+    viewport: RwLock<Option<Arc<str>>>,
+    map_image: RwLock<Option<MapImage>>,
+    // Where `tiles.rs` keeps tile pyramids, served at `/tiles/...`.
+    tile_cache: PathBuf,
+    shutdown: watch::Receiver<bool>,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    event: &'a str,
+    payload: &'a T,
+}
+
+fn envelope<T: Serialize>(event: &str, payload: &T) -> Arc<str> {
+    serde_json::to_string(&Envelope { event, payload })
+        .expect("sync payloads serialize")
+        .into()
+}
+
+// Messages clients may send.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ClientMessage {
+    // Missed a delta (sequence gap): send the full state again.
+    Resync,
+    Roll {
+        expression: String,
+        #[serde(default)]
+        by: String,
+    },
+}
+
+impl Shared {
+    // Map image URLs in the state are blob URLs (or, for a tiled map, a
+    // `tiles://` URL) that only resolve in the app's own webviews. A tiled
+    // map's tiles are served at `/tiles/...`, so clients keep drawing only
+    // what's in view; anything else is pointed at `/map`, the whole image.
+    fn rewrite_image_urls(&self, map: &mut Value) {
+        let Some(map) = map.as_object_mut() else {
+            return;
+        };
+        if map.get("imageUrl").is_none_or(Value::is_null) {
+            return;
+        }
+        let tiles = map.get("tiles").and_then(|tiles| {
+            let id = tiles.get("id")?.as_str()?;
+            let levels = tiles.get("levels")?.as_u64()?;
+            Some((id.to_string(), levels))
+        });
+        let url = match tiles {
+            // The overview tile, as in the app (`overviewUrl` in `src/tiles.ts`).
+            Some((id, levels)) => format!(
+                "/tiles/{id}/{}/0/0?code={}",
+                levels.saturating_sub(1),
+                self.join_code
+            ),
+            None => {
+                let version = self
+                    .map_image
+                    .read()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .as_ref()
+                    .map_or(0, |i| i.version);
+                format!("/map?code={}&v={}", self.join_code, version)
+            }
+        };
+        map.insert("imageUrl".into(), Value::String(url));
+        map.insert("playerImageUrl".into(), Value::Null);
+    }
+
+    fn full_state_message(&self, full: &FullState) -> Arc<str> {
+        let mut full = full.clone();
+        if let Some(map) = full.state.get_mut("map") {
+            self.rewrite_image_urls(map);
+        }
+        envelope(STATE_SYNC_EVENT, &full)
+    }
+
+    fn current_state_message(&self) -> Option<Arc<str>> {
+        let full = self.hub.lock().unwrap_or_else(|e| e.into_inner()).full()?;
+        Some(self.full_state_message(&full))
+    }
+
+    // Rolls a player's dice and passes them on; a reply for the player only
+    // when the expression is bad.
+    fn player_roll(&self, expression: &str, by: &str) -> Option<Arc<str>> {
+        match dice::roll(expression, &mut rand::rng()) {
+            Ok(roll) => {
+                let by = by.trim();
+                let by = if by.is_empty() { "Player" } else { by };
+                (self.on_roll)(PlayerRoll {
+                    by: by.to_string(),
+                    roll,
+                });
+                None
+            }
+            Err(e) => Some(envelope(ROLL_ERROR_EVENT, &e.to_string())),
+        }
+    }
+
+    // No receivers just means no clients are connected yet.
+    fn broadcast(&self, message: Arc<str>) {
+        let _ = self.tx.send(message);
+    }
+}
+
+pub struct LanServer {
+    shared: Arc<Shared>,
+    addr: SocketAddr,
+    shutdown: watch::Sender<bool>,
+}
+
+impl LanServer {
+    /// Bind `addr` and start serving on the current Tokio runtime. New
+    /// clients get the hub's current state; their dice rolls go to `on_roll`.
+    pub async fn start(
+        addr: SocketAddr,
+        assets: Arc<dyn AssetSource>,
+        hub: SharedHub,
+        tile_cache: PathBuf,
+        on_roll: RollHandler,
+    ) -> io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let (tx, _) = broadcast::channel(64);
+        let shared = Arc::new(Shared {
+            join_code: new_join_code(),
+            assets,
+            hub,
+            on_roll,
+            tx,
+            viewport: RwLock::new(None),
+            map_image: RwLock::new(None),
+            tile_cache,
+            shutdown: shutdown_rx.clone(),
+        });
+
+        let app = Router::new()
+            .route("/ws", get(ws_handler))
+            .route("/map", get(map_handler))
+            .route("/tiles/{*path}", get(tile_handler))
+            .fallback(get(asset_handler))
+            .with_state(shared.clone());
+        let mut stop = shutdown_rx;
+        tokio::spawn(async move {
+            let serve = axum::serve(listener, app).with_graceful_shutdown(async move {
+                let _ = stop.wait_for(|stopped| *stopped).await;
+            });
+            if let Err(e) = serve.await {
+                eprintln!("LAN server stopped: {e}");
+            }
+        });
+
+        Ok(LanServer {
+            shared,
+            addr,
+            shutdown,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn join_code(&self) -> &str {
+        &self.shared.join_code
+    }
+
+    pub fn info(&self) -> LanInfo {
+        let port = self.addr.port();
+        let code = &self.shared.join_code;
+        let mut hosts: Vec<String> = Vec::new();
+        if let Some(ip) = lan_ip() {
+            hosts.push(ip.to_string());
+        }
+        hosts.push("localhost".into());
+        LanInfo {
+            port,
+            join_code: code.clone(),
+            urls: hosts
+                .into_iter()
+                .map(|host| format!("http://{host}:{port}/?code={code}"))
+                .collect(),
+        }
+    }
+
+    pub fn publish_full(&self, full: &FullState) {
+        self.shared.broadcast(self.shared.full_state_message(full));
+    }
+
+    pub fn publish_delta(&self, delta: &StateDelta) {
+        let mut delta = delta.clone();
+        for patch in &mut delta.patches {
+            if let StatePatch::Set { field, value } = patch {
+                if field == "map" {
+                    self.shared.rewrite_image_urls(value);
+                }
+            }
+        }
+        self.shared.broadcast(envelope(STATE_DELTA_EVENT, &delta));
+    }
+
+    pub fn publish_viewport(&self, viewport: &Value) {
+        let message = envelope(VIEWPORT_SYNC_EVENT, viewport);
+        *self
+            .shared
+            .viewport
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(message.clone());
+        self.shared.broadcast(message);
+    }
+
+    /// Replace the image served at `/map` (the player-facing map image), and
+    /// resend the state so clients fetch the new version.
+    pub fn set_map_image(&self, bytes: Vec<u8>, mime: String) {
+        {
+            let mut image = self
+                .shared
+                .map_image
+                .write()
+                .unwrap_or_else(|e| e.into_inner());
+            let version = image.as_ref().map_or(1, |i| i.version + 1);
+            *image = Some(MapImage {
+                bytes,
+                mime,
+                version,
+            });
+        }
+        if let Some(message) = self.shared.current_state_message() {
+            self.shared.broadcast(message);
+        }
+    }
+}
+
+impl Drop for LanServer {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+fn new_join_code() -> String {
+    let mut rng = rand::rng();
+    (0..JOIN_CODE_LEN)
+        .map(|_| JOIN_CODE_ALPHABET[rng.random_range(0..JOIN_CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+// The address other machines on the LAN can reach us at. "Connecting" a UDP
+// socket sends nothing; it just makes the OS pick the outbound interface.
+fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 80)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified() && !ip.is_loopback()).then_some(ip)
+}
+
+#[derive(Deserialize)]
+struct CodeQuery {
+    code: Option<String>,
+}
+
+impl CodeQuery {
+    fn matches(&self, shared: &Shared) -> bool {
+        self.code
+            .as_deref()
+            .is_some_and(|code| code.eq_ignore_ascii_case(&shared.join_code))
+    }
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(query): Query<CodeQuery>,
+    State(shared): State<Arc<Shared>>,
+) -> Response {
+    if !query.matches(&shared) {
+        return (StatusCode::FORBIDDEN, "wrong join code").into_response();
+    }
+    ws.on_upgrade(move |socket| client_loop(socket, shared))
+}
+
+async fn client_loop(mut socket: WebSocket, shared: Arc<Shared>) {
+    let mut rx = shared.tx.subscribe();
+    let mut shutdown = shared.shutdown.clone();
+
+    // Catch the new client up: state first, then the viewport framed on it.
+    let viewport = shared
+        .viewport
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    for message in [shared.current_state_message(), viewport]
+        .into_iter()
+        .flatten()
+    {
+        if send(&mut socket, &message).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        let outgoing = tokio::select! {
+            received = rx.recv() => match received {
+                Ok(message) => Some(message),
+                // Fell behind (slow phone on bad Wi-Fi): skip to the newest state.
+                Err(broadcast::error::RecvError::Lagged(_)) => shared.current_state_message(),
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(ClientMessage::Resync) => shared.current_state_message(),
+                    Ok(ClientMessage::Roll { expression, by }) => shared.player_roll(&expression, &by),
+                    Err(_) => None,
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => None,
+            },
+            // The sender only ever flips to `true` (or is dropped).
+            _ = shutdown.changed() => {
+                let _ = socket.send(Message::Close(None)).await;
+                return;
+            }
+        };
+        if let Some(message) = outgoing {
+            if send(&mut socket, &message).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &str) -> Result<(), axum::Error> {
+    socket.send(Message::Text(message.into())).await
+}
+
+async fn map_handler(
+    Query(query): Query<CodeQuery>,
+    State(shared): State<Arc<Shared>>,
+) -> Response {
+    if !query.matches(&shared) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let image = shared.map_image.read().unwrap_or_else(|e| e.into_inner());
+    match image.as_ref() {
+        Some(image) => (
+            [
+                (header::CONTENT_TYPE, image.mime.clone()),
+                (header::CACHE_CONTROL, "no-cache".into()),
+            ],
+            image.bytes.clone(),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// A tile of a tiled map, as on the app's `tiles://` scheme.
+async fn tile_handler(
+    Path(path): Path<String>,
+    Query(query): Query<CodeQuery>,
+    State(shared): State<Arc<Shared>>,
+) -> Response {
+    if !query.matches(&shared) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let Some(file) = tiles::tile_path(&shared.tile_cache, &path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // Tiles are small; not worth a blocking-pool hop.
+    match std::fs::read(file) {
+        // Tiles are named by content, so they never go stale.
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "image/png"),
+                (header::CACHE_CONTROL, "max-age=31536000, immutable"),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn asset_handler(uri: Uri, State(shared): State<Arc<Shared>>) -> Response {
+    let path = uri.path().trim_start_matches('/');
+    let path = if path.is_empty() { "index.html" } else { path };
+    match shared.assets.get(path) {
+        Some(asset) => ([(header::CONTENT_TYPE, asset.mime)], asset.bytes).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::{StatePatch, SyncHub};
+    use crate::test_util::scratch_dir;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::json;
+    use std::fs;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    struct StubAssets;
+
+    impl AssetSource for StubAssets {
+        fn get(&self, path: &str) -> Option<Asset> {
+            (path == "index.html").then(|| Asset {
+                bytes: b"<html>player</html>".to_vec(),
+                mime: "text/html".into(),
+            })
+        }
+    }
+
+    async fn start() -> (LanServer, SharedHub) {
+        let (server, hub, _rolls) = start_with_rolls().await;
+        (server, hub)
+    }
+
+    async fn start_with_rolls() -> (LanServer, SharedHub, Arc<Mutex<Vec<PlayerRoll>>>) {
+        start_with(std::env::temp_dir().join("vtt-lan-no-tiles")).await
+    }
+
+    async fn start_with(
+        tile_cache: PathBuf,
+    ) -> (LanServer, SharedHub, Arc<Mutex<Vec<PlayerRoll>>>) {
+        let hub = SharedHub::new(Mutex::new(SyncHub::default()));
+        let rolls = Arc::new(Mutex::new(Vec::new()));
+        let sink = rolls.clone();
+        let on_roll: RollHandler = Arc::new(move |roll| sink.lock().unwrap().push(roll));
+        let server = LanServer::start(
+            ([127, 0, 0, 1], 0).into(),
+            Arc::new(StubAssets),
+            hub.clone(),
+            tile_cache,
+            on_roll,
+        )
+        .await
+        .expect("bind loopback");
+        (server, hub, rolls)
+    }
+
+    fn ws_url(server: &LanServer) -> String {
+        format!(
+            "ws://{}/ws?code={}",
+            server.local_addr(),
+            server.join_code().to_lowercase()
+        )
+    }
+
+    async fn http_get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    async fn next_json<S>(ws: &mut S) -> Value
+    where
+        S: StreamExt<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        let message = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("message before timeout")
+            .expect("stream open")
+            .expect("valid frame");
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_player_page() {
+        let (server, _hub) = start().await;
+        let response = http_get(server.local_addr(), "/").await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("<html>player</html>"));
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_join_code() {
+        let (server, _hub) = start().await;
+        let url = format!("ws://{}/ws?code=NOPE", server.local_addr());
+        assert!(tokio_tungstenite::connect_async(url).await.is_err());
+        let response = http_get(server.local_addr(), "/map?code=NOPE").await;
+        assert!(response.starts_with("HTTP/1.1 403"));
+    }
+
+    #[tokio::test]
+    async fn serves_tiles_to_clients_with_the_join_code() {
+        let cache = scratch_dir("lan-tiles");
+        fs::create_dir_all(cache.join("abc/1")).unwrap();
+        fs::write(cache.join("abc/1/2_3.png"), b"png").unwrap();
+        let (server, _hub, _rolls) = start_with(cache.clone()).await;
+        let addr = server.local_addr();
+
+        let response = http_get(
+            addr,
+            &format!("/tiles/abc/1/2/3?code={}", server.join_code()),
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("content-type: image/png"));
+        assert!(response.ends_with("png"));
+        assert!(http_get(addr, "/tiles/abc/1/2/3?code=NOPE")
+            .await
+            .starts_with("HTTP/1.1 403"));
+        let code = server.join_code();
+        for missing in ["/tiles/abc/1/2/4", "/tiles/../abc/1/2/3", "/tiles/abc/1/2"] {
+            let response = http_get(addr, &format!("{missing}?code={code}")).await;
+            assert!(response.starts_with("HTTP/1.1 404"), "{missing}");
+        }
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[tokio::test]
+    async fn pushes_state_deltas_and_viewport_to_clients() {
+        let (server, hub) = start().await;
+        // Already in the hub before the client joins: sent on connect.
+        let state = json!({
+            "map": { "imageUrl": "tiles://localhost/abc/2/0/0", "playerImageUrl": null, "tiles": { "id": "abc", "levels": 3 } },
+            "drawings": [],
+        });
+        hub.lock().unwrap().replace(state);
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(ws_url(&server))
+            .await
+            .expect("connect");
+
+        let full = next_json(&mut ws).await;
+        assert_eq!(full["event"], STATE_SYNC_EVENT);
+        assert_eq!(full["payload"]["seq"], 1);
+        let image_url = full["payload"]["state"]["map"]["imageUrl"]
+            .as_str()
+            .unwrap();
+        assert_eq!(
+            image_url,
+            format!("/tiles/abc/2/0/0?code={}", server.join_code())
+        );
+        assert_eq!(full["payload"]["state"]["map"]["tiles"]["id"], "abc");
+
+        // Changes while connected arrive as numbered deltas.
+        let drawing = json!({ "id": "d1", "points": [], "color": "#f00", "strokeWidth": 3.0 });
+        let delta = hub
+            .lock()
+            .unwrap()
+            .apply(vec![StatePatch::DrawingAdded {
+                drawing: serde_json::from_value(drawing.clone()).unwrap(),
+            }])
+            .unwrap()
+            .expect("a visible change");
+        server.publish_delta(&delta);
+        let pushed = next_json(&mut ws).await;
+        assert_eq!(pushed["event"], STATE_DELTA_EVENT);
+        assert_eq!(pushed["payload"]["seq"], 2);
+        assert_eq!(
+            pushed["payload"]["patches"],
+            json!([{ "type": "drawingAdded", "drawing": drawing }])
+        );
+
+        let viewport = json!({ "x": 1.0, "y": 2.0, "width": 300.0, "height": 200.0 });
+        server.publish_viewport(&viewport);
+        let pushed = next_json(&mut ws).await;
+        assert_eq!(pushed["event"], VIEWPORT_SYNC_EVENT);
+        assert_eq!(pushed["payload"], viewport);
+    }
+
+    #[tokio::test]
+    async fn resends_full_state_on_request() {
+        let (server, hub) = start().await;
+        hub.lock().unwrap().replace(json!({ "drawings": [] }));
+        let (mut ws, _) = tokio_tungstenite::connect_async(ws_url(&server))
+            .await
+            .expect("connect");
+        assert_eq!(next_json(&mut ws).await["event"], STATE_SYNC_EVENT);
+
+        ws.send(WsMessage::text(r#"{"type":"resync"}"#))
+            .await
+            .unwrap();
+        let full = next_json(&mut ws).await;
+        assert_eq!(full["event"], STATE_SYNC_EVENT);
+        assert_eq!(full["payload"]["state"], json!({ "drawings": [] }));
+    }
+
+    #[tokio::test]
+    async fn serves_uploaded_map_image() {
+        let (server, _hub) = start().await;
+        server.set_map_image(b"PNGDATA".to_vec(), "image/png".into());
+        let path = format!("/map?code={}", server.join_code());
+        let response = http_get(server.local_addr(), &path).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("content-type: image/png"));
+        assert!(response.ends_with("PNGDATA"));
+    }
+
+    #[tokio::test]
+    async fn rolls_player_dice_for_the_dm() {
+        let (server, hub, rolls) = start_with_rolls().await;
+        hub.lock().unwrap().replace(json!({ "drawings": [] }));
+        let (mut ws, _) = tokio_tungstenite::connect_async(ws_url(&server))
+            .await
+            .expect("connect");
+        assert_eq!(next_json(&mut ws).await["event"], STATE_SYNC_EVENT);
+
+        // A bad expression is answered to the player alone.
+        ws.send(WsMessage::text(
+            r#"{"type":"roll","expression":"2d","by":"Mira"}"#,
+        ))
+        .await
+        .unwrap();
+        let error = next_json(&mut ws).await;
+        assert_eq!(error["event"], ROLL_ERROR_EVENT);
+        assert!(rolls.lock().unwrap().is_empty());
+
+        ws.send(WsMessage::text(
+            r#"{"type":"roll","expression":"2d6+1","by":"Mira"}"#,
+        ))
+        .await
+        .unwrap();
+        for _ in 0..50 {
+            if !rolls.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let rolls = rolls.lock().unwrap();
+        assert_eq!(rolls.len(), 1);
+        assert_eq!(rolls[0].by, "Mira");
+        assert_eq!(rolls[0].roll.expression, "2d6+1");
+        assert!((3..=13).contains(&rolls[0].roll.total));
+    }
+}
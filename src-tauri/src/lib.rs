@@ -1,4 +1,51 @@
-use tauri::{Manager, WebviewUrl, WebviewWindowBuilder};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tauri::{Emitter, Listener, Manager, WebviewUrl, WebviewWindowBuilder};
+
+pub mod cli;
+pub mod dice;
+pub mod encounters;
+pub mod fog;
+pub mod griddetect;
+pub mod hexcrawl;
+pub mod hexm;
+pub mod hexrender;
+pub mod identity;
+pub mod lan;
+pub mod measure;
+pub mod migrate;
+pub mod persistence;
+pub mod projection;
+pub mod snapshots;
+pub mod sync;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod tiles;
+pub mod types;
+pub mod uvtt;
+pub mod visibility;
+pub mod watch;
+
+use dice::{DiceExpr, DiceRoll};
+use encounters::EncounterRoll;
+use fog::{Fog, FogState};
+use griddetect::GridEstimate;
+use hexcrawl::{TravelPlan, TravelRequest};
+use hexm::HexmReport;
+use hexrender::RenderOptions;
+use identity::MapIdentity;
+use lan::{LanInfo, LanServer};
+use measure::{Board, DistanceRule, Measurement, Template, TemplateArea};
+use persistence::{LoadedSave, PersistError};
+use snapshots::SnapshotMeta;
+use sync::{FullState, SharedHub, StatePatch};
+use tiles::TileSet;
+use types::{DrawingPoint, PartyState, RollRecord, SavedMapState, WallState};
+use uvtt::UvttMap;
+use visibility::GridGeometry;
+use watch::MapWatcher;
 
 #[tauri::command]
 async fn open_player_window(app: tauri::AppHandle) -> Result<(), String> {
@@ -16,13 +63,592 @@ async fn open_player_window(app: tauri::AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+// Directory holding one save file per map (`<app data>/maps`).
+fn app_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, PersistError> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| PersistError::AppDataDir(e.to_string()))
+}
+
+fn save_dir(app: &tauri::AppHandle) -> Result<PathBuf, PersistError> {
+    Ok(app_data_dir(app)?.join("maps"))
+}
+
+#[tauri::command]
+async fn save_map_state(app: tauri::AppHandle, state: SavedMapState) -> Result<(), PersistError> {
+    persistence::save_map_state(&save_dir(&app)?, &state)?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn load_map_state(
+    app: tauri::AppHandle,
+    map_file_path: String,
+) -> Result<Option<LoadedSave>, PersistError> {
+    persistence::load_map_state(&save_dir(&app)?, &map_file_path)
+}
+
+// The dice tray's history, global like the initiative roster.
+#[tauri::command]
+async fn save_rolls(app: tauri::AppHandle, rolls: Vec<RollRecord>) -> Result<(), PersistError> {
+    persistence::save_rolls(&app_data_dir(&app)?.join("dice-rolls.json"), &rolls)
+}
+
+// What a map file is known as: its content fingerprint, and the save file it
+// resolves to (`None` if it has never been saved).
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MapIdentityReport {
+    identity: MapIdentity,
+    save_file: Option<PathBuf>,
+}
+
+#[tauri::command]
+async fn map_identity(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<MapIdentityReport, PersistError> {
+    let identity = identity::fingerprint(path.as_ref()).map_err(|e| PersistError::Io {
+        path: path.clone().into(),
+        source: e,
+    })?;
+    let save_file = identity::resolve_save_file(&save_dir(&app)?, &path, false)?;
+    Ok(MapIdentityReport {
+        identity,
+        save_file,
+    })
+}
+
+// A `.hexm` map plus everything wrong with it. Only unreadable files fail;
+// problems inside a readable one come back as diagnostics.
+#[tauri::command]
+async fn load_hexmap(path: String) -> Result<HexmReport, String> {
+    hexm::load(path.as_ref()).map_err(|e| format!("{path}: {e}"))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ImageFormat {
+    Svg,
+    Png,
+}
+
+// Renders a `.hexm` map to SVG or PNG bytes (an `ArrayBuffer` in the webview).
+// A file that can't be read as a hex map at all is an error; lesser problems
+// are `load_hexmap`'s to report.
+#[tauri::command]
+async fn render_hexmap(
+    path: String,
+    format: ImageFormat,
+    options: Option<RenderOptions>,
+) -> Result<tauri::ipc::Response, String> {
+    let report = hexm::load(path.as_ref()).map_err(|e| format!("{path}: {e}"))?;
+    let Some(map) = report.map else {
+        let reason = report
+            .diagnostics
+            .first()
+            .map(|d| format!("line {}: {}", d.line, d.message));
+        return Err(format!(
+            "{path}: {}",
+            reason.unwrap_or_else(|| "not a hex map".into())
+        ));
+    };
+    let options = options.unwrap_or_default();
+    let bytes = match format {
+        ImageFormat::Svg => hexrender::render_svg(&map, &options).into_bytes(),
+        ImageFormat::Png => hexrender::render_png(&map, &options).map_err(|e| e.to_string())?,
+    };
+    Ok(tauri::ipc::Response::new(bytes))
+}
+
+// The fastest route between two hexes of a `.hexm` map and what each day of
+// it takes (see `hexcrawl.rs`).
+#[tauri::command]
+async fn plan_hex_travel(path: String, request: TravelRequest) -> Result<TravelPlan, String> {
+    let report = hexm::load(path.as_ref()).map_err(|e| format!("{path}: {e}"))?;
+    let map = report.map.ok_or_else(|| format!("{path}: not a hex map"))?;
+    hexcrawl::plan(&map, &request).map_err(|e| e.to_string())
+}
+
+// Writes the party's travel log on the `.hexm` at `map_path` to `path` as
+// Markdown.
+#[tauri::command]
+async fn export_travel_log(
+    map_path: String,
+    party: PartyState,
+    path: String,
+) -> Result<(), String> {
+    let report = hexm::load(map_path.as_ref()).map_err(|e| format!("{map_path}: {e}"))?;
+    let map = report
+        .map
+        .ok_or_else(|| format!("{map_path}: not a hex map"))?;
+    std::fs::write(&path, hexcrawl::travel_log_markdown(&map, &party))
+        .map_err(|e| format!("{path}: {e}"))
+}
+
+// Rolls a random encounter for `hex` of the `.hexm` at `map_path`, on the
+// tables in the `encounters` folder next to it (see `encounters.rs`).
+#[tauri::command]
+async fn roll_encounter(map_path: String, hex: (i64, i64)) -> Result<EncounterRoll, String> {
+    let report = hexm::load(map_path.as_ref()).map_err(|e| format!("{map_path}: {e}"))?;
+    let map = report
+        .map
+        .ok_or_else(|| format!("{map_path}: not a hex map"))?;
+    encounters::roll_for_hex(map_path.as_ref(), &map, hex, &mut rand::rng())
+        .map_err(|e| e.to_string())
+}
+
+// Rolls a dice expression (see `dice.rs`), with every die in the result.
+#[tauri::command]
+fn roll_dice(expression: String) -> Result<DiceRoll, String> {
+    dice::roll(&expression, &mut rand::rng()).map_err(|e| e.to_string())
+}
+
+// The normalised form of a dice expression, or why it can't be read.
+#[tauri::command]
+fn check_dice(expression: String) -> Result<String, String> {
+    expression
+        .parse::<DiceExpr>()
+        .map(|dice| dice.to_string())
+        .map_err(|e| e.to_string())
+}
+
+// Ruler distance between two points on the map (see `measure.rs`).
+#[tauri::command]
+fn measure_distance(
+    board: Board,
+    rule: DistanceRule,
+    ft_per_cell: f64,
+    from: DrawingPoint,
+    to: DrawingPoint,
+) -> Measurement {
+    measure::measure(&board, rule, ft_per_cell, from, to)
+}
+
+// The snapped origin and covered cells of an area-of-effect template.
+#[tauri::command]
+fn template_area(
+    board: Board,
+    rule: DistanceRule,
+    ft_per_cell: f64,
+    template: Template,
+) -> TemplateArea {
+    measure::template_area(&board, rule, ft_per_cell, &template)
+}
+
+// Estimates the grid drawn on an image map (see `griddetect.rs`).
+#[tauri::command]
+async fn detect_grid(path: String) -> Result<GridEstimate, String> {
+    griddetect::detect_file(path.as_ref()).map_err(|e| e.to_string())
+}
+
+// Directory holding the cached tile pyramids (`<app data>/tiles`).
+fn tiles_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("tiles"))
+        .map_err(|e| e.to_string())
+}
+
+// The tile pyramid of the image map at `path`, built on first load (see
+// `tiles.rs`); the views then fetch tiles from the `tiles://` scheme.
+#[tauri::command]
+async fn load_map_tiles(app: tauri::AppHandle, path: String) -> Result<TileSet, String> {
+    let cache = tiles_dir(&app)?;
+    // Cutting up a huge map takes a while; keep it off the async workers.
+    tauri::async_runtime::spawn_blocking(move || {
+        tiles::load(path.as_ref(), &cache).map_err(|e| format!("{path}: {e}"))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// A `tiles://` request: the tile's PNG, or 404. Tiles are named by content,
+// so they never go stale. The CORS header keeps the canvas untainted for
+// snapshot thumbnails.
+fn tile_response(app: &tauri::AppHandle, path: &str) -> tauri::http::Response<Vec<u8>> {
+    use tauri::http::{header, Response, StatusCode};
+    let file = tiles_dir(app)
+        .ok()
+        .and_then(|cache| tiles::tile_path(&cache, path));
+    let response = Response::builder().header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+    match file.map(std::fs::read) {
+        Some(Ok(bytes)) => response
+            .header(header::CONTENT_TYPE, "image/png")
+            .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
+            .body(bytes),
+        _ => response.status(StatusCode::NOT_FOUND).body(Vec::new()),
+    }
+    .unwrap_or_default()
+}
+
+// A `.dd2vtt` / `.uvtt` map: image, grid size and walls in one file.
+#[tauri::command]
+async fn load_uvtt(path: String) -> Result<UvttMap, String> {
+    uvtt::load(path.as_ref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_snapshots(
+    app: tauri::AppHandle,
+    map_file_path: String,
+) -> Result<Vec<SnapshotMeta>, PersistError> {
+    snapshots::list(&save_dir(&app)?, &map_file_path)
+}
+
+#[tauri::command]
+async fn create_snapshot(
+    app: tauri::AppHandle,
+    state: SavedMapState,
+    name: String,
+    thumbnail: Option<String>,
+) -> Result<SnapshotMeta, PersistError> {
+    snapshots::create(&save_dir(&app)?, &state, &name, thumbnail)
+}
+
+#[tauri::command]
+async fn rename_snapshot(
+    app: tauri::AppHandle,
+    map_file_path: String,
+    id: String,
+    name: String,
+) -> Result<SnapshotMeta, PersistError> {
+    snapshots::rename(&save_dir(&app)?, &map_file_path, &id, &name)
+}
+
+#[tauri::command]
+async fn duplicate_snapshot(
+    app: tauri::AppHandle,
+    map_file_path: String,
+    id: String,
+    name: String,
+) -> Result<SnapshotMeta, PersistError> {
+    snapshots::duplicate(&save_dir(&app)?, &map_file_path, &id, &name)
+}
+
+#[tauri::command]
+async fn delete_snapshot(
+    app: tauri::AppHandle,
+    map_file_path: String,
+    id: String,
+) -> Result<(), PersistError> {
+    snapshots::delete(&save_dir(&app)?, &map_file_path, &id)
+}
+
+#[tauri::command]
+async fn restore_snapshot(
+    app: tauri::AppHandle,
+    map_file_path: String,
+    id: String,
+) -> Result<serde_json::Value, PersistError> {
+    snapshots::restore(&save_dir(&app)?, &map_file_path, &id)
+        .map(|state| persistence::to_webview(&state))
+}
+
+// The LAN player server, when the DM has started it.
+#[derive(Default)]
+struct LanState(Mutex<Option<LanServer>>);
+
+impl LanState {
+    fn server(&self) -> std::sync::MutexGuard<'_, Option<LanServer>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Serves the bundled frontend (the same files the player window loads).
+struct AppAssets(tauri::AppHandle);
+
+impl lan::AssetSource for AppAssets {
+    fn get(&self, path: &str) -> Option<lan::Asset> {
+        self.0
+            .asset_resolver()
+            .get(path.to_string())
+            .map(|asset| lan::Asset {
+                bytes: asset.bytes,
+                mime: asset.mime_type,
+            })
+    }
+}
+
+#[tauri::command]
+async fn start_lan_server(
+    app: tauri::AppHandle,
+    lan: tauri::State<'_, LanState>,
+    port: Option<u16>,
+) -> Result<LanInfo, String> {
+    if let Some(server) = lan.server().as_ref() {
+        return Ok(server.info());
+    }
+    let addr = SocketAddr::from(([0, 0, 0, 0], port.unwrap_or(lan::DEFAULT_PORT)));
+    let hub = app.state::<SharedHub>().inner().clone();
+    let dm = app.clone();
+    let on_roll: lan::RollHandler = Arc::new(move |roll| {
+        let _ = dm.emit_to("dm", lan::PLAYER_ROLL_EVENT, roll);
+    });
+    let tile_cache = tiles_dir(&app)?;
+    let server = LanServer::start(addr, Arc::new(AppAssets(app)), hub, tile_cache, on_roll)
+        .await
+        .map_err(|e| format!("Could not start LAN server on {addr}: {e}"))?;
+    let info = server.info();
+    *lan.server() = Some(server);
+    Ok(info)
+}
+
+#[tauri::command]
+fn stop_lan_server(lan: tauri::State<'_, LanState>) {
+    // Dropping the server shuts it down and disconnects clients.
+    lan.server().take();
+}
+
+#[tauri::command]
+fn lan_server_info(lan: tauri::State<'_, LanState>) -> Option<LanInfo> {
+    lan.server().as_ref().map(LanServer::info)
+}
+
+// The map image as raw bytes (the webview's blob URLs mean nothing to other
+// machines), with its MIME type in the `content-type` header. Tiled maps
+// don't need it: the server serves their tiles itself.
+#[tauri::command]
+fn lan_set_map_image(
+    lan: tauri::State<'_, LanState>,
+    request: tauri::ipc::Request<'_>,
+) -> Result<(), String> {
+    let tauri::ipc::InvokeBody::Raw(bytes) = request.body() else {
+        return Err("expected raw image bytes".into());
+    };
+    let mime = request
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream");
+    if let Some(server) = lan.server().as_ref() {
+        server.set_map_image(bytes.clone(), mime.to_string());
+    }
+    Ok(())
+}
+
+// Watches the loaded map file; replaced on each load.
+#[derive(Default)]
+struct WatchState(Mutex<Option<MapWatcher>>);
+
+// Emits `vtt-map-changed` (with the path) to the DM window whenever `path`
+// changes on disk, until another map is watched or `unwatch_map` is called.
+#[tauri::command]
+fn watch_map(
+    app: tauri::AppHandle,
+    watch: tauri::State<'_, WatchState>,
+    path: String,
+) -> Result<(), String> {
+    let mut current = watch.0.lock().unwrap_or_else(|e| e.into_inner());
+    if current
+        .as_ref()
+        .is_some_and(|w| w.path() == std::path::Path::new(&path))
+    {
+        return Ok(());
+    }
+    // Drop the old watcher first so a failed watch doesn't leave it running.
+    current.take();
+    let watcher = MapWatcher::new(path.as_ref(), move |changed| {
+        let _ = app.emit_to("dm", watch::MAP_CHANGED_EVENT, changed.to_string_lossy());
+    })
+    .map_err(|e| format!("Could not watch {path}: {e}"))?;
+    *current = Some(watcher);
+    Ok(())
+}
+
+// Stops watching `path`. Another map's watcher is left alone, so an unwatch
+// arriving after the next map's `watch_map` can't cancel it.
+#[tauri::command]
+fn unwatch_map(watch: tauri::State<'_, WatchState>, path: String) {
+    let mut current = watch.0.lock().unwrap_or_else(|e| e.into_inner());
+    if current
+        .as_ref()
+        .is_some_and(|w| w.path() == std::path::Path::new(&path))
+    {
+        current.take();
+    }
+}
+
+// Fog shape operations. Each takes and returns the webview's unpacked fog
+// (see `fog.rs`). While dragging, the webview paints with its own copy of
+// the brush (`modifyFog`) rather than a round trip per pointer move.
+
+// `center` is `[row, col]`; `radius` is in cells.
+#[tauri::command]
+fn fog_brush(mut fog: Fog, center: (i64, i64), radius: i64, reveal: bool) -> FogState {
+    fog.brush(center, radius, reveal);
+    FogState::from(&fog)
+}
+
+// `from` / `to` are opposite corners as `[row, col]`.
+#[tauri::command]
+fn fog_rect(mut fog: Fog, from: (i64, i64), to: (i64, i64), reveal: bool) -> FogState {
+    fog.rect(from, to, reveal);
+    FogState::from(&fog)
+}
+
+// `points` are in map pixels; the grid size and offset place them on cells.
+#[tauri::command]
+fn fog_polygon(
+    mut fog: Fog,
+    points: Vec<DrawingPoint>,
+    grid_size: f64,
+    grid_offset_x: f64,
+    grid_offset_y: f64,
+    reveal: bool,
+) -> FogState {
+    if grid_size > 0.0 {
+        let cells: Vec<(f64, f64)> = points
+            .iter()
+            .map(|p| {
+                (
+                    (p.x - grid_offset_x) / grid_size,
+                    (p.y - grid_offset_y) / grid_size,
+                )
+            })
+            .collect();
+        fog.polygon(&cells, reveal);
+    }
+    FogState::from(&fog)
+}
+
+// Clears whatever the lights in `walls` can see; `width` / `height` are the
+// map image's size in pixels.
+#[tauri::command]
+fn reveal_line_of_sight(
+    mut fog: Fog,
+    walls: WallState,
+    grid_size: f64,
+    grid_offset_x: f64,
+    grid_offset_y: f64,
+    width: f64,
+    height: f64,
+) -> FogState {
+    let grid = GridGeometry {
+        size: grid_size,
+        offset_x: grid_offset_x,
+        offset_y: grid_offset_y,
+    };
+    visibility::reveal_line_of_sight(&mut fog, &walls, grid, width, height);
+    FogState::from(&fog)
+}
+
+// The DM's complete state: on first sync, and whenever the DM window can't
+// send a delta (it reloaded, or the hub has no state yet).
+#[tauri::command]
+fn sync_player_state(
+    app: tauri::AppHandle,
+    hub: tauri::State<'_, SharedHub>,
+    lan: tauri::State<'_, LanState>,
+    state: serde_json::Value,
+) -> Result<(), String> {
+    let full = hub.lock().unwrap_or_else(|e| e.into_inner()).replace(state);
+    if let Some(server) = lan.server().as_ref() {
+        server.publish_full(&full);
+    }
+    app.emit_to("player", sync::STATE_SYNC_EVENT, full)
+        .map_err(|e| e.to_string())
+}
+
+// Changes since the last sync. Subscribers get only the resulting change to
+// the player projection.
+#[tauri::command]
+fn sync_player_patches(
+    app: tauri::AppHandle,
+    hub: tauri::State<'_, SharedHub>,
+    lan: tauri::State<'_, LanState>,
+    patches: Vec<StatePatch>,
+) -> Result<(), String> {
+    let delta = hub
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .apply(patches)
+        .map_err(|e| e.to_string())?;
+    let Some(delta) = delta else { return Ok(()) };
+    if let Some(server) = lan.server().as_ref() {
+        server.publish_delta(&delta);
+    }
+    app.emit_to("player", sync::STATE_DELTA_EVENT, delta)
+        .map_err(|e| e.to_string())
+}
+
+// The current player projection, for the player window on startup and when
+// it detects a missed delta.
+#[tauri::command]
+fn player_state(hub: tauri::State<'_, SharedHub>) -> Option<FullState> {
+    hub.lock().unwrap_or_else(|e| e.into_inner()).full()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .invoke_handler(tauri::generate_handler![open_player_window])
+        .manage(SharedHub::default())
+        .manage(LanState::default())
+        .manage(WatchState::default())
+        .register_asynchronous_uri_scheme_protocol(tiles::SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            let path = request.uri().path().to_string();
+            tauri::async_runtime::spawn_blocking(move || {
+                responder.respond(tile_response(&app, &path))
+            });
+        })
+        .setup(|app| {
+            // Relay the player window's viewport to LAN clients. (Map state
+            // goes through the sync hub instead.)
+            let handle = app.handle().clone();
+            app.listen_any(lan::VIEWPORT_SYNC_EVENT, move |e| {
+                let lan = handle.state::<LanState>();
+                let server = lan.server();
+                let Some(server) = server.as_ref() else {
+                    return;
+                };
+                if let Ok(payload) = serde_json::from_str::<serde_json::Value>(e.payload()) {
+                    server.publish_viewport(&payload);
+                }
+            });
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            open_player_window,
+            save_map_state,
+            load_map_state,
+            map_identity,
+            save_rolls,
+            load_hexmap,
+            render_hexmap,
+            plan_hex_travel,
+            export_travel_log,
+            roll_encounter,
+            roll_dice,
+            check_dice,
+            measure_distance,
+            detect_grid,
+            load_map_tiles,
+            template_area,
+            watch_map,
+            unwatch_map,
+            load_uvtt,
+            list_snapshots,
+            create_snapshot,
+            rename_snapshot,
+            duplicate_snapshot,
+            delete_snapshot,
+            restore_snapshot,
+            start_lan_server,
+            stop_lan_server,
+            lan_server_info,
+            lan_set_map_image,
+            fog_brush,
+            fog_rect,
+            fog_polygon,
+            reveal_line_of_sight,
+            sync_player_state,
+            sync_player_patches,
+            player_state
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
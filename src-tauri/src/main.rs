@@ -1,6 +1,32 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-fn main() {
-    vtt_lib::run()
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    // `vtt render|convert|validate …` runs headless; no arguments opens the app.
+    #[cfg(all(windows, not(debug_assertions)))]
+    attach_parent_console();
+    if let Some(code) = vtt_lib::cli::main() {
+        return code;
+    }
+    vtt_lib::run();
+    ExitCode::SUCCESS
+}
+
+// A `windows_subsystem = "windows"` binary starts without a console, so the
+// CLI's output would go nowhere. Borrow the console of whatever started it
+// (a no-op when there is none, e.g. launched from Explorer). The shell doesn't
+// wait for GUI programs, so output can land after its next prompt.
+#[cfg(all(windows, not(debug_assertions)))]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // SAFETY: takes no pointers; failure (no parent console) is harmless.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
 }
@@ -0,0 +1,435 @@
+use serde::{Deserialize, Serialize};
+
+use crate::hexm::{self, HexOrientation};
+use crate::hexrender;
+use crate::types::DrawingPoint;
+
+// Distances and area-of-effect templates on the map grid.
+//
+// Square grids count distance in one of three ways: 5-10-5 (every second
+// diagonal costs double), Euclidean (straight-line) or Chebyshev (diagonals
+// cost the same as orthogonal steps). Hex maps always count hex steps.
+//
+// A template covers a cell when the cell's centre is inside it. On square
+// grids spheres, cones and cubes start on a grid intersection and lines at
+// the centre of a square (the caster's); on hex maps everything starts at a
+// hex centre. Sizes are in feet, turned into cells with the map's feet per
+// cell (a cartographer bundle's `grid.ft_per_cell`, 5 otherwise).
+
+const EPSILON: f64 = 1e-9;
+// A cone is as wide at its end as it is long (5e); hex cones span 60°.
+const SQUARE_CONE_SLOPE: f64 = 0.5;
+const HEX_CONE_SLOPE: f64 = 0.577_350_269_189_625_8; // tan 30°
+
+/// The grid distances are counted on, in map pixels.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Board {
+    Square(SquareGrid),
+    Hex(HexGrid),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SquareGrid {
+    pub size: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub cols: i64,
+    pub rows: i64,
+}
+
+/// A `.hexm` map's grid; hexes are 1-based (col, row) as in `hexm.rs`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HexGrid {
+    pub orientation: HexOrientation,
+    pub radius: f64,
+    pub cols: i64,
+    pub rows: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DistanceRule {
+    #[default]
+    Alternating,
+    Euclidean,
+    Chebyshev,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Shape {
+    Cone,
+    Sphere,
+    Line,
+    Cube,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub shape: Shape,
+    pub origin: DrawingPoint,
+    /// Where the template points (cones, lines and cubes).
+    pub toward: DrawingPoint,
+    /// Radius (sphere), length (cone, line) or side (cube).
+    pub size_ft: f64,
+    #[serde(default = "default_line_width")]
+    pub width_ft: f64, // lines only
+}
+
+fn default_line_width() -> f64 {
+    5.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Measurement {
+    pub cells: f64,
+    pub feet: f64,
+}
+
+/// A template placed on the grid: its snapped origin and every covered cell
+/// as `[col, row]` (0-based squares, 1-based hexes).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateArea {
+    pub origin: DrawingPoint,
+    pub cells: Vec<(i64, i64)>,
+}
+
+/// Distance between the cells (or hexes) under two points.
+pub fn measure(
+    board: &Board,
+    rule: DistanceRule,
+    ft_per_cell: f64,
+    from: DrawingPoint,
+    to: DrawingPoint,
+) -> Measurement {
+    let cells = match board {
+        Board::Square(grid) => {
+            let (a, b) = (grid.cell_at(from), grid.cell_at(to));
+            square_distance(rule, (b.0 - a.0) as f64, (b.1 - a.1) as f64)
+        }
+        Board::Hex(grid) => {
+            hexm::hex_distance(grid.orientation, grid.hex_at(from), grid.hex_at(to)) as f64
+        }
+    };
+    Measurement {
+        cells,
+        feet: (cells * ft_per_cell * 10.0).round() / 10.0,
+    }
+}
+
+/// The cells `template` covers.
+pub fn template_area(
+    board: &Board,
+    rule: DistanceRule,
+    ft_per_cell: f64,
+    template: &Template,
+) -> TemplateArea {
+    if !(ft_per_cell.is_finite() && ft_per_cell > 0.0) {
+        return TemplateArea {
+            origin: template.origin,
+            cells: Vec::new(),
+        };
+    }
+    let reach = template.size_ft / ft_per_cell;
+    let half_width = template.width_ft / ft_per_cell / 2.0;
+    match board {
+        Board::Square(grid) => grid.template_area(rule, template, reach, half_width),
+        Board::Hex(grid) => grid.template_area(template, reach, half_width),
+    }
+}
+
+impl SquareGrid {
+    fn cell_at(&self, p: DrawingPoint) -> (i64, i64) {
+        let size = self.size.max(EPSILON);
+        (
+            ((p.x - self.offset_x) / size).floor() as i64,
+            ((p.y - self.offset_y) / size).floor() as i64,
+        )
+    }
+
+    // `p` in cell units from the grid origin.
+    fn in_cells(&self, p: DrawingPoint) -> (f64, f64) {
+        let size = self.size.max(EPSILON);
+        ((p.x - self.offset_x) / size, (p.y - self.offset_y) / size)
+    }
+
+    fn template_area(
+        &self,
+        rule: DistanceRule,
+        template: &Template,
+        reach: f64,
+        half_width: f64,
+    ) -> TemplateArea {
+        let (x, y) = self.in_cells(template.origin);
+        let origin = match template.shape {
+            Shape::Line => (x.floor() + 0.5, y.floor() + 0.5),
+            _ => (x.round(), y.round()),
+        };
+        let (tx, ty) = self.in_cells(template.toward);
+        let direction = unit(tx - origin.0, ty - origin.1);
+
+        let mut cells = Vec::new();
+        let span = reach.ceil() as i64 + 1;
+        let (ox, oy) = (origin.0.floor() as i64, origin.1.floor() as i64);
+        for row in (oy - span).max(0)..=(oy + span).min(self.rows - 1) {
+            for col in (ox - span).max(0)..=(ox + span).min(self.cols - 1) {
+                let d = (col as f64 + 0.5 - origin.0, row as f64 + 0.5 - origin.1);
+                let distance = || square_distance(rule, d.0, d.1);
+                let covered = match template.shape {
+                    Shape::Sphere => distance() <= reach + EPSILON,
+                    Shape::Cone => {
+                        distance() <= reach + EPSILON && in_cone(d, direction, SQUARE_CONE_SLOPE)
+                    }
+                    Shape::Line => on_line(d, direction, reach, half_width),
+                    Shape::Cube => {
+                        // A corner on the origin, opening toward the target.
+                        let inside =
+                            |d: f64, dir: f64| (0.0..reach + EPSILON).contains(&(d * dir.signum()));
+                        inside(d.0, direction.0) && inside(d.1, direction.1)
+                    }
+                };
+                if covered {
+                    cells.push((col, row));
+                }
+            }
+        }
+        let origin = DrawingPoint {
+            x: self.offset_x + origin.0 * self.size,
+            y: self.offset_y + origin.1 * self.size,
+        };
+        TemplateArea { origin, cells }
+    }
+}
+
+impl HexGrid {
+    fn center(&self, (col, row): (i64, i64)) -> (f64, f64) {
+        hexrender::hex_center(self.orientation, self.radius, col, row)
+    }
+
+    // Hexes tile the plane, so the nearest centre is the hex a point is in.
+    fn hex_at(&self, p: DrawingPoint) -> (i64, i64) {
+        let mut best = ((1, 1), f64::INFINITY);
+        for col in 1..=self.cols.max(1) {
+            for row in 1..=self.rows.max(1) {
+                let (cx, cy) = self.center((col, row));
+                let d = (cx - p.x).powi(2) + (cy - p.y).powi(2);
+                if d < best.1 {
+                    best = ((col, row), d);
+                }
+            }
+        }
+        best.0
+    }
+
+    fn template_area(&self, template: &Template, reach: f64, half_width: f64) -> TemplateArea {
+        let start = self.hex_at(template.origin);
+        let (ox, oy) = self.center(start);
+        let spacing = 3f64.sqrt() * self.radius.max(EPSILON);
+        let direction = unit(template.toward.x - ox, template.toward.y - oy);
+
+        let mut cells = Vec::new();
+        for col in 1..=self.cols {
+            for row in 1..=self.rows {
+                let steps = hexm::hex_distance(self.orientation, start, (col, row)) as f64;
+                if steps > reach.ceil() + 1.0 {
+                    continue;
+                }
+                let (cx, cy) = self.center((col, row));
+                let d = ((cx - ox) / spacing, (cy - oy) / spacing);
+                let covered = match template.shape {
+                    Shape::Sphere => steps <= reach + EPSILON,
+                    Shape::Cone => {
+                        steps >= 1.0
+                            && steps <= reach + EPSILON
+                            && in_cone(d, direction, HEX_CONE_SLOPE)
+                    }
+                    Shape::Line => on_line(d, direction, reach, half_width),
+                    Shape::Cube => {
+                        d.0.abs() <= reach / 2.0 + EPSILON && d.1.abs() <= reach / 2.0 + EPSILON
+                    }
+                };
+                if covered {
+                    cells.push((col, row));
+                }
+            }
+        }
+        TemplateArea {
+            origin: DrawingPoint { x: ox, y: oy },
+            cells,
+        }
+    }
+}
+
+// Distance in cells to a square whose centre is `dx`, `dy` cells away.
+// Grid rules count the squares stepped through; from a grid intersection a
+// neighbouring centre is half a cell away but one step.
+fn square_distance(rule: DistanceRule, dx: f64, dy: f64) -> f64 {
+    let steps = |d: f64| (d.abs() + 0.5).floor();
+    let (sx, sy) = (steps(dx), steps(dy));
+    match rule {
+        DistanceRule::Euclidean => dx.hypot(dy),
+        DistanceRule::Chebyshev => sx.max(sy),
+        DistanceRule::Alternating => sx.max(sy) + (sx.min(sy) / 2.0).floor(),
+    }
+}
+
+// Unit vector, pointing right when there's no direction to speak of.
+fn unit(x: f64, y: f64) -> (f64, f64) {
+    let length = x.hypot(y);
+    if length < EPSILON {
+        (1.0, 0.0)
+    } else {
+        (x / length, y / length)
+    }
+}
+
+fn in_cone(d: (f64, f64), direction: (f64, f64), slope: f64) -> bool {
+    let along = d.0 * direction.0 + d.1 * direction.1;
+    let across = (d.0 * direction.1 - d.1 * direction.0).abs();
+    along > EPSILON && across <= along * slope + EPSILON
+}
+
+fn on_line(d: (f64, f64), direction: (f64, f64), length: f64, half_width: f64) -> bool {
+    let along = d.0 * direction.0 + d.1 * direction.1;
+    let across = (d.0 * direction.1 - d.1 * direction.0).abs();
+    along > EPSILON && along <= length + EPSILON && across <= half_width + EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRID: Board = Board::Square(SquareGrid {
+        size: 50.0,
+        offset_x: 0.0,
+        offset_y: 0.0,
+        cols: 40,
+        rows: 40,
+    });
+
+    fn point(x: f64, y: f64) -> DrawingPoint {
+        DrawingPoint { x, y }
+    }
+
+    fn template(shape: Shape, size_ft: f64, toward: DrawingPoint) -> Template {
+        Template {
+            shape,
+            origin: point(500.0, 500.0),
+            toward,
+            size_ft,
+            width_ft: 5.0,
+        }
+    }
+
+    #[test]
+    fn measures_diagonals_by_rule() {
+        // 4 squares across and 3 down.
+        let (from, to) = (point(25.0, 25.0), point(225.0, 175.0));
+        let feet = |rule| measure(&GRID, rule, 5.0, from, to).feet;
+        assert_eq!(feet(DistanceRule::Chebyshev), 20.0);
+        assert_eq!(feet(DistanceRule::Alternating), 25.0);
+        assert_eq!(feet(DistanceRule::Euclidean), 25.0);
+        assert_eq!(
+            measure(
+                &GRID,
+                DistanceRule::Alternating,
+                10.0,
+                from,
+                point(175.0, 175.0)
+            )
+            .feet,
+            40.0
+        );
+    }
+
+    #[test]
+    fn sphere_shape_follows_the_rule() {
+        let sphere = template(Shape::Sphere, 10.0, point(500.0, 500.0));
+        let count = |rule| template_area(&GRID, rule, 5.0, &sphere).cells.len();
+        assert_eq!(count(DistanceRule::Chebyshev), 16);
+        assert_eq!(count(DistanceRule::Alternating), 12);
+        assert_eq!(count(DistanceRule::Euclidean), 12);
+        let small = template(Shape::Sphere, 5.0, point(500.0, 500.0));
+        let area = template_area(&GRID, DistanceRule::Alternating, 5.0, &small);
+        assert_eq!(area.cells, vec![(9, 9), (10, 9), (9, 10), (10, 10)]);
+    }
+
+    #[test]
+    fn snaps_origins_to_the_grid() {
+        let mut sphere = template(Shape::Sphere, 5.0, point(0.0, 0.0));
+        sphere.origin = point(520.0, 480.0);
+        assert_eq!(
+            template_area(&GRID, DistanceRule::Alternating, 5.0, &sphere).origin,
+            point(500.0, 500.0)
+        );
+        let mut line = template(Shape::Line, 30.0, point(900.0, 520.0));
+        line.origin = point(510.0, 510.0);
+        let area = template_area(&GRID, DistanceRule::Alternating, 5.0, &line);
+        assert_eq!(area.origin, point(525.0, 525.0));
+        assert_eq!(
+            area.cells,
+            (11..=16).map(|col| (col, 10)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn cones_and_cubes_point_at_the_target() {
+        let cone = template(Shape::Cone, 15.0, point(900.0, 500.0));
+        let cells = template_area(&GRID, DistanceRule::Alternating, 5.0, &cone).cells;
+        assert!(!cells.is_empty());
+        assert!(cells.iter().all(|&(col, _)| (10..13).contains(&col)));
+
+        let cube = template(Shape::Cube, 10.0, point(400.0, 600.0));
+        let cells = template_area(&GRID, DistanceRule::Alternating, 5.0, &cube).cells;
+        assert_eq!(cells, vec![(8, 10), (9, 10), (8, 11), (9, 11)]);
+    }
+
+    #[test]
+    fn counts_hex_steps_on_hex_maps() {
+        let board = Board::Hex(HexGrid {
+            orientation: HexOrientation::Flat,
+            radius: 40.0,
+            cols: 10,
+            rows: 10,
+        });
+        let Board::Hex(grid) = board else {
+            unreachable!()
+        };
+        let at = |hex| {
+            let (x, y) = grid.center(hex);
+            point(x, y)
+        };
+        assert_eq!(
+            measure(&board, DistanceRule::Euclidean, 5.0, at((2, 2)), at((5, 3))).feet,
+            15.0
+        );
+
+        let sphere = Template {
+            shape: Shape::Sphere,
+            origin: at((5, 5)),
+            toward: at((5, 5)),
+            size_ft: 5.0,
+            width_ft: 5.0,
+        };
+        assert_eq!(
+            template_area(&board, DistanceRule::Alternating, 5.0, &sphere)
+                .cells
+                .len(),
+            7
+        );
+        let cone = Template {
+            shape: Shape::Cone,
+            toward: at((8, 5)),
+            ..sphere
+        };
+        let cells = template_area(&board, DistanceRule::Alternating, 5.0, &cone).cells;
+        assert!(!cells.contains(&(5, 5)));
+        assert!(cells.iter().all(|&(col, _)| col > 5));
+    }
+}
@@ -0,0 +1,214 @@
+use serde_json::{json, Map, Value};
+
+use crate::fog::Fog;
+
+/// Current `schemaVersion` of saved map files.
+pub const SCHEMA_VERSION: u32 = 2;
+
+// Block color used for legacy block entries that recorded only a cell.
+const LEGACY_BLOCK_COLOR: &str = "#ffffff";
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("save file is not a JSON object")]
+    NotAnObject,
+    #[error("save file has schema version {0}, newer than this app supports ({SCHEMA_VERSION})")]
+    TooNew(u64),
+}
+
+type Migration = fn(&mut Map<String, Value>);
+
+// `MIGRATIONS[n]` upgrades a version-`n` file to version `n + 1`. Files
+// written before versioning existed have no `schemaVersion` and count as 0.
+const MIGRATIONS: &[Migration] = &[v0_to_v1, v1_to_v2];
+
+/// Upgrade a saved map file of any known version to the current shape.
+pub fn migrate(mut value: Value) -> Result<Value, MigrationError> {
+    let obj = value.as_object_mut().ok_or(MigrationError::NotAnObject)?;
+    let mut version = obj
+        .get("schemaVersion")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    if version > SCHEMA_VERSION as u64 {
+        return Err(MigrationError::TooNew(version));
+    }
+    while version < SCHEMA_VERSION as u64 {
+        MIGRATIONS[version as usize](obj);
+        version += 1;
+        obj.insert("schemaVersion".into(), json!(version));
+    }
+    Ok(value)
+}
+
+// Unversioned saves accumulated several shapes as fields were added:
+// - no `gridOffsetX`/`gridOffsetY` (grid alignment came later) -> 0
+// - `blocks` missing, or the legacy array of painted cells -> `{ cells }`
+// - `calibration.savedPixelsPerInch` missing (predates Shift+R reset)
+fn v0_to_v1(obj: &mut Map<String, Value>) {
+    for key in ["gridOffsetX", "gridOffsetY"] {
+        if !obj.get(key).is_some_and(Value::is_number) {
+            obj.insert(key.into(), json!(0));
+        }
+    }
+
+    if !obj.get("drawings").is_some_and(Value::is_array) {
+        obj.insert("drawings".into(), json!([]));
+    }
+
+    let blocks = match obj.remove("blocks") {
+        Some(Value::Array(entries)) => json!({ "cells": legacy_block_cells(&entries) }),
+        Some(blocks @ Value::Object(_)) if blocks.get("cells").is_some_and(Value::is_object) => {
+            blocks
+        }
+        _ => json!({ "cells": {} }),
+    };
+    obj.insert("blocks".into(), blocks);
+
+    let calibration = obj
+        .entry("calibration")
+        .or_insert_with(|| json!({ "pixelsPerInch": 96 }));
+    if let Some(calibration) = calibration.as_object_mut() {
+        let ppi = calibration
+            .get("pixelsPerInch")
+            .cloned()
+            .unwrap_or(json!(96));
+        calibration.insert("pixelsPerInch".into(), ppi.clone());
+        if !calibration
+            .get("savedPixelsPerInch")
+            .is_some_and(Value::is_number)
+        {
+            calibration.insert("savedPixelsPerInch".into(), ppi);
+        }
+    }
+}
+
+// v2 stores fog packed (`{ cols, rows, rle }`, see `fog.rs`) instead of a
+// `boolean[][]`. Fog that doesn't parse is left alone so the load reports
+// it as corrupt rather than silently resetting it.
+fn v1_to_v2(obj: &mut Map<String, Value>) {
+    let Some(fog) = obj.get("fog") else { return };
+    if let Ok(packed) = serde_json::from_value::<Fog>(fog.clone()) {
+        obj.insert(
+            "fog".into(),
+            serde_json::to_value(packed).expect("Fog serializes"),
+        );
+    }
+}
+
+// The legacy block layer was a flat array, either of `{ row, col, color }`
+// objects or of bare `"row,col"` keys painted in the default block color.
+fn legacy_block_cells(entries: &[Value]) -> Map<String, Value> {
+    let mut cells = Map::new();
+    for entry in entries {
+        match entry {
+            Value::String(key) => {
+                cells.insert(key.clone(), json!(LEGACY_BLOCK_COLOR));
+            }
+            Value::Object(cell) => {
+                let (Some(row), Some(col)) = (
+                    cell.get("row").and_then(Value::as_i64),
+                    cell.get("col").and_then(Value::as_i64),
+                ) else {
+                    continue;
+                };
+                let color = cell
+                    .get("color")
+                    .and_then(Value::as_str)
+                    .unwrap_or(LEGACY_BLOCK_COLOR);
+                cells.insert(format!("{},{}", row, col), json!(color));
+            }
+            _ => {}
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SavedMapState;
+
+    fn load_fixture(json: &str) -> SavedMapState {
+        let value: Value = serde_json::from_str(json).expect("fixture is valid JSON");
+        serde_json::from_value(migrate(value).expect("fixture migrates")).expect("migrated shape")
+    }
+
+    #[test]
+    fn legacy_block_objects_keep_their_colors() {
+        let state = load_fixture(include_str!(
+            "../tests/fixtures/saves/v0-blocks-array-objects.json"
+        ));
+        assert_eq!(state.schema_version, SCHEMA_VERSION);
+        assert_eq!(state.blocks.cells.len(), 2);
+        assert_eq!(state.blocks.cells["3,4"], "#ff0000");
+        assert_eq!(state.blocks.cells["5,6"], "#00ff00");
+        assert_eq!((state.grid_offset_x, state.grid_offset_y), (0.0, 0.0));
+        assert_eq!(state.calibration.saved_pixels_per_inch, 110.0);
+    }
+
+    #[test]
+    fn legacy_block_keys_use_default_color() {
+        let state = load_fixture(include_str!(
+            "../tests/fixtures/saves/v0-blocks-array-keys.json"
+        ));
+        assert_eq!(state.blocks.cells.len(), 2);
+        assert_eq!(state.blocks.cells["0,0"], LEGACY_BLOCK_COLOR);
+        assert_eq!(state.blocks.cells["1,2"], LEGACY_BLOCK_COLOR);
+    }
+
+    #[test]
+    fn missing_blocks_and_grid_offset_default() {
+        let state = load_fixture(include_str!(
+            "../tests/fixtures/saves/v0-no-grid-offset.json"
+        ));
+        assert!(state.blocks.cells.is_empty());
+        assert_eq!((state.grid_offset_x, state.grid_offset_y), (0.0, 0.0));
+        assert_eq!(state.fog.to_cells()[0], vec![true, false, true]);
+    }
+
+    #[test]
+    fn missing_saved_calibration_falls_back_to_current() {
+        let state = load_fixture(include_str!(
+            "../tests/fixtures/saves/v0-no-saved-calibration.json"
+        ));
+        assert_eq!(state.calibration.pixels_per_inch, 72.0);
+        assert_eq!(state.calibration.saved_pixels_per_inch, 72.0);
+        assert_eq!((state.grid_offset_x, state.grid_offset_y), (12.0, -7.0));
+        assert_eq!(state.blocks.cells["2,2"], "#222222");
+    }
+
+    #[test]
+    fn unversioned_current_shape_is_unchanged() {
+        let state = load_fixture(include_str!("../tests/fixtures/saves/v0-unversioned.json"));
+        assert_eq!(state.schema_version, SCHEMA_VERSION);
+        assert_eq!(state.calibration.pixels_per_inch, 100.0);
+        assert_eq!(state.calibration.saved_pixels_per_inch, 90.0);
+        assert_eq!(state.drawings.len(), 1);
+        assert_eq!(state.blocks.cells["0,1"], "#123456");
+    }
+
+    #[test]
+    fn boolean_fog_is_packed() {
+        let json = include_str!("../tests/fixtures/saves/v1.json");
+        let migrated = migrate(serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            migrated["fog"],
+            json!({ "cols": 2, "rows": 1, "rle": "AQE=" })
+        );
+        assert_eq!(load_fixture(json).fog.to_cells(), vec![vec![true, false]]);
+    }
+
+    #[test]
+    fn current_version_is_untouched() {
+        let json = include_str!("../tests/fixtures/saves/v2.json");
+        let original: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(migrate(original.clone()).unwrap(), original);
+        assert_eq!(load_fixture(json).grid_size, 70.0);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let value = json!({ "schemaVersion": SCHEMA_VERSION + 1 });
+        assert!(matches!(migrate(value), Err(MigrationError::TooNew(_))));
+    }
+}
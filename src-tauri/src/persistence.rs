@@ -0,0 +1,426 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Serialize, Serializer};
+
+use crate::fog::FogState;
+use crate::identity;
+use crate::migrate::{self, MigrationError};
+use crate::types::{RollRecord, SavedMapState, ROLL_HISTORY_LIMIT};
+
+// How many previous versions of each save file to keep next to it
+// (`<name>.json.1` is the newest, `<name>.json.5` the oldest).
+pub const BACKUP_COUNT: usize = 5;
+// Autosave runs a second after every change, so a backup is only taken when
+// the newest one is at least this old; otherwise the five "previous versions"
+// would all be from the last few seconds.
+pub const BACKUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, thiserror::Error)]
+pub enum PersistError {
+    #[error("could not resolve the app data directory: {0}")]
+    AppDataDir(String),
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{} is not a valid save file: {source}", path.display())]
+    Corrupt {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("{}: {source}", path.display())]
+    Migration {
+        path: PathBuf,
+        #[source]
+        source: MigrationError,
+    },
+    #[error("{0} not found")]
+    NotFound(String),
+}
+
+impl PersistError {
+    fn io(path: &Path, source: io::Error) -> Self {
+        PersistError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            PersistError::AppDataDir(_) => "appDataDir",
+            PersistError::Io { .. } => "io",
+            PersistError::Corrupt { .. } => "corrupt",
+            PersistError::Migration { .. } => "migration",
+            PersistError::NotFound(_) => "notFound",
+        }
+    }
+}
+
+// Errors cross the command boundary as `{ kind, message }` so the webview can
+// tell a corrupt save apart from a disk problem.
+impl Serialize for PersistError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("PersistError", 2)?;
+        s.serialize_field("kind", self.kind())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+// Legacy save file name for a map: the readable file stem plus a 32-bit hash
+// of the full path. Bit-for-bit the same as the old webview `hashPath`; only
+// used to find saves made before the map index (see `identity.rs`) existed.
+pub fn save_file_name(map_file_path: &str) -> String {
+    let mut hash: i32 = 0;
+    for unit in map_file_path.encode_utf16() {
+        hash = hash
+            .wrapping_shl(5)
+            .wrapping_sub(hash)
+            .wrapping_add(unit as i32);
+    }
+
+    let file_name = map_file_path.rsplit('/').next().unwrap_or("");
+    let stem = match file_name.rfind('.') {
+        Some(dot) if dot + 1 < file_name.len() => &file_name[..dot],
+        _ => file_name,
+    };
+    let stem = if stem.is_empty() { "map" } else { stem };
+    let safe: String = stem
+        .encode_utf16()
+        .map(|unit| match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .take(50)
+        .collect();
+
+    format!("{}_{:x}", safe, (hash as i64).abs())
+}
+
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+// Write `contents` to `path` atomically: the bytes go to a sibling temp file
+// that is flushed to disk and then renamed over the target, so a crash leaves
+// either the old file or the new one, never a truncated mix. The previous
+// version is rotated into the numbered backups first.
+pub fn write_atomic(path: &Path, contents: &[u8], backups: usize) -> Result<(), PersistError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| PersistError::io(dir, e))?;
+    }
+
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = PathBuf::from(tmp_name);
+    {
+        let mut file = File::create(&tmp).map_err(|e| PersistError::io(&tmp, e))?;
+        file.write_all(contents)
+            .map_err(|e| PersistError::io(&tmp, e))?;
+        file.sync_all().map_err(|e| PersistError::io(&tmp, e))?;
+    }
+
+    if backups > 0 && path.exists() {
+        rotate_backups(path, backups)?;
+    }
+
+    fs::rename(&tmp, path).map_err(|e| PersistError::io(path, e))
+}
+
+// Shift `<file>.1..N-1` up by one (dropping the oldest) and copy the current
+// file into `<file>.1`. Copying rather than renaming keeps the live file in
+// place until the new version replaces it.
+fn rotate_backups(path: &Path, backups: usize) -> Result<(), PersistError> {
+    for n in (1..backups).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            let to = backup_path(path, n + 1);
+            fs::rename(&from, &to).map_err(|e| PersistError::io(&to, e))?;
+        }
+    }
+    let first = backup_path(path, 1);
+    fs::copy(path, &first).map_err(|e| PersistError::io(&first, e))?;
+    // Some platforms copy the timestamp along; `backup_due` wants the time
+    // the backup was taken.
+    File::options()
+        .write(true)
+        .open(&first)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+        .map_err(|e| PersistError::io(&first, e))
+}
+
+// Whether the next save of `path` should rotate the backups: there are none
+// yet, or the newest is older than `BACKUP_INTERVAL`.
+fn backup_due(path: &Path) -> bool {
+    fs::metadata(backup_path(path, 1))
+        .and_then(|meta| meta.modified())
+        .map_or(true, |taken| {
+            taken.elapsed().map_or(true, |age| age >= BACKUP_INTERVAL)
+        })
+}
+
+pub fn save_map_state(save_dir: &Path, state: &SavedMapState) -> Result<PathBuf, PersistError> {
+    let path = identity::resolve_save_file(save_dir, &state.file_path, true)?
+        .expect("resolve_save_file always yields a path when creating");
+    let state = SavedMapState {
+        schema_version: migrate::SCHEMA_VERSION,
+        ..state.clone()
+    };
+    let json = serde_json::to_vec_pretty(&state).map_err(|e| PersistError::Corrupt {
+        path: path.clone(),
+        source: e,
+    })?;
+    let backups = if backup_due(&path) { BACKUP_COUNT } else { 0 };
+    write_atomic(&path, &json, backups)?;
+    Ok(path)
+}
+
+/// Save the dice tray's history to `path` as `{ "rolls": [...] }`, keeping
+/// only the newest `ROLL_HISTORY_LIMIT`. Backed up like a map save.
+pub fn save_rolls(path: &Path, rolls: &[RollRecord]) -> Result<(), PersistError> {
+    let rolls = &rolls[rolls.len().saturating_sub(ROLL_HISTORY_LIMIT)..];
+    let json = serde_json::to_vec_pretty(&serde_json::json!({ "rolls": rolls })).map_err(|e| {
+        PersistError::Corrupt {
+            path: path.to_path_buf(),
+            source: e,
+        }
+    })?;
+    let backups = if backup_due(path) { BACKUP_COUNT } else { 0 };
+    write_atomic(path, &json, backups)
+}
+
+fn read_save_file(path: &Path) -> Result<Option<SavedMapState>, PersistError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(PersistError::io(path, e)),
+    };
+    let value = serde_json::from_slice(&bytes).map_err(|e| PersistError::Corrupt {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    parse_saved_state(value, path).map(Some)
+}
+
+// Upgrade raw saved-state JSON read from `path` to the current schema and
+// deserialize it.
+pub fn parse_saved_state(
+    value: serde_json::Value,
+    path: &Path,
+) -> Result<SavedMapState, PersistError> {
+    let value = migrate::migrate(value).map_err(|e| PersistError::Migration {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    serde_json::from_value(value).map_err(|e| PersistError::Corrupt {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// `state` as the webview takes it: like the file, but with the fog
+/// unpacked (see `fog.rs`).
+pub fn to_webview(state: &SavedMapState) -> serde_json::Value {
+    let mut value = serde_json::to_value(state).expect("SavedMapState serializes");
+    value["fog"] = serde_json::to_value(FogState::from(&state.fog)).expect("FogState serializes");
+    value
+}
+
+fn serialize_for_webview<S: Serializer>(
+    state: &SavedMapState,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    to_webview(state).serialize(serializer)
+}
+
+/// A save as loaded for the webview.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedSave {
+    #[serde(serialize_with = "serialize_for_webview")]
+    pub state: SavedMapState,
+    /// Set when the save file itself couldn't be read and `state` came from a
+    /// backup: what was wrong and which backup was used.
+    pub recovered: Option<String>,
+}
+
+// Load the saved state for a map. `Ok(None)` means the map has never been
+// saved. If the main file is unreadable, the newest backup that parses is
+// used instead (and said so in `recovered`); the original error is returned
+// only when every copy is bad. The save may have been found by content, so
+// `file_path` is updated to where the map lives now.
+pub fn load_map_state(
+    save_dir: &Path,
+    map_file_path: &str,
+) -> Result<Option<LoadedSave>, PersistError> {
+    let Some(path) = identity::resolve_save_file(save_dir, map_file_path, false)? else {
+        return Ok(None);
+    };
+    let (state, recovered) = match read_save_file(&path) {
+        Ok(None) => return Ok(None),
+        Ok(Some(state)) => (state, None),
+        Err(err) => (1..=BACKUP_COUNT)
+            .find_map(|n| match read_save_file(&backup_path(&path, n)) {
+                Ok(Some(state)) => Some((state, Some(format!("{err}; restored backup {n}")))),
+                _ => None,
+            })
+            .ok_or(err)?,
+    };
+    let state = SavedMapState {
+        file_path: map_file_path.to_string(),
+        ..state
+    };
+    Ok(Some(LoadedSave { state, recovered }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    // Saves are found by the map's content, so the map has to exist.
+    fn map_file(dir: &Path, name: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, name).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    // A save told apart from the others by its grid size.
+    fn saved_state(map_file_path: &str, grid_size: f64) -> SavedMapState {
+        let value = serde_json::from_str(include_str!("../tests/fixtures/saves/v2.json")).unwrap();
+        let state = parse_saved_state(value, Path::new("v2.json")).unwrap();
+        SavedMapState {
+            file_path: map_file_path.to_string(),
+            grid_size,
+            ..state
+        }
+    }
+
+    // Ages the newest backup past `BACKUP_INTERVAL`, as if the next save
+    // came much later.
+    fn age_backup(path: &Path) {
+        let old = SystemTime::now() - BACKUP_INTERVAL - Duration::from_secs(1);
+        File::options()
+            .write(true)
+            .open(backup_path(path, 1))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    }
+
+    #[test]
+    fn rotates_backups_newest_first_and_drops_the_oldest() {
+        let dir = scratch_dir("rotate");
+        let path = dir.join("save.json");
+        for version in 0..=BACKUP_COUNT + 1 {
+            write_atomic(&path, version.to_string().as_bytes(), BACKUP_COUNT).unwrap();
+        }
+        let read = |p: PathBuf| fs::read_to_string(p).unwrap();
+        assert_eq!(read(path.clone()), "6");
+        for n in 1..=BACKUP_COUNT {
+            assert_eq!(read(backup_path(&path, n)), (6 - n).to_string());
+        }
+        // Version 0 has gone.
+        assert!(!backup_path(&path, BACKUP_COUNT + 1).exists());
+        assert!(!dir.join("save.json.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn autosaves_only_back_up_once_per_interval() {
+        let dir = scratch_dir("interval");
+        let map = &map_file(&dir, "keep.png");
+        let path = save_map_state(&dir, &saved_state(map, 1.0)).unwrap();
+        save_map_state(&dir, &saved_state(map, 2.0)).unwrap();
+        save_map_state(&dir, &saved_state(map, 3.0)).unwrap();
+        // The first save had nothing to back up; the second took the first
+        // backup, the third was too soon after it.
+        let backup = |n| {
+            read_save_file(&backup_path(&path, n))
+                .unwrap()
+                .map(|s| s.grid_size)
+        };
+        assert_eq!((backup(1), backup(2)), (Some(1.0), None));
+
+        age_backup(&path);
+        save_map_state(&dir, &saved_state(map, 4.0)).unwrap();
+        assert_eq!((backup(1), backup(2)), (Some(3.0), Some(1.0)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn loads_the_newest_good_backup_when_the_save_is_corrupt() {
+        let dir = scratch_dir("recover");
+        let map = &map_file(&dir, "crypt.png");
+        assert!(load_map_state(&dir, map).unwrap().is_none());
+
+        let path = save_map_state(&dir, &saved_state(map, 1.0)).unwrap();
+        save_map_state(&dir, &saved_state(map, 2.0)).unwrap();
+        let loaded = load_map_state(&dir, map).unwrap().unwrap();
+        assert_eq!((loaded.state.grid_size, loaded.recovered), (2.0, None));
+
+        fs::write(&path, b"{ not json").unwrap();
+        let loaded = load_map_state(&dir, map).unwrap().unwrap();
+        assert_eq!(loaded.state.grid_size, 1.0);
+        assert_eq!(&loaded.state.file_path, map);
+        assert!(loaded.recovered.unwrap().ends_with("restored backup 1"));
+
+        // With every copy bad, the save file's own error comes back.
+        fs::write(backup_path(&path, 1), b"").unwrap();
+        assert!(
+            matches!(load_map_state(&dir, map), Err(PersistError::Corrupt { path: p, .. }) if p == path)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saves_only_the_newest_rolls() {
+        let dir = scratch_dir("rolls");
+        let path = dir.join("dice-rolls.json");
+        let rolls: Vec<RollRecord> = (0..ROLL_HISTORY_LIMIT + 5)
+            .map(|i| {
+                serde_json::from_value(serde_json::json!({
+                    "id": format!("roll-{i}"), "by": "DM", "at": i,
+                    "expression": "1d20", "total": 12, "terms": [], "breakdown": "1d20 [12] = 12",
+                }))
+                .unwrap()
+            })
+            .collect();
+        save_rolls(&path, &rolls[..1]).unwrap();
+        save_rolls(&path, &rolls).unwrap();
+
+        let saved: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let saved = saved["rolls"].as_array().unwrap();
+        assert_eq!(saved.len(), ROLL_HISTORY_LIMIT);
+        assert_eq!(saved[0]["id"], "roll-5");
+        assert!(backup_path(&path, 1).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sends_the_webview_unpacked_fog() {
+        let state = saved_state("keep.png", 1.0);
+        let loaded = serde_json::to_value(LoadedSave {
+            state: state.clone(),
+            recovered: None,
+        })
+        .unwrap();
+        let fog = &loaded["state"]["fog"];
+        assert!(fog.get("rle").is_none());
+        assert_eq!(
+            fog["cells"],
+            serde_json::to_value(state.fog.to_cells()).unwrap()
+        );
+        assert_eq!(loaded["state"]["gridSize"], 1.0);
+    }
+}
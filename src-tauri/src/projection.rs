@@ -0,0 +1,393 @@
+use std::cell::OnceCell;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::fog::Fog;
+
+// The "player projection" of the DM's live `AppState`: what the player window
+// and LAN clients are sent instead of the full state. Anything players
+// shouldn't see is removed here, before it leaves the DM process, rather
+// than hidden by the player view's rendering — devtools would show it.
+//
+// Works on the raw JSON so fields this module doesn't know about pass
+// through untouched.
+
+/// Redact `state` (an `AppState` as sent by the DM window) for players:
+/// - block cells under fog are dropped;
+/// - hex links, names and labels are dropped for fogged hexes, along with
+///   the map-wide default link (the DM's hex key);
+/// - the DM image (and its tiles) is replaced by the player image when there
+///   is one;
+/// - initiative entries are dropped while the tracker is hidden, and NPC hit
+///   points always;
+/// - walls, doors and light sources are dropped entirely;
+/// - the party's travel log and encounters are dropped; the marker stays;
+/// - secret dice rolls are dropped;
+/// - hidden tokens, and tokens whose centre is under fog, are dropped, as
+///   are initiative links to them.
+pub fn player_projection(state: &Value) -> Value {
+    let Some(root) = state.as_object() else {
+        return state.clone();
+    };
+    let projector = Projector::new(root);
+    Value::Object(
+        root.keys()
+            .filter_map(|field| Some((field.clone(), projector.field(field)?)))
+            .collect(),
+    )
+}
+
+/// The other top-level fields whose projection reads `field`, and so
+/// change when it does.
+pub fn dependents(field: &str) -> &'static [&'static str] {
+    match field {
+        "fog" => &["blocks", "map", "tokens", "initiative"],
+        "map" => &["tokens", "initiative"],
+        "tokens" => &["initiative"],
+        _ => &[],
+    }
+}
+
+/// Projects one top-level field of a DM state at a time, so a patch to a
+/// few fields only re-projects those (and their `dependents`). Only what
+/// players get is copied out of the DM state.
+pub struct Projector<'a> {
+    root: &'a Map<String, Value>,
+    grid: Option<Grid>,
+    fog: OnceCell<Option<Fog>>, // parsed on first use: most fields don't need it
+}
+
+impl<'a> Projector<'a> {
+    pub fn new(root: &'a Map<String, Value>) -> Self {
+        Projector {
+            root,
+            grid: root
+                .get("map")
+                .and_then(Value::as_object)
+                .map(Grid::from_map),
+            fog: OnceCell::new(),
+        }
+    }
+
+    /// The projection of `field`, or `None` when players don't get it.
+    pub fn field(&self, field: &str) -> Option<Value> {
+        let value = self.root.get(field)?;
+        Some(match field {
+            // Wall layout gives away secret doors and rooms still under fog.
+            "walls" => return None,
+            "blocks" => self.blocks(value),
+            "map" => self.map(value),
+            "initiative" => self.initiative(value),
+            "party" => without(value, &["log", "encounters"]),
+            "tokens" => match value.as_array() {
+                Some(tokens) => tokens
+                    .iter()
+                    .filter(|token| self.token_visible(token))
+                    .cloned()
+                    .collect(),
+                None => value.clone(),
+            },
+            "rolls" => match value.as_array() {
+                Some(rolls) => rolls
+                    .iter()
+                    .filter(|roll| roll.get("secret") != Some(&Value::Bool(true)))
+                    .cloned()
+                    .collect(),
+                None => value.clone(),
+            },
+            _ => value.clone(),
+        })
+    }
+
+    fn fog(&self) -> FogLookup<'_> {
+        let fog = self.fog.get_or_init(|| {
+            self.root
+                .get("fog")
+                .and_then(|fog| Fog::deserialize(fog).ok())
+        });
+        FogLookup { fog: fog.as_ref() }
+    }
+
+    fn blocks(&self, blocks: &Value) -> Value {
+        let mut blocks = blocks.clone();
+        if let Some(cells) = blocks.get_mut("cells").and_then(Value::as_object_mut) {
+            let fog = self.fog();
+            cells.retain(|key, _| {
+                parse_cell_key(key).is_some_and(|(row, col)| fog.is_revealed(row, col))
+            });
+        }
+        blocks
+    }
+
+    fn map(&self, map: &Value) -> Value {
+        let mut map = map.clone();
+        if let Some(map) = map.as_object_mut() {
+            let grid = Grid::from_map(map);
+            if let Some(hexmap) = map.get_mut("hexmap").and_then(Value::as_object_mut) {
+                redact_hexmap(hexmap, &grid, &self.fog());
+            }
+            // Cartographer bundles carry a DM image (secret doors, notes) and a
+            // player image; players only ever get the latter.
+            if let Some(player_image) = map.insert("playerImageUrl".into(), Value::Null) {
+                if !player_image.is_null() {
+                    map.insert("imageUrl".into(), player_image);
+                    map.insert("tiles".into(), Value::Null);
+                }
+            }
+        }
+        map
+    }
+
+    fn initiative(&self, initiative: &Value) -> Value {
+        let mut initiative = initiative.clone();
+        let Some(tracker) = initiative.as_object_mut() else {
+            return initiative;
+        };
+        if tracker.get("visible") != Some(&Value::Bool(true)) {
+            tracker.insert("entities".into(), Value::Array(Vec::new()));
+        }
+        // An entry linked to a dropped token would give away that the
+        // creature is on the map.
+        let visible_tokens: Vec<&Value> = self
+            .root
+            .get("tokens")
+            .and_then(Value::as_array)
+            .map(|tokens| {
+                tokens
+                    .iter()
+                    .filter(|token| self.token_visible(token))
+                    .filter_map(|token| token.get("id"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(entities) = tracker.get_mut("entities").and_then(Value::as_array_mut) {
+            for entity in entities.iter_mut().filter_map(Value::as_object_mut) {
+                if entity.get("isPc") != Some(&Value::Bool(true)) {
+                    entity.remove("hp");
+                    entity.remove("maxHp");
+                }
+                if entity
+                    .get("tokenId")
+                    .is_some_and(|id| !visible_tokens.contains(&id))
+                {
+                    entity.remove("tokenId");
+                }
+            }
+        }
+        initiative
+    }
+
+    fn token_visible(&self, token: &Value) -> bool {
+        let num = |key: &str| token.get(key).and_then(Value::as_f64);
+        token.get("hidden") != Some(&Value::Bool(true))
+            && self
+                .grid
+                .as_ref()
+                .zip(num("x").zip(num("y")))
+                .and_then(|(grid, (x, y))| grid.cell_at(x, y))
+                .is_some_and(|(row, col)| self.fog().is_revealed(row, col))
+    }
+}
+
+// A copy of object `value` without `keys`.
+fn without(value: &Value, keys: &[&str]) -> Value {
+    match value.as_object() {
+        Some(object) => object
+            .iter()
+            .filter(|(key, _)| !keys.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+        None => value.clone(),
+    }
+}
+
+struct FogLookup<'a> {
+    fog: Option<&'a Fog>,
+}
+
+impl FogLookup<'_> {
+    // Cells outside the fog grid (or with no readable fog at all) count as
+    // hidden, so a malformed state leaks nothing.
+    fn is_revealed(&self, row: i64, col: i64) -> bool {
+        let Some(fog) = self.fog else { return false };
+        match (u32::try_from(row), u32::try_from(col)) {
+            (Ok(row), Ok(col)) => !fog.is_fogged(row, col),
+            _ => false,
+        }
+    }
+}
+
+// Block cells are keyed "row,col".
+fn parse_cell_key(key: &str) -> Option<(i64, i64)> {
+    let (row, col) = key.split_once(',')?;
+    Some((row.trim().parse().ok()?, col.trim().parse().ok()?))
+}
+
+// The square fog grid laid over the map image.
+struct Grid {
+    size: f64,
+    offset_x: f64,
+    offset_y: f64,
+}
+
+impl Grid {
+    fn from_map(map: &Map<String, Value>) -> Self {
+        let num = |key: &str| map.get(key).and_then(Value::as_f64).unwrap_or(0.0);
+        Grid {
+            size: num("gridSize"),
+            offset_x: num("gridOffsetX"),
+            offset_y: num("gridOffsetY"),
+        }
+    }
+
+    fn cell_at(&self, x: f64, y: f64) -> Option<(i64, i64)> {
+        if self.size <= 0.0 {
+            return None;
+        }
+        let col = ((x - self.offset_x) / self.size).floor();
+        let row = ((y - self.offset_y) / self.size).floor();
+        Some((row as i64, col as i64))
+    }
+}
+
+// Mirrors `hexCenter` in `src/hexmap.ts` (1-based col/row, odd-q / odd-r).
+fn hex_center(hexmap: &Map<String, Value>, col: i64, row: i64) -> Option<(f64, f64)> {
+    let radius = hexmap.get("hexRadius").and_then(Value::as_f64)?;
+    let pad = hexmap.get("pad").and_then(Value::as_f64).unwrap_or(0.0);
+    let (c, r) = (col - 1, row - 1);
+    let sqrt3 = 3f64.sqrt();
+    if hexmap.get("orientation").and_then(Value::as_str) == Some("pointy") {
+        let w = sqrt3 * radius;
+        let cx = pad + w / 2.0 + w * (c as f64 + 0.5 * (r & 1) as f64);
+        let cy = pad + radius + 1.5 * radius * r as f64;
+        Some((cx, cy))
+    } else {
+        let h = sqrt3 * radius;
+        let cx = pad + radius + 1.5 * radius * c as f64;
+        let cy = pad + h / 2.0 + h * (r as f64 + 0.5 * (c & 1) as f64);
+        Some((cx, cy))
+    }
+}
+
+// A hex counts as revealed when the fog cell under its centre is.
+fn redact_hexmap(hexmap: &mut Map<String, Value>, grid: &Grid, fog: &FogLookup) {
+    hexmap.insert("defaultUrl".into(), Value::Null);
+    let Some(Value::Object(links)) = hexmap.get("links").cloned() else {
+        return;
+    };
+    let visible: Map<String, Value> = links
+        .into_iter()
+        .filter(|(key, _)| {
+            // Hex links are keyed "col,row" (the other way round from blocks).
+            parse_cell_key(key)
+                .and_then(|(col, row)| hex_center(hexmap, col, row))
+                .and_then(|(x, y)| grid.cell_at(x, y))
+                .is_some_and(|(row, col)| fog.is_revealed(row, col))
+        })
+        .collect();
+    hexmap.insert("links".into(), Value::Object(visible));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // A 4x4 fog grid of 50px cells with only the top row revealed, and
+    // something to hide in every part of the state.
+    fn dm_state() -> Value {
+        let mut cells = vec![vec![true; 4]; 4];
+        cells[0] = vec![false; 4];
+        json!({
+            "map": {
+                "imageUrl": "blob:dm",
+                "playerImageUrl": "blob:player",
+                "tiles": { "id": "abc", "width": 200, "height": 200, "tileSize": 256, "levels": 1 },
+                "gridSize": 50.0,
+                "gridOffsetX": 0.0,
+                "gridOffsetY": 0.0,
+                "hexmap": {
+                    "orientation": "flat",
+                    "hexRadius": 50.0,
+                    "pad": 0.0,
+                    "defaultUrl": "obsidian://open?file=key",
+                    // Centres (50, 43) under the revealed row, (50, 130) under fog.
+                    "links": { "1,1": "obsidian://hex-1-1", "1,2": "obsidian://hex-1-2" },
+                },
+            },
+            "fog": { "cols": 4, "rows": 4, "cells": cells },
+            "blocks": { "cells": { "0,1": "#ff0000", "2,2": "#00ff00" } },
+            "walls": { "walls": [{ "x1": 0, "y1": 0, "x2": 50, "y2": 0 }], "lights": [{ "x": 10, "y": 10 }] },
+            "party": { "position": [1, 1], "day": 3, "log": [{ "day": 1 }], "encounters": [{ "hex": [1, 1] }] },
+            "rolls": [{ "id": "public", "secret": false }, { "id": "gm", "secret": true }],
+            "tokens": [
+                { "id": "seen", "x": 75.0, "y": 25.0 },
+                { "id": "hidden", "x": 25.0, "y": 25.0, "hidden": true },
+                { "id": "fogged", "x": 25.0, "y": 125.0 },
+            ],
+            "initiative": {
+                "visible": true,
+                "entities": [
+                    { "id": "pc", "isPc": true, "hp": 12, "maxHp": 20, "tokenId": "seen" },
+                    { "id": "npc", "isPc": false, "hp": 5, "maxHp": 7, "tokenId": "fogged" },
+                    { "id": "lurker", "isPc": false, "tokenId": "hidden" },
+                ],
+            },
+        })
+    }
+
+    #[test]
+    fn redacts_everything_players_must_not_see() {
+        let state = player_projection(&dm_state());
+
+        assert_eq!(state["blocks"]["cells"], json!({ "0,1": "#ff0000" }));
+        let hexmap = &state["map"]["hexmap"];
+        assert!(hexmap["defaultUrl"].is_null());
+        assert_eq!(hexmap["links"], json!({ "1,1": "obsidian://hex-1-1" }));
+
+        assert_eq!(state["map"]["imageUrl"], "blob:player");
+        assert!(state["map"]["playerImageUrl"].is_null());
+        assert!(state["map"]["tiles"].is_null());
+
+        assert!(state.get("walls").is_none());
+        assert_eq!(state["party"], json!({ "position": [1, 1], "day": 3 }));
+        assert_eq!(state["rolls"], json!([{ "id": "public", "secret": false }]));
+        assert_eq!(
+            state["tokens"],
+            json!([{ "id": "seen", "x": 75.0, "y": 25.0 }])
+        );
+
+        assert_eq!(
+            state["initiative"]["entities"],
+            json!([
+                { "id": "pc", "isPc": true, "hp": 12, "maxHp": 20, "tokenId": "seen" },
+                { "id": "npc", "isPc": false },
+                { "id": "lurker", "isPc": false },
+            ])
+        );
+    }
+
+    #[test]
+    fn keeps_a_shared_image_and_hides_a_hidden_tracker() {
+        let mut state = dm_state();
+        state["map"]["playerImageUrl"] = Value::Null;
+        state["initiative"]["visible"] = Value::Bool(false);
+        let state = player_projection(&state);
+
+        assert_eq!(state["map"]["imageUrl"], "blob:dm");
+        assert_eq!(state["map"]["tiles"]["id"], "abc");
+        assert_eq!(state["initiative"]["entities"], json!([]));
+    }
+
+    #[test]
+    fn unreadable_fog_hides_everything_fog_covers() {
+        let mut state = dm_state();
+        state["fog"] = json!({ "cols": 4, "rows": 4 });
+        let state = player_projection(&state);
+
+        assert_eq!(state["blocks"]["cells"], json!({}));
+        assert_eq!(state["map"]["hexmap"]["links"], json!({}));
+        assert_eq!(state["tokens"], json!([]));
+    }
+}
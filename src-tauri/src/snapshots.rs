@@ -0,0 +1,284 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::identity;
+use crate::migrate;
+use crate::persistence::{self, PersistError};
+use crate::types::SavedMapState;
+
+// Named snapshots of a map's state ("Tuesday group", "before the boss"),
+// stored as `<save dir>/snapshots/<save name>/<id>.json` next to the map's
+// live save, so they follow the map through renames like the save itself.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotMeta {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64, // ms since the Unix epoch
+    pub updated_at: u64,
+    // Small PNG `data:` URL captured from the DM canvas, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotFile {
+    meta: SnapshotMeta,
+    state: serde_json::Value, // a SavedMapState of any schema version
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+// `None` for a map with no save yet (and so no snapshots) unless `create`,
+// which gives it one.
+fn snapshot_dir(
+    save_dir: &Path,
+    map_path: &str,
+    create: bool,
+) -> Result<Option<PathBuf>, PersistError> {
+    let save = identity::resolve_save_file(save_dir, map_path, create)?;
+    Ok(save.map(|save| {
+        save_dir
+            .join("snapshots")
+            .join(save.file_stem().unwrap_or_default())
+    }))
+}
+
+// The directory of an existing snapshot's map; a map without one has no
+// snapshot `id`.
+fn existing_snapshot_dir(
+    save_dir: &Path,
+    map_path: &str,
+    id: &str,
+) -> Result<PathBuf, PersistError> {
+    snapshot_dir(save_dir, map_path, false)?
+        .ok_or_else(|| PersistError::NotFound(format!("snapshot {id:?}")))
+}
+
+// Snapshot ids become file names, so only accept ids this module generated.
+fn snapshot_path(dir: &Path, id: &str) -> Result<PathBuf, PersistError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(PersistError::NotFound(format!("snapshot {id:?}")));
+    }
+    Ok(dir.join(format!("{id}.json")))
+}
+
+fn read_snapshot(path: &Path) -> Result<SnapshotFile, PersistError> {
+    let bytes = fs::read(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => PersistError::NotFound(path.display().to_string()),
+        _ => PersistError::Io {
+            path: path.to_path_buf(),
+            source: e,
+        },
+    })?;
+    serde_json::from_slice(&bytes).map_err(|e| PersistError::Corrupt {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+fn write_snapshot(path: &Path, file: &SnapshotFile) -> Result<(), PersistError> {
+    let json = serde_json::to_vec_pretty(file).map_err(|e| PersistError::Corrupt {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    persistence::write_atomic(path, &json, 0)
+}
+
+// A fresh id and its creation time: now, or just after it if another
+// snapshot already has that millisecond, so creation times never tie.
+fn new_id(dir: &Path) -> (String, u64) {
+    let mut stamp = now_ms();
+    loop {
+        let id = format!("snap-{stamp:x}");
+        if !dir.join(format!("{id}.json")).exists() {
+            return (id, stamp);
+        }
+        stamp += 1;
+    }
+}
+
+/// All snapshots of a map, newest first. Unreadable files are skipped.
+pub fn list(save_dir: &Path, map_path: &str) -> Result<Vec<SnapshotMeta>, PersistError> {
+    let Some(dir) = snapshot_dir(save_dir, map_path, false)? else {
+        return Ok(Vec::new());
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(PersistError::Io {
+                path: dir,
+                source: e,
+            })
+        }
+    };
+    let mut snapshots: Vec<SnapshotMeta> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| read_snapshot(&path).ok())
+        .map(|file| file.meta)
+        .collect();
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(snapshots)
+}
+
+pub fn create(
+    save_dir: &Path,
+    state: &SavedMapState,
+    name: &str,
+    thumbnail: Option<String>,
+) -> Result<SnapshotMeta, PersistError> {
+    let dir = snapshot_dir(save_dir, &state.file_path, true)?
+        .expect("resolve_save_file always yields a path when creating");
+    let (id, now) = new_id(&dir);
+    let meta = SnapshotMeta {
+        id,
+        name: name.trim().to_string(),
+        created_at: now,
+        updated_at: now,
+        thumbnail,
+    };
+    let state = SavedMapState {
+        schema_version: migrate::SCHEMA_VERSION,
+        ..state.clone()
+    };
+    let file = SnapshotFile {
+        meta: meta.clone(),
+        state: serde_json::to_value(&state).expect("SavedMapState serializes"),
+    };
+    write_snapshot(&snapshot_path(&dir, &meta.id)?, &file)?;
+    Ok(meta)
+}
+
+pub fn rename(
+    save_dir: &Path,
+    map_path: &str,
+    id: &str,
+    name: &str,
+) -> Result<SnapshotMeta, PersistError> {
+    let path = snapshot_path(&existing_snapshot_dir(save_dir, map_path, id)?, id)?;
+    let mut file = read_snapshot(&path)?;
+    file.meta.name = name.trim().to_string();
+    file.meta.updated_at = now_ms();
+    write_snapshot(&path, &file)?;
+    Ok(file.meta)
+}
+
+pub fn duplicate(
+    save_dir: &Path,
+    map_path: &str,
+    id: &str,
+    name: &str,
+) -> Result<SnapshotMeta, PersistError> {
+    let dir = existing_snapshot_dir(save_dir, map_path, id)?;
+    let mut file = read_snapshot(&snapshot_path(&dir, id)?)?;
+    let (id, now) = new_id(&dir);
+    file.meta = SnapshotMeta {
+        id,
+        name: name.trim().to_string(),
+        created_at: now,
+        updated_at: now,
+        thumbnail: file.meta.thumbnail,
+    };
+    write_snapshot(&snapshot_path(&dir, &file.meta.id)?, &file)?;
+    Ok(file.meta)
+}
+
+pub fn delete(save_dir: &Path, map_path: &str, id: &str) -> Result<(), PersistError> {
+    let path = snapshot_path(&existing_snapshot_dir(save_dir, map_path, id)?, id)?;
+    fs::remove_file(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => PersistError::NotFound(path.display().to_string()),
+        _ => PersistError::Io {
+            path: path.clone(),
+            source: e,
+        },
+    })
+}
+
+/// The snapshot's state, migrated to the current schema and pointed at the
+/// map's current path, ready to apply to the live session.
+pub fn restore(save_dir: &Path, map_path: &str, id: &str) -> Result<SavedMapState, PersistError> {
+    let path = snapshot_path(&existing_snapshot_dir(save_dir, map_path, id)?, id)?;
+    let file = read_snapshot(&path)?;
+    let state = persistence::parse_saved_state(file.state, &path)?;
+    Ok(SavedMapState {
+        file_path: map_path.to_string(),
+        ..state
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    fn saved_state(map_path: &str, grid_size: f64) -> SavedMapState {
+        let value = serde_json::from_str(include_str!("../tests/fixtures/saves/v2.json")).unwrap();
+        let state = persistence::parse_saved_state(value, Path::new("v2.json")).unwrap();
+        SavedMapState {
+            file_path: map_path.to_string(),
+            grid_size,
+            ..state
+        }
+    }
+
+    #[test]
+    fn creates_lists_restores_and_deletes() {
+        let dir = scratch_dir("lifecycle");
+        let saves = dir.join("saves");
+        let map = dir.join("keep.png");
+        fs::write(&map, b"keep").unwrap();
+        let map = map.to_str().unwrap();
+
+        // Nothing saved yet: no snapshots, and nothing written to find that out.
+        assert!(list(&saves, map).unwrap().is_empty());
+        assert!(!saves.exists());
+        assert!(matches!(
+            restore(&saves, map, "snap-1"),
+            Err(PersistError::NotFound(_))
+        ));
+
+        let first = create(&saves, &saved_state(map, 40.0), " Before the boss ", None).unwrap();
+        assert_eq!(first.name, "Before the boss");
+        let second = duplicate(&saves, map, &first.id, "Tuesday group").unwrap();
+        let listed: Vec<String> = list(&saves, map)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(listed, ["Tuesday group", "Before the boss"]);
+
+        let restored = restore(&saves, map, &second.id).unwrap();
+        assert_eq!(
+            (restored.grid_size, restored.file_path.as_str()),
+            (40.0, map)
+        );
+        assert_eq!(
+            rename(&saves, map, &first.id, "Session 3").unwrap().name,
+            "Session 3"
+        );
+
+        delete(&saves, map, &first.id).unwrap();
+        assert_eq!(list(&saves, map).unwrap().len(), 1);
+        assert!(matches!(
+            delete(&saves, map, &first.id),
+            Err(PersistError::NotFound(_))
+        ));
+        assert!(matches!(
+            restore(&saves, map, "../index"),
+            Err(PersistError::NotFound(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,557 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::projection::{self, Projector};
+use crate::types::{Drawing, DrawingPoint, RollRecord, Token, ROLL_HISTORY_LIMIT};
+
+// Sync hub between the DM window and its subscribers (the player window and
+// LAN clients). The DM sends small typed patches instead of its whole
+// `AppState`; the hub applies them to its copy of the DM state, re-derives
+// the player projection, and forwards only what changed in *that*, numbered
+// so subscribers can spot a missed delta and ask for a full resync.
+
+/// Full-state event, also the reply to a resync request.
+pub const STATE_SYNC_EVENT: &str = "vtt-state-sync";
+/// Delta event: a `StateDelta`.
+pub const STATE_DELTA_EVENT: &str = "vtt-state-delta";
+
+pub type SharedHub = Arc<Mutex<SyncHub>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FogCell {
+    pub row: usize,
+    pub col: usize,
+    pub fogged: bool,
+}
+
+/// One change to an `AppState`. Mirrored by `StatePatch` in `src/store.ts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StatePatch {
+    FogCells { cells: Vec<FogCell> },
+    DrawingAdded { drawing: Drawing },
+    DrawingRemoved { id: String },
+    Laser { points: Vec<DrawingPoint> },
+    Initiative { initiative: Value },
+    RollAdded { roll: RollRecord },
+    // A token placed, moved or edited; replaces the one with the same id.
+    TokenUpserted { token: Token },
+    TokenRemoved { id: String },
+    // Replace one top-level field wholesale (map, blocks, calibration, ...).
+    Set { field: String, value: Value },
+}
+
+impl StatePatch {
+    /// The top-level `AppState` field this patch changes.
+    pub fn field(&self) -> &str {
+        match self {
+            StatePatch::FogCells { .. } => "fog",
+            StatePatch::DrawingAdded { .. } | StatePatch::DrawingRemoved { .. } => "drawings",
+            StatePatch::Laser { .. } => "laserPoints",
+            StatePatch::Initiative { .. } => "initiative",
+            StatePatch::RollAdded { .. } => "rolls",
+            StatePatch::TokenUpserted { .. } | StatePatch::TokenRemoved { .. } => "tokens",
+            StatePatch::Set { field, .. } => field,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateDelta {
+    pub seq: u64,
+    pub patches: Vec<StatePatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FullState {
+    pub seq: u64,
+    pub state: Value, // the player projection
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    #[error("no state to patch yet; send the full state first")]
+    NoBaseState,
+}
+
+#[derive(Default)]
+pub struct SyncHub {
+    seq: u64,
+    dm_state: Option<Value>,
+    projection: Option<Value>,
+}
+
+impl SyncHub {
+    /// Take a complete DM state (first sync, or after the DM window reloads).
+    pub fn replace(&mut self, state: Value) -> FullState {
+        let projection = projection::player_projection(&state);
+        self.seq += 1;
+        self.dm_state = Some(state);
+        self.projection = Some(projection.clone());
+        FullState {
+            seq: self.seq,
+            state: projection,
+        }
+    }
+
+    /// Apply DM patches. Returns the delta for subscribers, or `None` when
+    /// nothing players can see changed. Only the fields the patches touch
+    /// (and those projected from them) are projected again.
+    pub fn apply(&mut self, patches: Vec<StatePatch>) -> Result<Option<StateDelta>, SyncError> {
+        let (Some(dm_state), Some(projection)) = (self.dm_state.as_mut(), self.projection.as_mut())
+        else {
+            return Err(SyncError::NoBaseState);
+        };
+        let mut touched = BTreeSet::new();
+        for patch in patches {
+            let field = patch.field().to_string();
+            apply_patch(dm_state, patch);
+            touched.extend(projection::dependents(&field).iter().map(|f| f.to_string()));
+            touched.insert(field);
+        }
+        let (Some(root), Some(projection)) = (dm_state.as_object(), projection.as_object_mut())
+        else {
+            return Ok(None);
+        };
+        let projector = Projector::new(root);
+        let mut patches = Vec::new();
+        for field in touched {
+            // Fields players never get stay out of the projection.
+            let Some(value) = projector.field(&field) else {
+                continue;
+            };
+            let before = projection.get(&field).unwrap_or(&Value::Null);
+            if *before != value {
+                patches.extend(diff_field(&field, before, &value));
+                projection.insert(field, value);
+            }
+        }
+        if patches.is_empty() {
+            return Ok(None);
+        }
+        self.seq += 1;
+        Ok(Some(StateDelta {
+            seq: self.seq,
+            patches,
+        }))
+    }
+
+    /// The current player projection, for new subscribers and resyncs.
+    pub fn full(&self) -> Option<FullState> {
+        self.projection.as_ref().map(|state| FullState {
+            seq: self.seq,
+            state: state.clone(),
+        })
+    }
+}
+
+/// Apply one patch to an `AppState` JSON value. Out-of-range fog cells are
+/// ignored, and re-adding a drawing or roll that's already there is a no-op, so a
+/// patch replayed after a resync does no harm.
+pub fn apply_patch(state: &mut Value, patch: StatePatch) {
+    let Some(root) = state.as_object_mut() else {
+        return;
+    };
+    match patch {
+        StatePatch::FogCells { cells } => {
+            let Some(rows) = root
+                .get_mut("fog")
+                .and_then(|fog| fog.get_mut("cells"))
+                .and_then(Value::as_array_mut)
+            else {
+                return;
+            };
+            for FogCell { row, col, fogged } in cells {
+                if let Some(cell) = rows.get_mut(row).and_then(|r| r.get_mut(col)) {
+                    *cell = Value::Bool(fogged);
+                }
+            }
+        }
+        StatePatch::DrawingAdded { drawing } => {
+            let drawings = array_field(root, "drawings");
+            if !drawings
+                .iter()
+                .any(|d| d.get("id").and_then(Value::as_str) == Some(&drawing.id))
+            {
+                drawings.push(serde_json::to_value(drawing).expect("Drawing serializes"));
+            }
+        }
+        StatePatch::DrawingRemoved { id } => {
+            array_field(root, "drawings")
+                .retain(|d| d.get("id").and_then(Value::as_str) != Some(&id));
+        }
+        StatePatch::Laser { points } => {
+            root.insert(
+                "laserPoints".into(),
+                serde_json::to_value(points).expect("points serialize"),
+            );
+        }
+        StatePatch::Initiative { initiative } => {
+            root.insert("initiative".into(), initiative);
+        }
+        StatePatch::RollAdded { roll } => {
+            let rolls = array_field(root, "rolls");
+            if !rolls
+                .iter()
+                .any(|r| r.get("id").and_then(Value::as_str) == Some(&roll.id))
+            {
+                rolls.push(serde_json::to_value(roll).expect("RollRecord serializes"));
+                // As the DM window does (`appendRoll`).
+                let excess = rolls.len().saturating_sub(ROLL_HISTORY_LIMIT);
+                rolls.drain(..excess);
+            }
+        }
+        StatePatch::TokenUpserted { token } => {
+            let value = serde_json::to_value(&token).expect("Token serializes");
+            let tokens = array_field(root, "tokens");
+            match tokens
+                .iter_mut()
+                .find(|t| t.get("id").and_then(Value::as_str) == Some(&token.id))
+            {
+                Some(existing) => *existing = value,
+                None => tokens.push(value),
+            }
+        }
+        StatePatch::TokenRemoved { id } => {
+            array_field(root, "tokens")
+                .retain(|t| t.get("id").and_then(Value::as_str) != Some(&id));
+        }
+        StatePatch::Set { field, value } => {
+            root.insert(field, value);
+        }
+    }
+}
+
+fn array_field<'a>(root: &'a mut Map<String, Value>, key: &str) -> &'a mut Vec<Value> {
+    let value = root.entry(key).or_insert_with(|| Value::Array(Vec::new()));
+    if !value.is_array() {
+        *value = Value::Array(Vec::new());
+    }
+    value.as_array_mut().expect("just made it an array")
+}
+
+/// Patches that turn projected `field` from `old` into `new`.
+fn diff_field(field: &str, old: &Value, new: &Value) -> Vec<StatePatch> {
+    let patch = match field {
+        "fog" => diff_fog(old, new).map(|cells| vec![StatePatch::FogCells { cells }]),
+        "drawings" => diff_drawings(old, new),
+        "laserPoints" => serde_json::from_value(new.clone())
+            .ok()
+            .map(|points| vec![StatePatch::Laser { points }]),
+        "initiative" => Some(vec![StatePatch::Initiative {
+            initiative: new.clone(),
+        }]),
+        "rolls" => diff_rolls(old, new),
+        "tokens" => diff_tokens(old, new),
+        _ => None,
+    };
+    patch.unwrap_or_else(|| {
+        vec![StatePatch::Set {
+            field: field.to_string(),
+            value: new.clone(),
+        }]
+    })
+}
+
+// Changed cells, or `None` when the grid was resized (or there are so many
+// changes that sending the whole fog is smaller).
+fn diff_fog(old: &Value, new: &Value) -> Option<Vec<FogCell>> {
+    if old.get("cols") != new.get("cols") || old.get("rows") != new.get("rows") {
+        return None;
+    }
+    let old_rows = old.get("cells")?.as_array()?;
+    let new_rows = new.get("cells")?.as_array()?;
+    let mut cells = Vec::new();
+    let mut total = 0;
+    for (row, (a, b)) in old_rows.iter().zip(new_rows).enumerate() {
+        let (a, b) = (a.as_array()?, b.as_array()?);
+        total += b.len();
+        if a == b {
+            continue;
+        }
+        for (col, (x, y)) in a.iter().zip(b).enumerate() {
+            if x != y {
+                cells.push(FogCell {
+                    row,
+                    col,
+                    fogged: y.as_bool()?,
+                });
+            }
+        }
+    }
+    // A changed cell costs ~6x a plain `true`/`false` on the wire.
+    (cells.len() * 6 <= total).then_some(cells)
+}
+
+// Appended or single removed drawings; anything else is a full `Set`.
+fn diff_drawings(old: &Value, new: &Value) -> Option<Vec<StatePatch>> {
+    let (old, new) = (old.as_array()?, new.as_array()?);
+    if new.len() > old.len() && new.starts_with(old) {
+        return new[old.len()..]
+            .iter()
+            .map(|d| {
+                serde_json::from_value(d.clone())
+                    .ok()
+                    .map(|drawing| StatePatch::DrawingAdded { drawing })
+            })
+            .collect();
+    }
+    if new.len() + 1 == old.len() {
+        let removed = old.iter().position(|d| !new.contains(d))?;
+        let mut rest = old.clone();
+        let drawing = rest.remove(removed);
+        if rest == *new {
+            let id = drawing.get("id")?.as_str()?.to_string();
+            return Some(vec![StatePatch::DrawingRemoved { id }]);
+        }
+    }
+    None
+}
+
+// Rolls only ever get appended (or cleared, which is a full `Set`). Once the
+// history is full the oldest drops off too, which is also a `Set`: the
+// projection has no secret rolls, so players can't trim it the same way.
+fn diff_rolls(old: &Value, new: &Value) -> Option<Vec<StatePatch>> {
+    let (old, new) = (old.as_array()?, new.as_array()?);
+    if new.len() <= old.len() || !new.starts_with(old) {
+        return None;
+    }
+    new[old.len()..]
+        .iter()
+        .map(|r| {
+            serde_json::from_value(r.clone())
+                .ok()
+                .map(|roll| StatePatch::RollAdded { roll })
+        })
+        .collect()
+}
+
+// Tokens by id: changed or new ones are upserted, missing ones removed, so
+// moving one token doesn't resend every token's image.
+fn diff_tokens(old: &Value, new: &Value) -> Option<Vec<StatePatch>> {
+    let (old, new) = (old.as_array()?, new.as_array()?);
+    let id = |t: &Value| t.get("id").and_then(Value::as_str).map(str::to_string);
+    let mut patches = Vec::new();
+    for token in new {
+        if !old.contains(token) {
+            patches.push(StatePatch::TokenUpserted {
+                token: serde_json::from_value(token.clone()).ok()?,
+            });
+        }
+    }
+    let new_ids: Vec<String> = new.iter().filter_map(id).collect();
+    for token in old {
+        let old_id = id(token)?;
+        if !new_ids.contains(&old_id) {
+            patches.push(StatePatch::TokenRemoved { id: old_id });
+        }
+    }
+    Some(patches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 4x4 fog of 50px cells; only the top row is revealed.
+    fn hub() -> SyncHub {
+        let mut cells = vec![vec![true; 4]; 4];
+        cells[0] = vec![false; 4];
+        let mut hub = SyncHub::default();
+        hub.replace(json!({
+            "map": { "gridSize": 50.0, "gridOffsetX": 0.0, "gridOffsetY": 0.0 },
+            "fog": { "cols": 4, "rows": 4, "cells": cells },
+            "drawings": [],
+            "rolls": [],
+            "tokens": [],
+        }));
+        hub
+    }
+
+    fn patches(hub: &mut SyncHub, patches: Vec<StatePatch>) -> Vec<StatePatch> {
+        hub.apply(patches)
+            .unwrap()
+            .map(|delta| delta.patches)
+            .unwrap_or_default()
+    }
+
+    fn drawing(id: &str) -> Drawing {
+        serde_json::from_value(
+            json!({ "id": id, "points": [], "color": "#f00", "strokeWidth": 3.0 }),
+        )
+        .unwrap()
+    }
+
+    fn token(id: &str, x: f64, y: f64) -> Token {
+        serde_json::from_value(
+            json!({ "id": id, "name": id, "color": "#00f", "size": 1.0, "x": x, "y": y }),
+        )
+        .unwrap()
+    }
+
+    fn roll(id: &str, secret: bool) -> RollRecord {
+        serde_json::from_value(json!({
+            "id": id, "by": "DM", "secret": secret, "at": 0,
+            "expression": "1d20", "total": 12, "terms": [], "breakdown": "1d20 [12] = 12",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn patches_need_a_full_state_first() {
+        let mut hub = SyncHub::default();
+        assert!(matches!(
+            hub.apply(vec![StatePatch::DrawingRemoved { id: "d".into() }]),
+            Err(SyncError::NoBaseState)
+        ));
+        assert!(hub.full().is_none());
+    }
+
+    #[test]
+    fn numbers_full_states_and_visible_deltas() {
+        let mut hub = hub();
+        assert_eq!(hub.full().unwrap().seq, 1);
+
+        // Walls never reach players, so nothing is sent and no number used.
+        let walls = StatePatch::Set {
+            field: "walls".into(),
+            value: json!({ "walls": [] }),
+        };
+        assert!(hub.apply(vec![walls]).unwrap().is_none());
+        let delta = hub
+            .apply(vec![StatePatch::DrawingAdded {
+                drawing: drawing("d1"),
+            }])
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.seq, 2);
+        assert_eq!(hub.full().unwrap().seq, 2);
+        assert_eq!(hub.replace(json!({})).seq, 3);
+    }
+
+    #[test]
+    fn sends_fog_cells_until_the_whole_fog_is_smaller() {
+        let mut hub = hub();
+        let cell = |row, col| FogCell {
+            row,
+            col,
+            fogged: false,
+        };
+        assert_eq!(
+            patches(
+                &mut hub,
+                vec![StatePatch::FogCells {
+                    cells: vec![cell(1, 0), cell(1, 1)]
+                }]
+            ),
+            [StatePatch::FogCells {
+                cells: vec![cell(1, 0), cell(1, 1)]
+            }]
+        );
+        let sent = patches(
+            &mut hub,
+            vec![StatePatch::FogCells {
+                cells: vec![cell(2, 0), cell(2, 1), cell(2, 2)],
+            }],
+        );
+        assert!(matches!(sent.as_slice(), [StatePatch::Set { field, .. }] if field == "fog"));
+
+        // A resized grid can't be sent as cells either.
+        let resized = json!({ "cols": 2, "rows": 2, "cells": [[false, false], [true, true]] });
+        let sent = patches(
+            &mut hub,
+            vec![StatePatch::Set {
+                field: "fog".into(),
+                value: resized.clone(),
+            }],
+        );
+        assert_eq!(
+            sent,
+            [StatePatch::Set {
+                field: "fog".into(),
+                value: resized
+            }]
+        );
+    }
+
+    #[test]
+    fn sends_drawings_rolls_and_tokens_one_by_one() {
+        let mut hub = hub();
+        let added = vec![
+            StatePatch::DrawingAdded {
+                drawing: drawing("d1"),
+            },
+            StatePatch::DrawingAdded {
+                drawing: drawing("d2"),
+            },
+        ];
+        assert_eq!(patches(&mut hub, added.clone()), added);
+        let removed = vec![StatePatch::DrawingRemoved { id: "d1".into() }];
+        assert_eq!(patches(&mut hub, removed.clone()), removed);
+
+        let public = vec![StatePatch::RollAdded {
+            roll: roll("r1", false),
+        }];
+        assert_eq!(patches(&mut hub, public.clone()), public);
+        assert!(patches(
+            &mut hub,
+            vec![StatePatch::RollAdded {
+                roll: roll("r2", true)
+            }]
+        )
+        .is_empty());
+
+        let placed = vec![StatePatch::TokenUpserted {
+            token: token("goblin", 25.0, 25.0),
+        }];
+        assert_eq!(patches(&mut hub, placed.clone()), placed);
+        let moved = vec![StatePatch::TokenUpserted {
+            token: token("goblin", 75.0, 25.0),
+        }];
+        assert_eq!(patches(&mut hub, moved.clone()), moved);
+        // Walking into the fog looks like leaving, to players.
+        let fogged = StatePatch::TokenUpserted {
+            token: token("goblin", 75.0, 125.0),
+        };
+        assert_eq!(
+            patches(&mut hub, vec![fogged]),
+            [StatePatch::TokenRemoved {
+                id: "goblin".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn revealing_fog_sends_what_it_uncovers() {
+        let mut hub = hub();
+        hub.apply(vec![StatePatch::TokenUpserted {
+            token: token("goblin", 75.0, 125.0),
+        }])
+        .unwrap();
+        let revealed = FogCell {
+            row: 2,
+            col: 1,
+            fogged: false,
+        };
+        assert_eq!(
+            patches(
+                &mut hub,
+                vec![StatePatch::FogCells {
+                    cells: vec![revealed]
+                }]
+            ),
+            [
+                StatePatch::FogCells {
+                    cells: vec![revealed]
+                },
+                StatePatch::TokenUpserted {
+                    token: token("goblin", 75.0, 125.0)
+                },
+            ]
+        );
+    }
+}
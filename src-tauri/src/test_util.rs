@@ -0,0 +1,13 @@
+use std::fs;
+use std::path::PathBuf;
+
+// Helpers shared by the unit tests.
+
+/// An empty directory for one test, under the system temp dir. `name` must
+/// be unique across the crate's tests, which run in parallel.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vtt-test-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
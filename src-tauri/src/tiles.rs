@@ -0,0 +1,349 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader};
+use serde::{Deserialize, Serialize};
+
+use crate::identity;
+
+// Image maps are cut into a pyramid of small tiles so the views only load
+// what's on screen at the current zoom; Konva chokes on a 20k-pixel map drawn
+// as one image. The pyramid is built once per map (by content, so a moved
+// file reuses it) under `<app data>/tiles/<id>`:
+//
+//   tiles.json            the `TileSet`, written last
+//   <level>/<col>_<row>.png
+
+pub const TILE_SIZE: u32 = 256;
+/// The URI scheme tiles are served on: `tiles://localhost/<id>/<level>/<col>/<row>`
+/// (`http://tiles.localhost/...` on Windows).
+pub const SCHEME: &str = "tiles";
+const MANIFEST: &str = "tiles.json";
+// Pyramids kept on disk; the least recently loaded go first.
+const MAX_CACHED: usize = 8;
+
+// Ids of the pyramids being built. Loading a map again while its pyramid is
+// still being written waits for it rather than start over on top of it;
+// other maps, cached or not, don't wait.
+static BUILDING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static BUILT: Condvar = Condvar::new();
+
+fn building() -> MutexGuard<'static, BTreeSet<String>> {
+    BUILDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Claims `id` for as long as it lives, after waiting out anyone else's claim.
+struct Building(String);
+
+impl Building {
+    fn claim(id: &str) -> Self {
+        let mut ids = building();
+        while ids.contains(id) {
+            ids = BUILT.wait(ids).unwrap_or_else(|e| e.into_inner());
+        }
+        ids.insert(id.to_string());
+        Building(id.to_string())
+    }
+}
+
+impl Drop for Building {
+    fn drop(&mut self) {
+        building().remove(&self.0);
+        BUILT.notify_all();
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TileError {
+    #[error("could not read the image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl TileError {
+    fn io(path: &Path, source: io::Error) -> Self {
+        TileError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// A map image's tile pyramid. Level 0 is the image at full size; each level
+/// after it is half the size of the one before, down to one that fits in a
+/// single tile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TileSet {
+    pub id: String,
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: u32,
+    pub levels: u32,
+}
+
+/// The tile pyramid for the image at `source`, built under `cache` unless an
+/// earlier load already did.
+pub fn load(source: &Path, cache: &Path) -> Result<TileSet, TileError> {
+    let id = identity::fingerprint(source)
+        .map_err(|e| TileError::io(source, e))?
+        .key();
+    let dir = cache.join(&id);
+    let _claim = Building::claim(&id);
+    if let Some(tiles) = read_manifest(&dir) {
+        // Marks it recently used, for `prune`.
+        let _ = File::options()
+            .write(true)
+            .open(dir.join(MANIFEST))
+            .and_then(|f| f.set_modified(SystemTime::now()));
+        return Ok(tiles);
+    }
+
+    // Whatever is there is an interrupted build.
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(TileError::io(&dir, e)),
+        _ => {}
+    }
+    let image = decode(source)?;
+    let tiles = TileSet {
+        id,
+        width: image.width(),
+        height: image.height(),
+        tile_size: TILE_SIZE,
+        levels: write_levels(image, &dir)?,
+    };
+    let manifest = dir.join(MANIFEST);
+    let json = serde_json::to_vec(&tiles).map_err(|e| TileError::io(&manifest, e.into()))?;
+    fs::write(&manifest, json).map_err(|e| TileError::io(&manifest, e))?;
+    prune(cache, &tiles.id);
+    Ok(tiles)
+}
+
+/// The file behind a tile request path (`/<id>/<level>/<col>/<row>`), or
+/// `None` if the path isn't one.
+pub fn tile_path(cache: &Path, request_path: &str) -> Option<PathBuf> {
+    let mut parts = request_path.trim_start_matches('/').split('/');
+    let id = parts
+        .next()
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))?;
+    let mut number = || parts.next()?.parse::<u32>().ok();
+    let (level, col, row) = (number()?, number()?, number()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(
+        cache
+            .join(id)
+            .join(level.to_string())
+            .join(format!("{col}_{row}.png")),
+    )
+}
+
+fn read_manifest(dir: &Path) -> Option<TileSet> {
+    let tiles: TileSet = serde_json::from_slice(&fs::read(dir.join(MANIFEST)).ok()?).ok()?;
+    (tiles.tile_size == TILE_SIZE).then_some(tiles)
+}
+
+fn decode(path: &Path) -> Result<DynamicImage, TileError> {
+    let mut reader = ImageReader::open(path)
+        .and_then(ImageReader::with_guessed_format)
+        .map_err(|e| TileError::io(path, e))?;
+    // Huge maps are the point; the default limits stop at 512 MiB.
+    reader.no_limits();
+    Ok(reader.decode()?)
+}
+
+// Writes every level and returns how many there are.
+fn write_levels(mut image: DynamicImage, dir: &Path) -> Result<u32, TileError> {
+    let mut level = 0;
+    loop {
+        write_level(&image, &dir.join(level.to_string()))?;
+        level += 1;
+        if image.width() <= TILE_SIZE && image.height() <= TILE_SIZE {
+            return Ok(level);
+        }
+        let (width, height) = (image.width().div_ceil(2), image.height().div_ceil(2));
+        image = image.resize_exact(width, height, FilterType::Triangle);
+    }
+}
+
+fn write_level(image: &DynamicImage, dir: &Path) -> Result<(), TileError> {
+    fs::create_dir_all(dir).map_err(|e| TileError::io(dir, e))?;
+    for row in 0..image.height().div_ceil(TILE_SIZE) {
+        for col in 0..image.width().div_ceil(TILE_SIZE) {
+            let (x, y) = (col * TILE_SIZE, row * TILE_SIZE);
+            let tile = image.crop_imm(
+                x,
+                y,
+                TILE_SIZE.min(image.width() - x),
+                TILE_SIZE.min(image.height() - y),
+            );
+            let path = dir.join(format!("{col}_{row}.png"));
+            let file = File::create(&path).map_err(|e| TileError::io(&path, e))?;
+            // Thousands of tiles for a big map: favour speed over size.
+            tile.write_with_encoder(PngEncoder::new_with_quality(
+                BufWriter::new(file),
+                CompressionType::Fast,
+                PngFilter::Adaptive,
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+// Removes all but the `MAX_CACHED` most recently loaded pyramids (and never
+// `keep`, or one being built). Best effort: a pyramid that can't be removed
+// just stays.
+fn prune(cache: &Path, keep: &str) {
+    // Held throughout, so no build starts in a directory about to go.
+    let ids = building();
+    let Ok(entries) = fs::read_dir(cache) else {
+        return;
+    };
+    let mut pyramids: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name() != keep)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_none_or(|name| !ids.contains(name))
+        })
+        .map(|entry| {
+            let used = fs::metadata(entry.path().join(MANIFEST))
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (used, entry.path())
+        })
+        .collect();
+    pyramids.sort_by_key(|(used, _)| std::cmp::Reverse(*used));
+    for (_, path) in pyramids.into_iter().skip(MAX_CACHED - 1) {
+        let _ = fs::remove_dir_all(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    #[test]
+    fn builds_and_reuses_the_pyramid() {
+        let dir = scratch_dir("pyramid");
+        let source = dir.join("map.png");
+        image::RgbImage::from_fn(600, 300, |x, y| image::Rgb([x as u8, y as u8, 0]))
+            .save(&source)
+            .unwrap();
+        let cache = dir.join("tiles");
+
+        let tiles = load(&source, &cache).unwrap();
+        // 600x300, 300x150, then 150x75 fits in one tile.
+        assert_eq!((tiles.width, tiles.height, tiles.levels), (600, 300, 3));
+        let level = |n: u32| {
+            let mut names: Vec<_> = fs::read_dir(cache.join(&tiles.id).join(n.to_string()))
+                .unwrap()
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            level(0),
+            ["0_0.png", "0_1.png", "1_0.png", "1_1.png", "2_0.png", "2_1.png"]
+        );
+        assert_eq!(level(1), ["0_0.png", "1_0.png"]);
+        assert_eq!(level(2), ["0_0.png"]);
+        // The right-hand column is what's left of the image.
+        let edge = image::open(cache.join(&tiles.id).join("0").join("2_1.png")).unwrap();
+        assert_eq!((edge.width(), edge.height()), (88, 44));
+
+        // The same file again comes from the cache.
+        fs::remove_file(cache.join(&tiles.id).join("2").join("0_0.png")).unwrap();
+        assert_eq!(load(&source, &cache).unwrap(), tiles);
+        assert!(!cache.join(&tiles.id).join("2").join("0_0.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_a_build_of_the_same_map_waits() {
+        let dir = scratch_dir("claims");
+        let cache = dir.join("tiles");
+        let sources: Vec<PathBuf> = (0..2u8)
+            .map(|i| {
+                let path = dir.join(format!("map{i}.png"));
+                image::RgbImage::from_pixel(300, 300, image::Rgb([i, 0, 0]))
+                    .save(&path)
+                    .unwrap();
+                path
+            })
+            .collect();
+        let id = |path: &Path| identity::fingerprint(path).unwrap().key();
+        // Stands in for a slow build of map 0, half written.
+        let claim = Building::claim(&id(&sources[0]));
+        fs::create_dir_all(cache.join(id(&sources[0])).join("0")).unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            for source in &sources {
+                let (tx, cache) = (tx.clone(), &cache);
+                scope.spawn(move || {
+                    tx.send((source.clone(), load(source, cache).unwrap()))
+                        .unwrap()
+                });
+            }
+            let timeout = std::time::Duration::from_secs(10);
+            let (first, _) = rx.recv_timeout(timeout).unwrap();
+            assert_eq!(first, sources[1]);
+            assert!(rx
+                .recv_timeout(std::time::Duration::from_millis(200))
+                .is_err());
+
+            drop(claim);
+            let (second, tiles) = rx.recv_timeout(timeout).unwrap();
+            assert_eq!((second, tiles.levels), (sources[0].clone(), 2));
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pruning_spares_pyramids_being_built() {
+        let dir = scratch_dir("prune");
+        for i in 0..MAX_CACHED + 2 {
+            fs::create_dir_all(dir.join(format!("old-{i}"))).unwrap();
+        }
+        let claim = Building::claim("old-0");
+        prune(&dir, "old-1");
+        let left: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(left.len(), MAX_CACHED + 1);
+        assert!(left.contains(&"old-0".into()) && left.contains(&"old-1".into()));
+        drop(claim);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn maps_request_paths_to_tiles() {
+        let cache = Path::new("/cache");
+        assert_eq!(
+            tile_path(cache, "/abc-12/3/4/5"),
+            Some(cache.join("abc-12").join("3").join("4_5.png"))
+        );
+        assert_eq!(tile_path(cache, "/../0/0/0"), None);
+        assert_eq!(tile_path(cache, "/abc/0/0"), None);
+        assert_eq!(tile_path(cache, "/abc/0/0/0/0"), None);
+        assert_eq!(tile_path(cache, "/abc/-1/0/0"), None);
+    }
+}
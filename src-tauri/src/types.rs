@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+
+use crate::dice::DiceRoll;
+use crate::fog::Fog;
+
+// Serde mirrors of the persisted shapes in `src/types.ts`. Field names are
+// camelCase on the wire so the JSON round-trips unchanged between the webview
+// and the save files on disk.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DrawingPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Drawing {
+    pub id: String,
+    pub points: Vec<DrawingPoint>,
+    pub color: String,
+    pub stroke_width: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlockState {
+    // "row,col" -> color
+    pub cells: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewState {
+    pub scale: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerViewOffset {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationState {
+    pub pixels_per_inch: f64,
+    pub saved_pixels_per_inch: f64,
+}
+
+// A wall segment in map pixels. Doors block sight only while closed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Wall {
+    pub id: String,
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+    #[serde(default)]
+    pub door: bool,
+    #[serde(default)]
+    pub open: bool,
+}
+
+// A point the party sees from (a torch, the party marker).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LightSource {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    // Sight radius in map pixels; `None` = unlimited (until a wall).
+    #[serde(default)]
+    pub radius: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WallState {
+    pub walls: Vec<Wall>,
+    pub lights: Vec<LightSource>,
+    // Light sources baked into an imported map (torches, braziers). Kept for
+    // reference; unlike `lights` they don't reveal fog.
+    #[serde(default)]
+    pub map_lights: Vec<LightSource>,
+}
+
+// One move of the party marker on a hex map. `from` is `None` when the
+// marker was placed rather than moved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TravelLogEntry {
+    pub from: Option<(i64, i64)>,
+    pub to: (i64, i64),
+    pub day: u32,
+    #[serde(default)]
+    pub note: String,
+}
+
+// An encounter rolled for the party's hex (see `encounters.rs`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncounterLogEntry {
+    pub hex: (i64, i64),
+    pub day: u32,
+    pub summary: String,
+}
+
+// Where the party is on a `.hexm` map (1-based col, row), the in-game day
+// and every move and encounter so far.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartyState {
+    pub position: Option<(i64, i64)>,
+    pub day: u32,
+    pub log: Vec<TravelLogEntry>,
+    #[serde(default)]
+    pub encounters: Vec<EncounterLogEntry>,
+}
+
+impl Default for PartyState {
+    fn default() -> Self {
+        PartyState {
+            position: None,
+            day: 1,
+            log: Vec::new(),
+            encounters: Vec::new(),
+        }
+    }
+}
+
+// A creature token. `x`/`y` is its centre in map pixels, snapped to the grid
+// (or a hex centre) when placed; `size` is in grid squares.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Token {
+    pub id: String,
+    pub name: String,
+    // Small data: URL; `None` draws a coloured disc with the initial.
+    #[serde(default)]
+    pub image: Option<String>,
+    pub color: String,
+    pub size: f64,
+    // Player who controls it; empty for the DM's creatures.
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub hidden: bool,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// How many rolls the tray's history keeps; older ones drop off the front.
+/// Mirrors `ROLL_HISTORY_LIMIT` in `src/dice.ts`.
+pub const ROLL_HISTORY_LIMIT: usize = 200;
+
+// A roll from the dice tray, by the DM or a LAN player. Secret rolls stay in
+// the DM window (`projection.rs`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollRecord {
+    pub id: String,
+    pub by: String,
+    #[serde(default)]
+    pub secret: bool,
+    pub at: u64, // ms since epoch
+    #[serde(flatten)]
+    pub roll: DiceRoll,
+}
+
+/// Persisted state for one map, as written to `<app data>/maps/*.json`.
+/// Files written by older versions are upgraded to this shape by
+/// `migrate::migrate` before they are deserialized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedMapState {
+    // Stamped with `migrate::SCHEMA_VERSION` on save; the webview omits it.
+    #[serde(default)]
+    pub schema_version: u32,
+    pub file_path: String,
+    pub image_width: f64,
+    pub image_height: f64,
+    pub grid_size: f64,
+    pub grid_visible: bool,
+    pub grid_color: String,
+    pub grid_opacity: f64,
+    pub grid_offset_x: f64,
+    pub grid_offset_y: f64,
+    pub fog: Fog, // packed; see `fog.rs`
+    pub drawings: Vec<Drawing>,
+    pub blocks: BlockState,
+    // Added after v2; older saves have no walls.
+    #[serde(default)]
+    pub walls: WallState,
+    // Added after v2; only hex maps use it.
+    #[serde(default)]
+    pub party: PartyState,
+    // Added after v2.
+    #[serde(default)]
+    pub tokens: Vec<Token>,
+    pub view: ViewState,
+    pub player_view_offset: PlayerViewOffset,
+    pub calibration: CalibrationState,
+    pub saved_at: String,
+}
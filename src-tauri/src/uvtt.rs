@@ -0,0 +1,276 @@
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{LightSource, Wall, WallState};
+
+// Universal VTT import (`.dd2vtt` from Dungeondraft, `.uvtt` from others):
+// one JSON file holding the map image as base64 plus its grid, walls, doors
+// ("portals") and lights. Everything in the file is in grid units relative to
+// `map_origin`; it's converted here to the map pixels the rest of the app
+// uses, so the grid needs no calibration.
+
+#[derive(Debug, thiserror::Error)]
+pub enum UvttError {
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("not a Universal VTT file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("embedded map image is not valid base64: {0}")]
+    Image(#[from] base64::DecodeError),
+    #[error("file has no embedded map image")]
+    MissingImage,
+    #[error("invalid pixels_per_grid: {0}")]
+    InvalidGrid(f64),
+}
+
+/// A parsed UVTT map, ready for the map loader.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UvttMap {
+    // `data:` URL of the embedded image (decoded once to check it and find
+    // its type).
+    pub image: String,
+    pub grid_size: f64,
+    pub width: f64,
+    pub height: f64,
+    pub walls: WallState,
+}
+
+#[derive(Deserialize)]
+struct UvttFile {
+    resolution: Resolution,
+    #[serde(default)]
+    line_of_sight: Vec<Vec<Point>>,
+    // Format 0.3+: walls around objects (pillars, furniture), kept separate
+    // from the architectural walls.
+    #[serde(default)]
+    objects_line_of_sight: Vec<Vec<Point>>,
+    #[serde(default)]
+    portals: Vec<Portal>,
+    #[serde(default)]
+    lights: Vec<Light>,
+    #[serde(default)]
+    image: String,
+}
+
+#[derive(Deserialize)]
+struct Resolution {
+    #[serde(default)]
+    map_origin: Point,
+    map_size: Point,
+    pixels_per_grid: f64,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Deserialize)]
+struct Portal {
+    bounds: Vec<Point>,
+    #[serde(default = "default_closed")]
+    closed: bool,
+}
+
+fn default_closed() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct Light {
+    position: Point,
+    #[serde(default)]
+    range: Option<f64>,
+}
+
+pub fn load(path: &Path) -> Result<UvttMap, UvttError> {
+    let bytes = std::fs::read(path).map_err(|source| UvttError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse(&bytes)
+}
+
+pub fn parse(bytes: &[u8]) -> Result<UvttMap, UvttError> {
+    let file: UvttFile = serde_json::from_slice(bytes)?;
+    let Resolution {
+        map_origin: origin,
+        map_size,
+        pixels_per_grid: ppg,
+    } = file.resolution;
+    if !(ppg.is_finite() && ppg > 0.0) {
+        return Err(UvttError::InvalidGrid(ppg));
+    }
+
+    // Some exporters wrap the image in a data URL, most don't.
+    let encoded = file
+        .image
+        .rsplit_once(',')
+        .map_or(file.image.as_str(), |(_, data)| data)
+        .trim();
+    if encoded.is_empty() {
+        return Err(UvttError::MissingImage);
+    }
+    let image = BASE64.decode(encoded)?;
+    let image = format!("data:{};base64,{encoded}", sniff_mime(&image));
+
+    let to_px = |p: Point| ((p.x - origin.x) * ppg, (p.y - origin.y) * ppg);
+    let mut walls = Vec::new();
+    let mut wall = |a: Point, b: Point, door: bool, open: bool| {
+        let ((x1, y1), (x2, y2)) = (to_px(a), to_px(b));
+        let id = format!("uvtt-{}", walls.len());
+        walls.push(Wall {
+            id,
+            x1,
+            y1,
+            x2,
+            y2,
+            door,
+            open,
+        });
+    };
+    for line in file.line_of_sight.iter().chain(&file.objects_line_of_sight) {
+        for pair in line.windows(2) {
+            wall(pair[0], pair[1], false, false);
+        }
+    }
+    for portal in &file.portals {
+        if let [a, b, ..] = portal.bounds[..] {
+            wall(a, b, true, !portal.closed);
+        }
+    }
+
+    let map_lights = file
+        .lights
+        .iter()
+        .enumerate()
+        .map(|(i, light)| {
+            let (x, y) = to_px(light.position);
+            let radius = light.range.filter(|r| *r > 0.0).map(|r| r * ppg);
+            LightSource {
+                id: format!("uvtt-light-{i}"),
+                x,
+                y,
+                radius,
+            }
+        })
+        .collect();
+
+    Ok(UvttMap {
+        image,
+        grid_size: ppg,
+        width: map_size.x * ppg,
+        height: map_size.y * ppg,
+        walls: WallState {
+            walls,
+            lights: Vec::new(),
+            map_lights,
+        },
+    })
+}
+
+fn sniff_mime(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "image/png", // Dungeondraft's default export
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const PNG: &str = "iVBORw0KGgo="; // just the PNG signature
+
+    // A 4x3 grid map at 50 px per grid unit, its origin one unit in.
+    fn file() -> Value {
+        json!({
+            "format": 0.3,
+            "resolution": { "map_origin": { "x": 1, "y": 1 }, "map_size": { "x": 4, "y": 3 }, "pixels_per_grid": 50 },
+            "line_of_sight": [[{ "x": 1, "y": 1 }, { "x": 3, "y": 1 }, { "x": 3, "y": 2 }]],
+            "objects_line_of_sight": [[{ "x": 2, "y": 2 }, { "x": 2.5, "y": 2 }]],
+            "portals": [
+                { "bounds": [{ "x": 3, "y": 2 }, { "x": 3, "y": 3 }] },
+                { "bounds": [{ "x": 1, "y": 2 }, { "x": 1, "y": 3 }], "closed": false },
+            ],
+            "lights": [{ "position": { "x": 2, "y": 3 }, "range": 2 }, { "position": { "x": 4, "y": 4 }, "range": 0 }],
+            "image": PNG,
+        })
+    }
+
+    fn parse_json(file: &Value) -> Result<UvttMap, UvttError> {
+        parse(&serde_json::to_vec(file).unwrap())
+    }
+
+    #[test]
+    fn converts_grid_units_to_map_pixels() {
+        let map = parse_json(&file()).unwrap();
+        assert_eq!((map.grid_size, map.width, map.height), (50.0, 200.0, 150.0));
+        assert_eq!(map.image, format!("data:image/png;base64,{PNG}"));
+
+        let walls: Vec<_> = map
+            .walls
+            .walls
+            .iter()
+            .map(|w| (w.x1, w.y1, w.x2, w.y2, w.door, w.open))
+            .collect();
+        assert_eq!(
+            walls,
+            [
+                (0.0, 0.0, 100.0, 0.0, false, false),
+                (100.0, 0.0, 100.0, 50.0, false, false),
+                (50.0, 50.0, 75.0, 50.0, false, false),
+                // Portals are doors, closed unless they say otherwise.
+                (100.0, 50.0, 100.0, 100.0, true, false),
+                (0.0, 50.0, 0.0, 100.0, true, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_lights_for_reference_only() {
+        let walls = parse_json(&file()).unwrap().walls;
+        assert!(walls.lights.is_empty());
+        let lights: Vec<_> = walls
+            .map_lights
+            .iter()
+            .map(|l| (l.x, l.y, l.radius))
+            .collect();
+        assert_eq!(lights, [(50.0, 100.0, Some(100.0)), (150.0, 150.0, None)]);
+    }
+
+    #[test]
+    fn reads_data_urls_and_sniffs_the_image_type() {
+        let mut file = file();
+        file["image"] = json!("data:application/octet-stream;base64,/9j/4AAQ");
+        assert_eq!(
+            parse_json(&file).unwrap().image,
+            "data:image/jpeg;base64,/9j/4AAQ"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let mut file = file();
+        file["image"] = json!("not base64!");
+        assert!(matches!(parse_json(&file), Err(UvttError::Image(_))));
+        file.as_object_mut().unwrap().remove("image");
+        assert!(matches!(parse_json(&file), Err(UvttError::MissingImage)));
+        file["image"] = json!(PNG);
+        file["resolution"]["pixels_per_grid"] = json!(0);
+        assert!(matches!(parse_json(&file), Err(UvttError::InvalidGrid(_))));
+        assert!(matches!(parse(b"<svg/>"), Err(UvttError::Json(_))));
+    }
+}
@@ -0,0 +1,231 @@
+use std::f64::consts::TAU;
+
+use crate::fog::Fog;
+use crate::types::{DrawingPoint, LightSource, WallState};
+
+// Line of sight over the DM's walls: the area visible from a light source is
+// found by casting rays at every wall endpoint (and just either side of it,
+// to slip past corners), keeping the nearest hit of each, and joining the
+// hits in angle order. Revealing fog then means clearing every grid cell
+// whose centre falls inside that polygon.
+
+// Angle offset for the rays either side of each wall endpoint.
+const CORNER_EPSILON: f64 = 1e-4;
+// Extra evenly spaced rays so a limited sight radius comes out round.
+const RADIUS_RAYS: usize = 72;
+
+type Segment = ((f64, f64), (f64, f64));
+
+/// Where the fog grid sits on the map image.
+#[derive(Debug, Clone, Copy)]
+pub struct GridGeometry {
+    pub size: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+/// The polygon (map pixels, in angle order) visible from `light` within a
+/// `width` x `height` map. Closed doors block sight; open ones don't.
+pub fn visibility_polygon(
+    light: &LightSource,
+    walls: &WallState,
+    width: f64,
+    height: f64,
+) -> Vec<DrawingPoint> {
+    let origin = (light.x, light.y);
+    let mut segments: Vec<Segment> = walls
+        .walls
+        .iter()
+        .filter(|wall| !(wall.door && wall.open))
+        .map(|wall| ((wall.x1, wall.y1), (wall.x2, wall.y2)))
+        .collect();
+    // The map edge stops every ray.
+    let corners = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)];
+    for i in 0..4 {
+        segments.push((corners[i], corners[(i + 1) % 4]));
+    }
+
+    let mut angles: Vec<f64> = Vec::with_capacity(segments.len() * 6);
+    for &(a, b) in &segments {
+        for (x, y) in [a, b] {
+            let angle = (y - origin.1).atan2(x - origin.0);
+            angles.extend([angle - CORNER_EPSILON, angle, angle + CORNER_EPSILON]);
+        }
+    }
+    let radius = light.radius.filter(|r| *r > 0.0);
+    if radius.is_some() {
+        angles.extend((0..RADIUS_RAYS).map(|i| i as f64 * TAU / RADIUS_RAYS as f64 - TAU / 2.0));
+    }
+    angles.sort_by(f64::total_cmp);
+    angles.dedup_by(|a, b| (*a - *b).abs() < CORNER_EPSILON / 10.0);
+
+    let mut polygon: Vec<DrawingPoint> = Vec::with_capacity(angles.len());
+    for angle in angles {
+        let dir = (angle.cos(), angle.sin());
+        let nearest = segments
+            .iter()
+            .filter_map(|segment| ray_hit(origin, dir, *segment))
+            .fold(f64::INFINITY, f64::min);
+        let distance = radius.map_or(nearest, |r| nearest.min(r));
+        if !distance.is_finite() {
+            continue; // light outside the map, looking away from it
+        }
+        let point = DrawingPoint {
+            x: origin.0 + dir.0 * distance,
+            y: origin.1 + dir.1 * distance,
+        };
+        if polygon
+            .last()
+            .is_none_or(|last| (last.x - point.x).abs() > 1e-6 || (last.y - point.y).abs() > 1e-6)
+        {
+            polygon.push(point);
+        }
+    }
+    polygon
+}
+
+// Distance along the ray from `origin` in direction `dir` (unit length) to
+// where it crosses `segment`, if it does.
+fn ray_hit(origin: (f64, f64), dir: (f64, f64), ((ax, ay), (bx, by)): Segment) -> Option<f64> {
+    let seg = (bx - ax, by - ay);
+    let denom = cross(dir, seg);
+    if denom.abs() < 1e-12 {
+        return None; // parallel
+    }
+    let to_start = (ax - origin.0, ay - origin.1);
+    let t = cross(to_start, seg) / denom;
+    let u = cross(to_start, dir) / denom;
+    (t >= 0.0 && (-1e-9..=1.0 + 1e-9).contains(&u)).then_some(t)
+}
+
+fn cross(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+/// Reveal every cell visible from any of the light sources. Fog is only ever
+/// cleared, never re-added: what the party has seen stays explored.
+pub fn reveal_line_of_sight(
+    fog: &mut Fog,
+    walls: &WallState,
+    grid: GridGeometry,
+    width: f64,
+    height: f64,
+) {
+    if grid.size <= 0.0 {
+        return;
+    }
+    for light in &walls.lights {
+        let polygon: Vec<(f64, f64)> = visibility_polygon(light, walls, width, height)
+            .iter()
+            .map(|p| {
+                (
+                    (p.x - grid.offset_x) / grid.size,
+                    (p.y - grid.offset_y) / grid.size,
+                )
+            })
+            .collect();
+        fog.polygon(&polygon, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Wall;
+
+    // A 100x100 px map under a 10x10 fog grid.
+    const GRID: GridGeometry = GridGeometry {
+        size: 10.0,
+        offset_x: 0.0,
+        offset_y: 0.0,
+    };
+
+    fn light(x: f64, y: f64, radius: Option<f64>) -> LightSource {
+        LightSource {
+            id: "torch".into(),
+            x,
+            y,
+            radius,
+        }
+    }
+
+    // A wall straight down the middle of the map.
+    fn middle_wall(door: bool, open: bool) -> Wall {
+        Wall {
+            id: "w".into(),
+            x1: 50.0,
+            y1: 0.0,
+            x2: 50.0,
+            y2: 100.0,
+            door,
+            open,
+        }
+    }
+
+    fn revealed(walls: WallState) -> Vec<Vec<bool>> {
+        let mut fog = Fog::new(10, 10, true).unwrap();
+        reveal_line_of_sight(&mut fog, &walls, GRID, 100.0, 100.0);
+        fog.to_cells()
+            .iter()
+            .map(|row| row.iter().map(|fogged| !fogged).collect())
+            .collect()
+    }
+
+    #[test]
+    fn closed_walls_and_doors_block_sight() {
+        for wall in [middle_wall(false, false), middle_wall(true, false)] {
+            let lights = vec![light(15.0, 50.0, None)];
+            let cells = revealed(WallState {
+                walls: vec![wall],
+                lights,
+                ..Default::default()
+            });
+            for row in cells {
+                assert_eq!(
+                    row,
+                    [true, true, true, true, true, false, false, false, false, false]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn open_doors_do_not() {
+        let lights = vec![light(15.0, 50.0, None)];
+        let walls = WallState {
+            walls: vec![middle_wall(true, true)],
+            lights,
+            ..Default::default()
+        };
+        assert!(revealed(walls).iter().flatten().all(|&seen| seen));
+    }
+
+    #[test]
+    fn sight_stops_at_the_radius_or_the_map_edge() {
+        let cells = revealed(WallState {
+            lights: vec![light(50.0, 50.0, Some(25.0))],
+            ..Default::default()
+        });
+        // Centres 15.8 px and 25.5 px from the light.
+        assert!(cells[5][6] && !cells[5][7]);
+        assert!(cells[4][4] && !cells[0][0]);
+        assert_eq!(cells.iter().flatten().filter(|&&seen| seen).count(), 16);
+
+        let polygon = visibility_polygon(
+            &light(50.0, 50.0, None),
+            &WallState::default(),
+            100.0,
+            100.0,
+        );
+        for corner in [(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0)] {
+            assert!(polygon
+                .iter()
+                .any(|p| (p.x - corner.0).abs() < 1e-6 && (p.y - corner.1).abs() < 1e-6));
+        }
+        let cells = revealed(WallState {
+            lights: vec![light(50.0, 50.0, None)],
+            ..Default::default()
+        });
+        assert!(cells.iter().flatten().all(|&seen| seen));
+    }
+}
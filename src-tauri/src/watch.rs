@@ -0,0 +1,132 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Watches the loaded map file so edits made in other tools (a `.hexm` in a
+// text editor, a re-exported cartographer bundle) show up without re-running
+// Load Map. The parent directory is watched rather than the file itself:
+// most editors save by writing a temp file and renaming it over the
+// original, which a watch on the old inode would miss.
+
+pub const MAP_CHANGED_EVENT: &str = "vtt-map-changed";
+
+// Saves arrive as bursts of events (truncate, write, rename, metadata); wait
+// for this much quiet before reporting one change.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Calls `on_change` (on a background thread) after each burst of changes to
+/// `path`. Stops when dropped.
+pub struct MapWatcher {
+    path: PathBuf,
+    _watcher: RecommendedWatcher,
+}
+
+impl MapWatcher {
+    pub fn new(path: &Path, on_change: impl Fn(&Path) + Send + 'static) -> notify::Result<Self> {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let name: OsString = path.file_name().map(Into::into).unwrap_or_default();
+
+        let (tx, rx) = mpsc::channel::<()>();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else { return };
+                let relevant = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+                if relevant
+                    && event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name() == Some(name.as_os_str()))
+                {
+                    let _ = tx.send(());
+                }
+            })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        // Ends when the watcher (and with it the sender) is dropped.
+        let target = path.to_path_buf();
+        std::thread::spawn(move || {
+            while rx.recv().is_ok() {
+                loop {
+                    match rx.recv_timeout(DEBOUNCE) {
+                        Ok(()) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                // Mid-rename the file can briefly not exist; the rename
+                // itself is another event.
+                if target.exists() {
+                    on_change(&target);
+                }
+            }
+        });
+
+        Ok(MapWatcher {
+            path: path.to_path_buf(),
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    // A watcher on `path` and the channel its changes arrive on.
+    fn watch(path: &Path) -> (MapWatcher, mpsc::Receiver<PathBuf>) {
+        let (tx, rx) = mpsc::channel();
+        let watcher =
+            MapWatcher::new(path, move |changed| tx.send(changed.to_path_buf()).unwrap()).unwrap();
+        (watcher, rx)
+    }
+
+    #[test]
+    fn reports_a_burst_of_writes_once() {
+        let dir = scratch_dir("burst");
+        let map = dir.join("map.hexm");
+        std::fs::write(&map, "v1").unwrap();
+        let (_watcher, rx) = watch(&map);
+
+        for version in 2..6 {
+            std::fs::write(&map, format!("v{version}")).unwrap();
+            std::thread::sleep(DEBOUNCE / 5);
+        }
+        assert_eq!(rx.recv_timeout(DEBOUNCE * 10).unwrap(), map);
+        assert!(rx.recv_timeout(DEBOUNCE * 3).is_err());
+
+        // Saved the editor way: a temp file renamed over the map.
+        let temp = dir.join("map.hexm.tmp");
+        std::fs::write(&temp, "v6").unwrap();
+        std::fs::rename(&temp, &map).unwrap();
+        assert_eq!(rx.recv_timeout(DEBOUNCE * 10).unwrap(), map);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ignores_other_files_and_stops_when_dropped() {
+        let dir = scratch_dir("filter");
+        let map = dir.join("map.hexm");
+        std::fs::write(&map, "v1").unwrap();
+        let (watcher, rx) = watch(&map);
+
+        std::fs::write(dir.join("other.hexm"), "other").unwrap();
+        std::fs::write(dir.join("map.hexm.bak"), "backup").unwrap();
+        assert!(rx.recv_timeout(DEBOUNCE * 3).is_err());
+
+        drop(watcher);
+        std::fs::write(&map, "v2").unwrap();
+        assert!(rx.recv_timeout(DEBOUNCE * 3).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,13 +2,15 @@ import { useState, useEffect } from 'react';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { DMView } from './views/DMView';
 import { PlayerView } from './views/PlayerView';
+import { isRemotePlayer } from './store';
 import './App.css';
 
 function App() {
   const [windowLabel, setWindowLabel] = useState<string | null>(null);
 
   useEffect(() => {
-    setWindowLabel(getCurrentWindow().label);
+    // Browsers on the LAN have no Tauri window; they're always players.
+    setWindowLabel(isRemotePlayer ? 'player' : getCurrentWindow().label);
   }, []);
 
   if (windowLabel === null) {
@@ -1,4 +1,5 @@
 import { readFile } from '@tauri-apps/plugin-fs';
+import { WallState } from './types';
 
 // A cartographer "both views" bundle: a thin JSON wrapper holding the GM and
 // player SVG renders of one map, plus the grid metadata. Produced by
@@ -9,6 +10,7 @@ export interface CartographerBundle {
   grid: { cell_size: number; units?: string; ft_per_cell?: number };
   gm: string; // GM-facing SVG document
   player: string; // player-facing SVG document
+  walls?: WallState; // not cartographer's: added by `vtt convert` from a UVTT map
 }
 
 function isBundle(value: unknown): value is CartographerBundle {
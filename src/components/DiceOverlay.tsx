@@ -0,0 +1,51 @@
+import { useEffect, useState } from 'react';
+import { RollRecord } from '../types';
+
+interface DiceOverlayProps {
+  rolls: RollRecord[];
+}
+
+const SHOW_MS = 6000;
+
+// The newest public roll, tumbling in over the player view and fading out.
+// Only fresh rolls are shown, so opening the view (or a resync) doesn't
+// replay the last one.
+export function DiceOverlay({ rolls }: DiceOverlayProps) {
+  const latest = rolls.length > 0 ? rolls[rolls.length - 1] : null;
+  const [shown, setShown] = useState<RollRecord | null>(null);
+
+  const latestId = latest?.id ?? null;
+  useEffect(() => {
+    // Keyed on the id: state updates hand over new objects for old rolls.
+    if (latest && Date.now() - latest.at < SHOW_MS) setShown(latest);
+  }, [latestId]);
+
+  useEffect(() => {
+    if (!shown) return;
+    const timer = window.setTimeout(() => setShown(null), SHOW_MS);
+    return () => window.clearTimeout(timer);
+  }, [shown]);
+
+  if (!shown) return null;
+
+  return (
+    <div key={shown.id} className="dice-overlay">
+      <div className="dice-overlay-by">{shown.by} rolls {shown.expression}</div>
+      <div className="dice-overlay-dice">
+        {shown.terms.flatMap((term, t) =>
+          term.dice.map((die, d) => (
+            <span
+              key={`${t}-${d}`}
+              className={`dice-overlay-die${die.kept ? '' : ' dropped'}${die.exploded ? ' exploded' : ''}`}
+              style={{ animationDelay: `${(t * 4 + d) * 60}ms` }}
+            >
+              {die.value}
+            </span>
+          )),
+        )}
+      </div>
+      <div className="dice-overlay-total">{shown.total}</div>
+      <div className="dice-overlay-breakdown">{shown.breakdown}</div>
+    </div>
+  );
+}
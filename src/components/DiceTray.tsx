@@ -0,0 +1,68 @@
+import { useState } from 'react';
+import { RollRecord } from '../types';
+
+interface DiceTrayProps {
+  rolls: RollRecord[];
+  onRoll: (expression: string, secret: boolean) => void;
+  onNewSession: () => void;
+}
+
+const QUICK_DICE = ['d4', 'd6', 'd8', 'd10', 'd12', 'd20', 'd20adv', 'd%'];
+const SHOWN_ROLLS = 30;
+
+export function DiceTray({ rolls, onRoll, onNewSession }: DiceTrayProps) {
+  const [expression, setExpression] = useState('1d20');
+  const [secret, setSecret] = useState(false);
+
+  const submit = () => {
+    if (expression.trim()) onRoll(expression.trim(), secret);
+  };
+
+  return (
+    <div className="settings-panel">
+      <h3>Dice</h3>
+      <p className="settings-help">
+        Public rolls pop up on the player view; secret rolls stay here. LAN players can roll from their browser.
+      </p>
+
+      <div className="setting-row">
+        <input
+          type="text"
+          className="dice-input"
+          value={expression}
+          onChange={(e) => setExpression(e.target.value)}
+          onKeyDown={(e) => e.key === 'Enter' && submit()}
+          placeholder="2d6+3"
+        />
+        <button onClick={submit}>Roll</button>
+      </div>
+      <div className="setting-row">
+        <label>
+          <input type="checkbox" checked={secret} onChange={(e) => setSecret(e.target.checked)} />
+          Secret (GM only)
+        </label>
+      </div>
+      <div className="dice-quick">
+        {QUICK_DICE.map((die) => (
+          <button key={die} onClick={() => onRoll(die, secret)}>{die}</button>
+        ))}
+      </div>
+
+      {rolls.length > 0 && (
+        <div className="dice-history">
+          {rolls.slice(-SHOWN_ROLLS).reverse().map((roll) => (
+            <div key={roll.id} className={roll.secret ? 'dice-history-entry secret' : 'dice-history-entry'}>
+              <span className="dice-history-total">{roll.total}</span>
+              <span className="dice-history-by">{roll.by}{roll.secret ? ' (secret)' : ''}</span>
+              <span className="dice-history-breakdown">{roll.breakdown}</span>
+            </div>
+          ))}
+        </div>
+      )}
+
+      <div className="setting-row">
+        <button onClick={onNewSession} disabled={rolls.length === 0} title="Clear the roll history">New session</button>
+      </div>
+    </div>
+  );
+}
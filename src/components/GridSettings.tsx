@@ -6,6 +6,9 @@ interface GridSettingsProps {
   onGridVisibleChange: (visible: boolean) => void;
   onGridColorChange: (color: string) => void;
   onGridOpacityChange: (opacity: number) => void;
+  onDetectGrid?: () => void; // Only for image maps
+  detecting?: boolean;
+  detectStatus?: string | null; // What the last detection found
 }
 
 export function GridSettings({
@@ -14,6 +17,9 @@ export function GridSettings({
   onGridVisibleChange,
   onGridColorChange,
   onGridOpacityChange,
+  onDetectGrid,
+  detecting = false,
+  detectStatus,
 }: GridSettingsProps) {
   return (
     <div className="settings-panel">
@@ -41,6 +47,15 @@ export function GridSettings({
         />
       </div>
 
+      {onDetectGrid && (
+        <div className="setting-row">
+          <button onClick={onDetectGrid} disabled={detecting}>
+            {detecting ? 'Detecting…' : 'Detect Grid'}
+          </button>
+          {detectStatus && <span className="settings-help">{detectStatus}</span>}
+        </div>
+      )}
+
       <div className="setting-row">
         <label>Grid Color:</label>
         <input
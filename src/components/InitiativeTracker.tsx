@@ -1,14 +1,75 @@
-import { useState } from 'react';
-import { InitiativeState } from '../types';
-import { sortedByInitiative, isValidDiceSpec, DEFAULT_DICE } from '../initiative';
+import { useEffect, useState } from 'react';
+import { InitiativeEntity, InitiativeState, Token } from '../types';
+import { sortedByInitiative, formatHp, DEFAULT_DICE } from '../initiative';
+import { checkDice } from '../dice';
 
 interface InitiativeTrackerProps {
   initiative: InitiativeState;
   editable: boolean; // DM window can add/remove/reroll; player window is read-only
-  onAdd?: (name: string, count: number, dice: string, isPc: boolean) => void;
+  tokens?: Token[]; // Tokens on the map, for linking entries (DM only)
+  onAdd?: (name: string, count: number, dice: string, isPc: boolean, dexMod: number) => void;
   onRemove?: (id: string) => void;
   onReroll?: () => void;
   onClear?: () => void;
+  onNextTurn?: () => void;
+  onPreviousTurn?: () => void;
+  onEndCombat?: () => void;
+  onHpChange?: (id: string, input: string) => void; // "12", "-7", "+5" or "12/30"
+  onAddCondition?: (id: string, input: string) => void; // "Prone" or "Stunned 2"
+  onRemoveCondition?: (id: string, index: number) => void;
+  onLinkToken?: (id: string, tokenId: string | null) => void;
+  onSelect?: (id: string) => void; // Name clicked: show the linked token
+}
+
+// HP typed into a row; applied on Enter or when the field loses focus.
+function HpInput({ entity, onChange }: { entity: InitiativeEntity; onChange: (input: string) => void }) {
+  const current = formatHp(entity);
+  const [text, setText] = useState(current);
+  useEffect(() => setText(current), [current]);
+
+  const commit = () => {
+    if (text !== current) onChange(text);
+  };
+
+  return (
+    <input
+      type="text"
+      className="initiative-hp-input"
+      value={text}
+      placeholder="HP"
+      title='HP: "12", "-7" for damage, "+5" for healing, "12/30" with max'
+      onChange={(e) => setText(e.target.value)}
+      onKeyDown={(e) => { if (e.key === 'Enter') commit(); }}
+      onBlur={commit}
+    />
+  );
+}
+
+// A condition typed under a row; added on Enter, dropped on Escape or when
+// the field loses focus.
+function ConditionInput({ onAdd, onClose }: { onAdd: (input: string) => void; onClose: () => void }) {
+  const [text, setText] = useState('');
+
+  return (
+    <input
+      type="text"
+      className="initiative-condition-input"
+      value={text}
+      placeholder="Condition"
+      title='Add a number of rounds to make it expire, e.g. "Stunned 2"'
+      autoFocus
+      onChange={(e) => setText(e.target.value)}
+      onKeyDown={(e) => {
+        if (e.key === 'Enter' && text.trim()) {
+          onAdd(text);
+          onClose();
+        } else if (e.key === 'Escape') {
+          onClose();
+        }
+      }}
+      onBlur={onClose}
+    />
+  );
 }
 
 export function InitiativeTracker({
@@ -18,29 +79,53 @@ export function InitiativeTracker({
   onRemove,
   onReroll,
   onClear,
+  onNextTurn,
+  onPreviousTurn,
+  onEndCombat,
+  onHpChange,
+  onAddCondition,
+  onRemoveCondition,
+  onLinkToken,
+  onSelect,
+  tokens = [],
 }: InitiativeTrackerProps) {
   const [name, setName] = useState('');
   const [count, setCount] = useState(1);
   const [dice, setDice] = useState(DEFAULT_DICE);
+  const [dexMod, setDexMod] = useState('0');
   const [isPc, setIsPc] = useState(false);
+  const [diceValid, setDiceValid] = useState(true);
+  const [addingCondition, setAddingCondition] = useState<string | null>(null); // entity id
+
+  // The Rust dice engine is the one source of truth for what parses.
+  useEffect(() => {
+    if (!editable) return;
+    let cancelled = false;
+    checkDice(dice).then(
+      () => { if (!cancelled) setDiceValid(true); },
+      () => { if (!cancelled) setDiceValid(false); },
+    );
+    return () => { cancelled = true; };
+  }, [dice, editable]);
 
   if (!initiative.visible) return null;
 
   const ordered = sortedByInitiative(initiative.entities);
-  const diceValid = isValidDiceSpec(dice);
+  const inCombat = initiative.turn !== null;
 
   const submitAdd = () => {
     if (!name.trim() || !diceValid || !onAdd) return;
-    onAdd(name, count, dice, isPc);
+    onAdd(name, count, dice, isPc, parseInt(dexMod, 10) || 0);
     setName('');
     setCount(1);
-    // Keep the dice spec and PC flag so adding several groups in a row is quick.
+    // Keep the dice spec, Dex and PC flag so adding several groups in a row is quick.
   };
 
   return (
     <div className="initiative-tracker">
       <div className="initiative-header">
         <span className="initiative-title">Initiative</span>
+        {inCombat && <span className="initiative-round">Round {initiative.round}</span>}
         {editable && (
           <div className="initiative-header-actions">
             <button onClick={onReroll} title="Re-roll all initiative">Reroll</button>
@@ -48,16 +133,62 @@ export function InitiativeTracker({
           </div>
         )}
       </div>
+      {editable && (
+        <div className="initiative-turn-actions">
+          <button onClick={onPreviousTurn} disabled={!inCombat} title="Previous turn (Shift+N)">◀ Prev</button>
+          <button onClick={onNextTurn} disabled={ordered.every((e) => e.roll === null)} title="Next turn (N)">
+            {inCombat ? 'Next ▶' : 'Start combat'}
+          </button>
+          <button onClick={onEndCombat} disabled={!inCombat} title="End combat">End</button>
+        </div>
+      )}
 
       <ol className="initiative-list">
         {ordered.length === 0 && (
           <li className="initiative-empty">No entities yet.</li>
         )}
         {ordered.map((e) => (
-          <li key={e.id} className="initiative-row">
+          <li key={e.id} className={e.id === initiative.turn ? 'initiative-row active' : 'initiative-row'}>
             <span className="initiative-roll">{e.roll ?? '—'}</span>
-            <span className="initiative-name">{e.name}</span>
+            {editable && tokens.some((t) => t.id === e.tokenId) ? (
+              <span
+                className="initiative-name linked"
+                onClick={() => onSelect?.(e.id)}
+                title="Show on the map"
+              >
+                {e.name}
+              </span>
+            ) : (
+              <span className="initiative-name">{e.name}</span>
+            )}
             {editable && <span className="initiative-dice">{e.dice}</span>}
+            {editable ? (
+              <HpInput entity={e} onChange={(input) => onHpChange?.(e.id, input)} />
+            ) : (
+              e.hp != null && <span className="initiative-hp">{formatHp(e)}</span>
+            )}
+            {editable && tokens.length > 0 && (
+              <select
+                className="initiative-token-select"
+                value={tokens.some((t) => t.id === e.tokenId) ? e.tokenId ?? '' : ''}
+                onChange={(ev) => onLinkToken?.(e.id, ev.target.value || null)}
+                title="Token on the map"
+              >
+                <option value="">No token</option>
+                {tokens.map((t) => (
+                  <option key={t.id} value={t.id}>{t.name}</option>
+                ))}
+              </select>
+            )}
+            {editable && (
+              <button
+                className="initiative-condition-add"
+                onClick={() => setAddingCondition(e.id)}
+                title={`Add a condition to ${e.name}`}
+              >
+                +
+              </button>
+            )}
             {editable && (
               <button
                 className="initiative-remove"
@@ -67,6 +198,26 @@ export function InitiativeTracker({
                 ×
               </button>
             )}
+            {addingCondition === e.id && (
+              <ConditionInput
+                onAdd={(input) => onAddCondition?.(e.id, input)}
+                onClose={() => setAddingCondition(null)}
+              />
+            )}
+            {(e.conditions ?? []).length > 0 && (
+              <div className="initiative-conditions">
+                {(e.conditions ?? []).map((c, i) => (
+                  <span
+                    key={i}
+                    className={editable ? 'initiative-condition removable' : 'initiative-condition'}
+                    onClick={editable ? () => onRemoveCondition?.(e.id, i) : undefined}
+                    title={editable ? `Remove ${c.name}` : undefined}
+                  >
+                    {c.name}{c.rounds !== null ? ` (${c.rounds})` : ''}
+                  </span>
+                ))}
+              </div>
+            )}
           </li>
         ))}
       </ol>
@@ -99,6 +250,13 @@ export function InitiativeTracker({
               onKeyDown={(e) => { if (e.key === 'Enter') submitAdd(); }}
               title="Initiative dice, e.g. 1d20, 1d6, 1d20+3"
             />
+            <input
+              type="number"
+              className="initiative-dex-input"
+              value={dexMod}
+              onChange={(e) => setDexMod(e.target.value)}
+              title="Dexterity modifier (breaks initiative ties)"
+            />
             <button onClick={submitAdd} disabled={!name.trim() || !diceValid}>Add</button>
           </div>
           <label className="initiative-pc-label">
@@ -0,0 +1,34 @@
+import { LanInfo } from '../types';
+
+interface LanSettingsProps {
+  info: LanInfo | null; // null while the server is stopped
+  onStart: () => void;
+  onStop: () => void;
+}
+
+export function LanSettings({ info, onStart, onStop }: LanSettingsProps) {
+  return (
+    <div className="settings-panel">
+      <h3>LAN Players</h3>
+      <p className="settings-help">
+        Let phones and tablets on this network open the player view in a browser.
+      </p>
+
+      {info ? (
+        <>
+          <div className="lan-join-code" title="Join code">{info.joinCode}</div>
+          <div className="setting-info">
+            {info.urls.map((url) => <p key={url} className="lan-url">{url}</p>)}
+          </div>
+          <div className="setting-row">
+            <button onClick={onStop}>Stop server</button>
+          </div>
+        </>
+      ) : (
+        <div className="setting-row">
+          <button onClick={onStart}>Start server</button>
+        </div>
+      )}
+    </div>
+  );
+}
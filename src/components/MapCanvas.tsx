@@ -1,10 +1,182 @@
-import { useRef, useEffect, useState, useCallback, useMemo } from 'react';
-import { Stage, Layer, Image, Line, Rect, Group } from 'react-konva';
+import { useRef, useEffect, useState, useCallback, useMemo, type RefObject } from 'react';
+import { Stage, Layer, Image, Line, Rect, Group, Circle, Text } from 'react-konva';
 import Konva from 'konva';
-import { AppState, ToolState, Drawing, DrawingPoint, PlayerViewport } from '../types';
+import { AppState, ToolState, Drawing, DrawingPoint, PlayerViewport, FogShape, Wall, Token, AoeTemplate } from '../types';
 import { modifyFog, modifyBlocks } from '../store';
 import { openUrl } from '@tauri-apps/plugin-opener';
-import { hexAtPoint, linkForHex } from '../hexmap';
+import { hexAtPoint, hexCenter, linkForHex } from '../hexmap';
+import { createToken, snapToken, tokenAt, tokenRadius } from '../tokens';
+import { cellOutline, formatFeet } from '../measure';
+import { levelForScale, visibleTiles, type TileSet } from '../tiles';
+
+// Distance from a point to a wall segment (map pixels).
+function distanceToWall(p: DrawingPoint, wall: Wall): number {
+  const dx = wall.x2 - wall.x1;
+  const dy = wall.y2 - wall.y1;
+  const lengthSq = dx * dx + dy * dy;
+  const t = lengthSq === 0 ? 0 : Math.max(0, Math.min(1, ((p.x - wall.x1) * dx + (p.y - wall.y1) * dy) / lengthSq));
+  return Math.hypot(p.x - (wall.x1 + t * dx), p.y - (wall.y1 + t * dy));
+}
+
+// Index of the item nearest to `p` within `maxDistance`, or -1.
+function nearestIndex<T>(items: T[], distance: (item: T) => number, maxDistance: number): number {
+  let best = -1;
+  let bestDistance = maxDistance;
+  items.forEach((item, i) => {
+    const d = distance(item);
+    if (d <= bestDistance) {
+      best = i;
+      bestDistance = d;
+    }
+  });
+  return best;
+}
+
+// One token: its image clipped to a disc, or a coloured disc with the
+// initial, and the name underneath; ringed while its initiative turn is up.
+// Draggable with the Token tool (DM).
+function TokenSprite({ token, radius, scale, draggable, dim, active, onMove }: {
+  token: Token;
+  radius: number;
+  scale: number;
+  draggable: boolean;
+  dim: boolean;
+  active: boolean;
+  onMove: (x: number, y: number) => { x: number; y: number };
+}) {
+  const [image, setImage] = useState<HTMLImageElement | null>(null);
+  useEffect(() => {
+    if (!token.image) {
+      setImage(null);
+      return;
+    }
+    const img = new window.Image();
+    img.onload = () => setImage(img);
+    img.src = token.image;
+  }, [token.image]);
+
+  // Keep the drag out of the layer's pan handler, and snap on drop.
+  const stopBubble = (e: Konva.KonvaEventObject<DragEvent>) => {
+    e.cancelBubble = true;
+  };
+  const handleDragEnd = (e: Konva.KonvaEventObject<DragEvent>) => {
+    e.cancelBubble = true;
+    e.target.position(onMove(e.target.x(), e.target.y()));
+  };
+
+  return (
+    <Group
+      x={token.x}
+      y={token.y}
+      opacity={dim ? 0.5 : 1}
+      draggable={draggable}
+      onDragStart={stopBubble}
+      onDragMove={stopBubble}
+      onDragEnd={handleDragEnd}
+    >
+      <Circle radius={radius} fill={token.color} />
+      {image ? (
+        <Group clipFunc={(ctx) => ctx.arc(0, 0, radius, 0, Math.PI * 2)}>
+          <Image image={image} x={-radius} y={-radius} width={radius * 2} height={radius * 2} />
+        </Group>
+      ) : (
+        <Text
+          text={token.name.charAt(0).toUpperCase()}
+          fontSize={radius}
+          fontStyle="bold"
+          fill="#ffffff"
+          width={radius * 2}
+          height={radius * 2}
+          x={-radius}
+          y={-radius}
+          align="center"
+          verticalAlign="middle"
+        />
+      )}
+      <Circle radius={radius} stroke="#ffffff" strokeWidth={2 / scale} />
+      {active && <Circle radius={radius + 4 / scale} stroke="#ffd54f" strokeWidth={4 / scale} />}
+      <Text
+        text={token.name}
+        fontSize={12 / scale}
+        fill="#ffffff"
+        stroke="#000000"
+        strokeWidth={0.5 / scale}
+        width={radius * 4}
+        x={-radius * 2}
+        y={radius + 2 / scale}
+        align="center"
+      />
+    </Group>
+  );
+}
+
+// Decoded tiles kept around, so panning back over a spot doesn't fetch it
+// again. Tiles in view are never dropped, however many there are.
+const MAX_CACHED_TILES = 512;
+
+// A tiled map image (see `tiles.ts`): the tiles in view at the level that
+// suits the zoom, over the whole map at the coarsest level so nothing goes
+// blank while they load.
+function MapTiles({ tiles, scale, view }: {
+  tiles: TileSet;
+  scale: number;
+  view: { x: number; y: number; width: number; height: number }; // map pixels
+}) {
+  const cacheRef = useRef(new Map<string, HTMLImageElement>());
+  const [, setLoadedCount] = useState(0);
+
+  const level = levelForScale(tiles, scale);
+  const coarsest = tiles.levels - 1;
+  const wanted = useMemo(() => [
+    ...(level < coarsest ? visibleTiles(tiles, coarsest, { x: 0, y: 0, width: tiles.width, height: tiles.height }) : []),
+    ...visibleTiles(tiles, level, { x: view.x, y: view.y, width: view.width, height: view.height }),
+  ], [tiles, level, coarsest, view.x, view.y, view.width, view.height]);
+
+  useEffect(() => {
+    const cache = cacheRef.current;
+    for (const tile of wanted) {
+      let img = cache.get(tile.url);
+      if (img) {
+        cache.delete(tile.url); // re-added below as the most recently used
+      } else {
+        img = new window.Image();
+        // Served with CORS, so the canvas stays exportable (snapshot thumbnails).
+        img.crossOrigin = 'anonymous';
+        img.onload = () => setLoadedCount(n => n + 1);
+        img.src = tile.url;
+      }
+      cache.set(tile.url, img);
+    }
+    const inView = new Set(wanted.map(tile => tile.url));
+    for (const url of cache.keys()) {
+      if (cache.size <= MAX_CACHED_TILES) break;
+      if (!inView.has(url)) cache.delete(url);
+    }
+  }, [wanted]);
+
+  // Tiles meet at fractional screen positions; a pixel of overlap hides the
+  // seams.
+  const overlap = 1 / scale;
+  return (
+    <Group listening={false}>
+      {wanted.map((tile) => {
+        const img = cacheRef.current.get(tile.url);
+        if (!img?.complete || img.naturalWidth === 0) return null;
+        return (
+          <Image
+            key={tile.key}
+            image={img}
+            x={tile.x}
+            y={tile.y}
+            width={tile.width + overlap}
+            height={tile.height + overlap}
+            perfectDrawEnabled={false}
+          />
+        );
+      })}
+    </Group>
+  );
+}
 
 interface MapCanvasProps {
   state: AppState;
@@ -13,6 +185,7 @@ interface MapCanvasProps {
   onStateChange?: (state: AppState) => void;
   onFogOperationStart?: () => void; // Called when fog operation starts
   onFogOperationEnd?: () => void; // Called when fog operation ends
+  onFogShape?: (shape: FogShape, reveal: boolean) => void; // Shift/Alt+drag with a fog tool
   onDrawingAdded?: (drawing: Drawing) => void; // Called when a drawing is completed
   onBlockOperationStart?: () => void; // Called when block operation starts
   onBlockOperationEnd?: () => void; // Called when block operation ends
@@ -20,6 +193,11 @@ interface MapCanvasProps {
   height: number;
   playerViewport?: PlayerViewport | null;
   onHexHover?: (hex: { col: number; row: number } | null) => void; // .hexm hover (DM)
+  onPartyMove?: (hex: { col: number; row: number }, place: boolean) => void; // Party tool click on a .hexm
+  onTokensChange?: (tokens: Token[]) => void; // Token tool: placed, moved, hidden or deleted (one undo step each)
+  onMeasure?: (from: DrawingPoint, to: DrawingPoint, done: boolean) => void; // Measure tool drag (ruler or template)
+  templateDraft?: AoeTemplate | null; // Template being dragged out (DM only until released)
+  stageRef?: RefObject<Konva.Stage | null>; // Exposes the stage (e.g. for snapshot thumbnails)
 }
 
 export function MapCanvas({
@@ -29,6 +207,7 @@ export function MapCanvas({
   onStateChange,
   onFogOperationStart,
   onFogOperationEnd,
+  onFogShape,
   onDrawingAdded,
   onBlockOperationStart,
   onBlockOperationEnd,
@@ -36,13 +215,27 @@ export function MapCanvas({
   height,
   playerViewport,
   onHexHover,
+  onPartyMove,
+  onTokensChange,
+  onMeasure,
+  templateDraft,
+  stageRef: externalStageRef,
 }: MapCanvasProps) {
-  const stageRef = useRef<Konva.Stage>(null);
+  const localStageRef = useRef<Konva.Stage>(null);
+  const stageRef = externalStageRef ?? localStageRef;
   const [image, setImage] = useState<HTMLImageElement | null>(null);
   const [isDrawing, setIsDrawing] = useState(false);
   const [isFogging, setIsFogging] = useState(false);
   const [isBlocking, setIsBlocking] = useState(false);
   const [currentDrawing, setCurrentDrawing] = useState<DrawingPoint[]>([]);
+  // Fog shape being dragged (map pixels): a rectangle's two corners, or the
+  // lasso path so far.
+  const [fogShape, setFogShape] = useState<{ kind: FogShape['kind']; points: DrawingPoint[] } | null>(null);
+  // Wall being dragged out with the wall tool (ends snapped to grid corners).
+  const [wallDraft, setWallDraft] = useState<Wall | null>(null);
+  // Where a Measure tool drag started, and where the pointer is now.
+  const [measureStart, setMeasureStart] = useState<DrawingPoint | null>(null);
+  const measureEndRef = useRef<DrawingPoint | null>(null);
 
   // Track latest state for operation completion (avoids stale closure issues)
   const latestStateRef = useRef(state);
@@ -145,15 +338,19 @@ export function MapCanvas({
     isPlayerView && state.map.playerImageUrl
       ? state.map.playerImageUrl
       : state.map.imageUrl;
+  // A tiled map's `imageUrl` is only its overview tile; the tiles are drawn
+  // instead.
+  const mapTiles = activeImageUrl === state.map.imageUrl ? state.map.tiles ?? null : null;
+  const tiled = mapTiles !== null;
   useEffect(() => {
-    if (activeImageUrl) {
+    if (activeImageUrl && !tiled) {
       const img = new window.Image();
       img.src = activeImageUrl;
       img.onload = () => setImage(img);
     } else {
       setImage(null);
     }
-  }, [activeImageUrl]);
+  }, [activeImageUrl, tiled]);
 
   // Handle wheel zoom (DM only)
   const handleWheel = useCallback((e: Konva.KonvaEventObject<WheelEvent>) => {
@@ -204,8 +401,17 @@ export function MapCanvas({
     };
   }, [state.view, state.map.gridSize, state.map.gridOffsetX, state.map.gridOffsetY]);
 
+  // Nearest grid corner, so walls line up with the fog cells they block.
+  const snapToCorner = useCallback((x: number, y: number): DrawingPoint => {
+    const gridSize = state.map.gridSize || 50;
+    return {
+      x: Math.round((x - state.map.gridOffsetX) / gridSize) * gridSize + state.map.gridOffsetX,
+      y: Math.round((y - state.map.gridOffsetY) / gridSize) * gridSize + state.map.gridOffsetY,
+    };
+  }, [state.map.gridSize, state.map.gridOffsetX, state.map.gridOffsetY]);
+
   // Handle mouse down
-  const handleMouseDown = useCallback((_e: Konva.KonvaEventObject<MouseEvent>) => {
+  const handleMouseDown = useCallback((e: Konva.KonvaEventObject<MouseEvent>) => {
     if (isPlayerView || !onStateChange || !toolState) return;
 
     const stage = stageRef.current;
@@ -214,7 +420,10 @@ export function MapCanvas({
     const pos = getGridPosition(stage);
     if (!pos) return;
 
-    if (toolState.activeTool === 'fogReveal' || toolState.activeTool === 'fogHide') {
+    const fogTool = toolState.activeTool === 'fogReveal' || toolState.activeTool === 'fogHide';
+    if (fogTool && onFogShape && (e.evt.shiftKey || e.evt.altKey)) {
+      setFogShape({ kind: e.evt.shiftKey ? 'rect' : 'polygon', points: [{ x: pos.x, y: pos.y }] });
+    } else if (fogTool) {
       setIsFogging(true);
       onFogOperationStart?.();
       const reveal = toolState.activeTool === 'fogReveal';
@@ -232,10 +441,72 @@ export function MapCanvas({
       onBlockOperationStart?.();
       const newBlocks = modifyBlocks(state.blocks, pos.gridX, pos.gridY, toolState.brushSize, toolState.blockColor);
       onStateChange({ ...state, blocks: newBlocks });
+    } else if (toolState.activeTool === 'wall') {
+      const { walls } = state.walls;
+      if (e.evt.altKey) {
+        // Alt+click deletes the nearest wall
+        const index = nearestIndex(walls, wall => distanceToWall(pos, wall), (state.map.gridSize || 50) / 2);
+        if (index >= 0) {
+          onStateChange({ ...state, walls: { ...state.walls, walls: walls.filter((_, i) => i !== index) } });
+        }
+        return;
+      }
+      const start = snapToCorner(pos.x, pos.y);
+      setWallDraft({ id: Date.now().toString(), x1: start.x, y1: start.y, x2: start.x, y2: start.y, door: e.evt.shiftKey });
+    } else if (toolState.activeTool === 'vision') {
+      const { lights } = state.walls;
+      const gridSize = state.map.gridSize || 50;
+      if (e.evt.altKey) {
+        // Alt+click removes the nearest light
+        const index = nearestIndex(lights, light => Math.hypot(light.x - pos.x, light.y - pos.y), gridSize);
+        if (index >= 0) {
+          onStateChange({ ...state, walls: { ...state.walls, lights: lights.filter((_, i) => i !== index) } });
+        }
+        return;
+      }
+      const light = {
+        id: Date.now().toString(),
+        x: pos.x,
+        y: pos.y,
+        radius: toolState.sightRadius > 0 ? toolState.sightRadius * gridSize : null,
+      };
+      // Click moves the party's light; Shift+click adds another one.
+      const newLights = e.evt.shiftKey || lights.length === 0
+        ? [...lights, light]
+        : [{ ...light, id: lights[0].id }, ...lights.slice(1)];
+      onStateChange({ ...state, walls: { ...state.walls, lights: newLights } });
+    } else if (toolState.activeTool === 'party') {
+      // Moving (and whether the hex is next to the party) is the DM view's call.
+      const hex = state.map.hexmap && hexAtPoint(state.map.hexmap, pos.x, pos.y);
+      if (hex) onPartyMove?.(hex, e.evt.shiftKey);
+    } else if (toolState.activeTool === 'token' && onTokensChange) {
+      const hit = tokenAt(state.tokens, state.map, pos.x, pos.y);
+      if (hit && e.evt.altKey) {
+        onTokensChange(state.tokens.filter(t => t.id !== hit.id));
+      } else if (hit && e.evt.shiftKey) {
+        onTokensChange(state.tokens.map(t => (t.id === hit.id ? { ...t, hidden: !t.hidden } : t)));
+      } else if (!hit && !e.evt.altKey && !e.evt.shiftKey) {
+        const { x, y } = snapToken(state.map, toolState.tokenTemplate.size, pos.x, pos.y);
+        onTokensChange([...state.tokens, createToken(state.tokens, toolState.tokenTemplate, x, y)]);
+      }
+      // A plain press on a token starts dragging it (see TokenSprite).
+    } else if (toolState.activeTool === 'measure' && onMeasure) {
+      if (e.evt.altKey) {
+        // Alt+click removes the topmost template covering the cell
+        const hex = state.map.hexmap && hexAtPoint(state.map.hexmap, pos.x, pos.y);
+        const [col, row] = state.map.hexmap ? (hex ? [hex.col, hex.row] : [NaN, NaN]) : [pos.gridX, pos.gridY];
+        const hit = [...state.templates].reverse().find(t => t.cells.some(([c, r]) => c === col && r === row));
+        if (hit) onStateChange({ ...state, templates: state.templates.filter(t => t !== hit) });
+        return;
+      }
+      const start = { x: pos.x, y: pos.y };
+      setMeasureStart(start);
+      measureEndRef.current = start;
+      onMeasure(start, start, false);
     } else if (toolState.activeTool === 'pan') {
       // Pan is handled by drag
     }
-  }, [isPlayerView, onStateChange, onFogOperationStart, toolState, state, getGridPosition]);
+  }, [isPlayerView, onStateChange, onFogOperationStart, onFogShape, onPartyMove, onTokensChange, onMeasure, toolState, state, getGridPosition, snapToCorner]);
 
   // Click a hex on a `.hexm` map (DM view, pan tool) to open its key — e.g.
   // an `obsidian://` link to the hex-key note. A pan drag does not fire click,
@@ -280,7 +551,20 @@ export function MapCanvas({
     const pos = getGridPosition(stage);
     if (!pos) return;
 
-    if (toolState.activeTool === 'fogReveal' || toolState.activeTool === 'fogHide') {
+    if (measureStart) {
+      const end = { x: pos.x, y: pos.y };
+      measureEndRef.current = end;
+      onMeasure?.(measureStart, end, false);
+    } else if (wallDraft) {
+      const end = snapToCorner(pos.x, pos.y);
+      setWallDraft(prev => prev && { ...prev, x2: end.x, y2: end.y });
+    } else if (fogShape) {
+      const point = { x: pos.x, y: pos.y };
+      setFogShape(prev => prev && {
+        ...prev,
+        points: prev.kind === 'rect' ? [prev.points[0], point] : [...prev.points, point],
+      });
+    } else if (toolState.activeTool === 'fogReveal' || toolState.activeTool === 'fogHide') {
       const reveal = toolState.activeTool === 'fogReveal';
       const newFog = modifyFog(state.fog, pos.gridX, pos.gridY, toolState.brushSize, reveal);
       onStateChange({ ...state, fog: newFog });
@@ -296,7 +580,7 @@ export function MapCanvas({
       const newBlocks = modifyBlocks(latestStateRef.current.blocks, pos.gridX, pos.gridY, toolState.brushSize, toolState.blockColor);
       onStateChange({ ...latestStateRef.current, blocks: newBlocks });
     }
-  }, [isPlayerView, onStateChange, toolState, state, getGridPosition, isDrawing, isBlocking, onHexHover]);
+  }, [isPlayerView, onStateChange, toolState, state, getGridPosition, isDrawing, isBlocking, fogShape, wallDraft, measureStart, onMeasure, snapToCorner, onHexHover]);
 
   // Handle mouse up
   const handleMouseUp = useCallback(() => {
@@ -322,14 +606,49 @@ export function MapCanvas({
     if (isFogging) {
       onFogOperationEnd?.();
     }
+    if (fogShape && toolState && onFogShape) {
+      const reveal = toolState.activeTool === 'fogReveal';
+      const gridSize = state.map.gridSize || 50;
+      const cellAt = (p: DrawingPoint) => ({
+        row: Math.floor((p.y - state.map.gridOffsetY) / gridSize),
+        col: Math.floor((p.x - state.map.gridOffsetX) / gridSize),
+      });
+      const { points } = fogShape;
+      if (fogShape.kind === 'rect') {
+        onFogShape({ kind: 'rect', from: cellAt(points[0]), to: cellAt(points[points.length - 1]) }, reveal);
+      } else if (fogShape.points.length > 2) {
+        onFogShape({ kind: 'polygon', points: fogShape.points }, reveal);
+      }
+      setFogShape(null);
+    }
+    if (wallDraft && onStateChange) {
+      const current = latestStateRef.current;
+      if (wallDraft.x1 !== wallDraft.x2 || wallDraft.y1 !== wallDraft.y2) {
+        onStateChange({ ...current, walls: { ...current.walls, walls: [...current.walls.walls, wallDraft] } });
+      } else {
+        // A click (no drag) opens or closes the nearest door
+        const { walls } = current.walls;
+        const point = { x: wallDraft.x1, y: wallDraft.y1 };
+        const index = nearestIndex(walls, wall => (wall.door ? distanceToWall(point, wall) : Infinity), (state.map.gridSize || 50) / 2);
+        if (index >= 0) {
+          const toggled = walls.map((wall, i) => (i === index ? { ...wall, open: !wall.open } : wall));
+          onStateChange({ ...current, walls: { ...current.walls, walls: toggled } });
+        }
+      }
+      setWallDraft(null);
+    }
     if (isBlocking) {
       onBlockOperationEnd?.();
     }
+    if (measureStart) {
+      onMeasure?.(measureStart, measureEndRef.current ?? measureStart, true);
+      setMeasureStart(null);
+    }
     setIsDrawing(false);
     setIsFogging(false);
     setIsBlocking(false);
     setCurrentDrawing([]);
-  }, [isDrawing, isFogging, isBlocking, currentDrawing, onStateChange, onFogOperationEnd, onBlockOperationEnd, onDrawingAdded, toolState]);
+  }, [isDrawing, isFogging, isBlocking, currentDrawing, onStateChange, onFogOperationEnd, onBlockOperationEnd, onDrawingAdded, toolState, fogShape, onFogShape, wallDraft, measureStart, onMeasure, state.map]);
 
   // Handle drag for panning
   const handleDragEnd = useCallback((e: Konva.KonvaEventObject<DragEvent>) => {
@@ -357,6 +676,26 @@ export function MapCanvas({
     ? { x: -state.playerViewOffset.x * playerScale, y: -state.playerViewOffset.y * playerScale }
     : { x: state.view.offsetX, y: state.view.offsetY };
 
+  // Where the party marker sits (hex maps only).
+  const hexmap = state.map.hexmap;
+  const partyPosition = state.party?.position;
+  const partyMarker = hexmap && partyPosition
+    ? { ...hexCenter(hexmap, partyPosition[0], partyPosition[1]), radius: hexmap.hexRadius * 0.4 }
+    : null;
+
+  // The token whose initiative turn it is, if its entry is linked to one.
+  const activeTokenId = state.initiative.entities.find(e => e.id === state.initiative.turn)?.tokenId ?? null;
+
+  // Snap a dropped token and report the move; returns where it ended up.
+  const moveToken = (token: Token, x: number, y: number) => {
+    const snapped = snapToken(state.map, token.size, x, y);
+    const current = latestStateRef.current.tokens;
+    if (snapped.x !== token.x || snapped.y !== token.y) {
+      onTokensChange?.(current.map(t => (t.id === token.id ? { ...t, ...snapped } : t)));
+    }
+    return snapped;
+  };
+
   return (
     <Stage
       ref={stageRef}
@@ -378,7 +717,20 @@ export function MapCanvas({
         onDragEnd={handleDragEnd}
       >
         {/* Map Image */}
-        {image && (
+        {mapTiles && (
+          <MapTiles
+            key={mapTiles.id}
+            tiles={mapTiles}
+            scale={effectiveScale}
+            view={{
+              x: -effectiveOffset.x / effectiveScale,
+              y: -effectiveOffset.y / effectiveScale,
+              width: width / effectiveScale,
+              height: height / effectiveScale,
+            }}
+          />
+        )}
+        {!mapTiles && image && (
           <Image
             image={image}
             width={state.map.imageWidth}
@@ -456,6 +808,51 @@ export function MapCanvas({
           />
         )}
 
+        {/* Fog shape being dragged out (Shift = rectangle, Alt = lasso) */}
+        {fogShape && fogShape.kind === 'rect' && fogShape.points.length > 1 && (
+          <Rect
+            x={Math.min(fogShape.points[0].x, fogShape.points[1].x)}
+            y={Math.min(fogShape.points[0].y, fogShape.points[1].y)}
+            width={Math.abs(fogShape.points[1].x - fogShape.points[0].x)}
+            height={Math.abs(fogShape.points[1].y - fogShape.points[0].y)}
+            stroke="#ffffff"
+            strokeWidth={2 / effectiveScale}
+            dash={[8 / effectiveScale, 6 / effectiveScale]}
+          />
+        )}
+        {fogShape && fogShape.kind === 'polygon' && fogShape.points.length > 1 && (
+          <Line
+            points={fogShape.points.flatMap(p => [p.x, p.y])}
+            closed
+            stroke="#ffffff"
+            strokeWidth={2 / effectiveScale}
+            dash={[8 / effectiveScale, 6 / effectiveScale]}
+          />
+        )}
+
+        {/* Area-of-effect templates (under the fog, like drawings) */}
+        {[...(state.templates ?? []), ...(templateDraft ? [templateDraft] : [])].map((template) => (
+          <Group key={template.id} listening={false}>
+            {template.cells.map((cell) => (
+              <Line
+                key={`${cell[0]},${cell[1]}`}
+                points={cellOutline(state.map, cell)}
+                closed
+                fill={template.color}
+                opacity={0.35}
+              />
+            ))}
+            <Circle
+              x={template.origin.x}
+              y={template.origin.y}
+              radius={5 / effectiveScale}
+              fill={template.color}
+              stroke="#000000"
+              strokeWidth={1 / effectiveScale}
+            />
+          </Group>
+        ))}
+
         {/* Laser pointer (temporary, synced from DM) */}
         {state.laserPoints.length > 1 && (
           <>
@@ -486,6 +883,104 @@ export function MapCanvas({
           />
         )}
 
+        {/* Party marker (hex maps; players see it too) */}
+        {partyMarker && (
+          <Circle
+            x={partyMarker.cx}
+            y={partyMarker.cy}
+            radius={partyMarker.radius}
+            fill="#d32f2f"
+            stroke="#ffffff"
+            strokeWidth={3 / effectiveScale}
+          />
+        )}
+
+        {/* Tokens (hidden ones are dimmed for the DM and never sent to players) */}
+        {(state.tokens ?? []).map((token) => (
+          <TokenSprite
+            key={token.id}
+            token={token}
+            radius={tokenRadius(state.map, token)}
+            scale={effectiveScale}
+            draggable={!isPlayerView && toolState?.activeTool === 'token'}
+            dim={!isPlayerView && token.hidden}
+            active={token.id === activeTokenId}
+            onMove={(x, y) => moveToken(token, x, y)}
+          />
+        ))}
+
+        {/* Ruler (temporary, synced from DM) */}
+        {state.ruler && (
+          <Group listening={false}>
+            <Line
+              points={[state.ruler.from.x, state.ruler.from.y, state.ruler.to.x, state.ruler.to.y]}
+              stroke="#ffeb3b"
+              strokeWidth={3 / effectiveScale}
+              dash={[10 / effectiveScale, 6 / effectiveScale]}
+              lineCap="round"
+            />
+            <Circle x={state.ruler.from.x} y={state.ruler.from.y} radius={5 / effectiveScale} fill="#ffeb3b" />
+            <Text
+              text={formatFeet(state.ruler.feet)}
+              x={state.ruler.to.x + 12 / effectiveScale}
+              y={state.ruler.to.y - 12 / effectiveScale}
+              fontSize={20 / effectiveScale}
+              fontStyle="bold"
+              fill="#ffeb3b"
+              stroke="#000000"
+              strokeWidth={1 / effectiveScale}
+            />
+          </Group>
+        )}
+
+        {/* Walls, doors and light sources (DM view only; never synced to players) */}
+        {!isPlayerView && state.walls && (
+          <>
+            {[...state.walls.walls, ...(wallDraft ? [wallDraft] : [])].map((wall) => (
+              <Line
+                key={wall.id}
+                points={[wall.x1, wall.y1, wall.x2, wall.y2]}
+                stroke={wall.door ? '#ff9900' : '#ff3333'}
+                strokeWidth={4 / effectiveScale}
+                dash={wall.door && wall.open ? [8 / effectiveScale, 6 / effectiveScale] : undefined}
+                lineCap="round"
+              />
+            ))}
+            {(state.walls.mapLights ?? []).map((light) => (
+              <Circle
+                key={light.id}
+                x={light.x}
+                y={light.y}
+                radius={6 / effectiveScale}
+                stroke="#ffdd00"
+                strokeWidth={2 / effectiveScale}
+              />
+            ))}
+            {state.walls.lights.map((light) => (
+              <Group key={light.id}>
+                {light.radius && (
+                  <Circle
+                    x={light.x}
+                    y={light.y}
+                    radius={light.radius}
+                    stroke="#ffdd00"
+                    strokeWidth={2 / effectiveScale}
+                    dash={[8 / effectiveScale, 6 / effectiveScale]}
+                  />
+                )}
+                <Circle
+                  x={light.x}
+                  y={light.y}
+                  radius={10 / effectiveScale}
+                  fill="#ffdd00"
+                  stroke="#000000"
+                  strokeWidth={1 / effectiveScale}
+                />
+              </Group>
+            ))}
+          </>
+        )}
+
         {/* Player Viewport Indicator (DM view only) */}
         {!isPlayerView && playerViewport && (
           <Rect
@@ -0,0 +1,70 @@
+import { PartyState } from '../types';
+import { formatHexCoord } from '../hexcrawl';
+
+interface PartyLogProps {
+  party: PartyState;
+  onDayChange: (day: number) => void;
+  onNoteChange: (index: number, note: string) => void;
+  onUndo: () => void;
+  onExport: () => void;
+  onRollEncounter: () => void;
+}
+
+export function PartyLog({ party, onDayChange, onNoteChange, onUndo, onExport, onRollEncounter }: PartyLogProps) {
+  return (
+    <div className="settings-panel">
+      <h3>Party</h3>
+      <p className="settings-help">
+        With the Party tool (M), click a hex next to the marker to move there; Shift+click places it anywhere.
+        Each move reveals the hex and its neighbors and is logged below. Encounters roll on the tables in the
+        map's <code>encounters</code> folder.
+      </p>
+
+      <div className="setting-row">
+        <label>Day:</label>
+        <button onClick={() => onDayChange(Math.max(1, party.day - 1))} disabled={party.day <= 1}>−</button>
+        <span>{party.day}</span>
+        <button onClick={() => onDayChange(party.day + 1)}>+</button>
+        <span className="party-position">
+          {party.position ? `at ${formatHexCoord(party.position)}` : 'not placed'}
+        </span>
+      </div>
+
+      {party.log.length > 0 && (
+        <div className="party-log">
+          {party.log.map((entry, i) => (
+            <div key={i} className="party-log-entry">
+              <span className="party-log-move">
+                Day {entry.day} · {entry.from ? `${formatHexCoord(entry.from)} → ` : 'start '}{formatHexCoord(entry.to)}
+              </span>
+              <input
+                type="text"
+                placeholder="Note"
+                value={entry.note}
+                onChange={(e) => onNoteChange(i, e.target.value)}
+              />
+            </div>
+          ))}
+        </div>
+      )}
+
+      <div className="setting-row">
+        <button onClick={onRollEncounter} disabled={!party.position}>Roll encounter</button>
+      </div>
+      {party.encounters.length > 0 && (
+        <div className="setting-info party-encounters">
+          {party.encounters.map((entry, i) => (
+            <p key={i}>
+              Day {entry.day} · {formatHexCoord(entry.hex)}: {entry.summary}
+            </p>
+          ))}
+        </div>
+      )}
+
+      <div className="setting-row">
+        <button onClick={onUndo} disabled={party.log.length === 0}>Undo last move</button>
+        <button onClick={onExport} disabled={party.log.length === 0}>Export…</button>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,43 @@
+import { useEffect, useState } from 'react';
+import { onPlayerRollError, sendPlayerRoll } from '../dice';
+
+const NAME_KEY = 'vtt-player-name';
+
+// Dice box for LAN players. The server rolls; the result shows up for
+// everyone through the DM's roll history.
+export function PlayerDice() {
+  const [name, setName] = useState(() => localStorage.getItem(NAME_KEY) ?? '');
+  const [expression, setExpression] = useState('1d20');
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => onPlayerRollError(setError), []);
+
+  const submit = () => {
+    if (!expression.trim()) return;
+    setError(null);
+    localStorage.setItem(NAME_KEY, name.trim());
+    sendPlayerRoll(expression.trim(), name.trim());
+  };
+
+  return (
+    <div className="player-dice">
+      <input
+        type="text"
+        className="player-dice-name"
+        value={name}
+        onChange={(e) => setName(e.target.value)}
+        placeholder="Name"
+      />
+      <input
+        type="text"
+        className="dice-input"
+        value={expression}
+        onChange={(e) => setExpression(e.target.value)}
+        onKeyDown={(e) => e.key === 'Enter' && submit()}
+        placeholder="1d20+5"
+      />
+      <button onClick={submit}>Roll</button>
+      {error && <div className="travel-error">{error}</div>}
+    </div>
+  );
+}
@@ -0,0 +1,112 @@
+import { useState } from 'react';
+import { SnapshotMeta } from '../types';
+
+interface SnapshotSettingsProps {
+  snapshots: SnapshotMeta[];
+  enabled: boolean; // false until a map is loaded
+  onCreate: (name: string) => void;
+  onRestore: (id: string) => void;
+  onRename: (id: string, name: string) => void;
+  onDuplicate: (id: string) => void;
+  onDelete: (id: string) => void;
+}
+
+export function SnapshotSettings({
+  snapshots,
+  enabled,
+  onCreate,
+  onRestore,
+  onRename,
+  onDuplicate,
+  onDelete,
+}: SnapshotSettingsProps) {
+  const [newName, setNewName] = useState('');
+  const [editing, setEditing] = useState<{ id: string; name: string } | null>(null);
+  // Delete and restore are destructive, so each needs a second click.
+  const [confirming, setConfirming] = useState<{ id: string; action: 'restore' | 'delete' } | null>(null);
+
+  const submitCreate = () => {
+    if (!newName.trim()) return;
+    onCreate(newName.trim());
+    setNewName('');
+  };
+
+  const submitRename = () => {
+    if (editing && editing.name.trim()) onRename(editing.id, editing.name.trim());
+    setEditing(null);
+  };
+
+  const confirmThen = (id: string, action: 'restore' | 'delete', run: () => void) => {
+    if (confirming?.id === id && confirming.action === action) {
+      setConfirming(null);
+      run();
+    } else {
+      setConfirming({ id, action });
+    }
+  };
+
+  return (
+    <div className="settings-panel">
+      <h3>Snapshots</h3>
+      <p className="settings-help">
+        Named copies of this map's fog, drawings and blocks — e.g. one per party.
+      </p>
+
+      <div className="setting-row">
+        <input
+          type="text"
+          className="snapshot-name-input"
+          placeholder="Snapshot name"
+          value={newName}
+          disabled={!enabled}
+          onChange={(e) => setNewName(e.target.value)}
+          onKeyDown={(e) => { if (e.key === 'Enter') submitCreate(); }}
+        />
+        <button onClick={submitCreate} disabled={!enabled || !newName.trim()}>Save</button>
+      </div>
+
+      {snapshots.length === 0 && enabled && (
+        <div className="setting-info"><p>No snapshots yet.</p></div>
+      )}
+
+      <ul className="snapshot-list">
+        {snapshots.map((s) => (
+          <li key={s.id} className="snapshot-row">
+            {s.thumbnail
+              ? <img className="snapshot-thumb" src={s.thumbnail} alt="" />
+              : <div className="snapshot-thumb" />}
+            <div className="snapshot-body">
+              {editing?.id === s.id ? (
+                <input
+                  type="text"
+                  className="snapshot-name-input"
+                  autoFocus
+                  value={editing.name}
+                  onChange={(e) => setEditing({ id: s.id, name: e.target.value })}
+                  onKeyDown={(e) => {
+                    if (e.key === 'Enter') submitRename();
+                    if (e.key === 'Escape') setEditing(null);
+                  }}
+                  onBlur={submitRename}
+                />
+              ) : (
+                <span className="snapshot-name" title={s.name}>{s.name}</span>
+              )}
+              <span className="snapshot-date">{new Date(s.updatedAt).toLocaleString()}</span>
+              <div className="snapshot-actions">
+                <button onClick={() => confirmThen(s.id, 'restore', () => onRestore(s.id))}>
+                  {confirming?.id === s.id && confirming.action === 'restore' ? 'Replace live?' : 'Restore'}
+                </button>
+                <button onClick={() => setEditing({ id: s.id, name: s.name })}>Rename</button>
+                <button onClick={() => onDuplicate(s.id)}>Copy</button>
+                <button onClick={() => confirmThen(s.id, 'delete', () => onDelete(s.id))}>
+                  {confirming?.id === s.id && confirming.action === 'delete' ? 'Sure?' : 'Delete'}
+                </button>
+              </div>
+            </div>
+          </li>
+        ))}
+      </ul>
+    </div>
+  );
+}
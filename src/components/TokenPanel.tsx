@@ -0,0 +1,81 @@
+import { Token } from '../types';
+import { TokenTemplate } from '../tokens';
+
+interface TokenPanelProps {
+  tokens: Token[];
+  template: TokenTemplate;
+  onTemplateChange: (template: TokenTemplate) => void;
+  onPickImage: () => void;
+  onToggleHidden: (id: string) => void;
+  onRemove: (id: string) => void;
+}
+
+export function TokenPanel({ tokens, template, onTemplateChange, onPickImage, onToggleHidden, onRemove }: TokenPanelProps) {
+  const set = (changes: Partial<TokenTemplate>) => onTemplateChange({ ...template, ...changes });
+
+  return (
+    <div className="settings-panel">
+      <h3>Tokens</h3>
+      <p className="settings-help">
+        With the Token tool (T), click the map to place a token like this one. Hidden tokens (and tokens under fog)
+        aren't shown to players.
+      </p>
+
+      <div className="setting-row">
+        <label>Name:</label>
+        <input type="text" className="token-input" value={template.name} onChange={(e) => set({ name: e.target.value })} />
+      </div>
+      <div className="setting-row">
+        <label>Size:</label>
+        <select value={template.size} onChange={(e) => set({ size: parseFloat(e.target.value) })}>
+          <option value={0.5}>Tiny (½)</option>
+          <option value={1}>Medium (1)</option>
+          <option value={2}>Large (2)</option>
+          <option value={3}>Huge (3)</option>
+          <option value={4}>Gargantuan (4)</option>
+        </select>
+        <input type="color" value={template.color} onChange={(e) => set({ color: e.target.value })} />
+      </div>
+      <div className="setting-row">
+        <label>Owner:</label>
+        <input
+          type="text"
+          className="token-input"
+          value={template.owner}
+          placeholder="DM"
+          onChange={(e) => set({ owner: e.target.value })}
+        />
+      </div>
+      <div className="setting-row">
+        <button onClick={onPickImage}>Image…</button>
+        {template.image && (
+          <>
+            <img className="token-thumb" src={template.image} alt="" />
+            <button onClick={() => set({ image: null })}>Remove</button>
+          </>
+        )}
+        <label className="token-hidden-label">
+          <input type="checkbox" checked={template.hidden} onChange={(e) => set({ hidden: e.target.checked })} />
+          Hidden
+        </label>
+      </div>
+
+      {tokens.length > 0 && (
+        <div className="token-list">
+          {tokens.map((token) => (
+            <div key={token.id} className={token.hidden ? 'token-list-entry hidden' : 'token-list-entry'}>
+              <span className="token-swatch" style={{ backgroundColor: token.color }} />
+              <span className="token-list-name">
+                {token.name}{token.owner ? ` (${token.owner})` : ''}
+              </span>
+              <button onClick={() => onToggleHidden(token.id)} title={token.hidden ? 'Show to players' : 'Hide from players'}>
+                {token.hidden ? 'Show' : 'Hide'}
+              </button>
+              <button onClick={() => onRemove(token.id)} title={`Remove ${token.name}`}>×</button>
+            </div>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+}
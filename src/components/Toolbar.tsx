@@ -1,4 +1,5 @@
 import { ToolState } from '../types';
+import { DISTANCE_RULES, DistanceRule, MeasureMode } from '../measure';
 
 const TOOL_NAMES: Record<string, string> = {
   pan: 'Pan',
@@ -7,14 +8,32 @@ const TOOL_NAMES: Record<string, string> = {
   draw: 'Draw (E)',
   laser: 'Laser (R)',
   block: 'Block (B)',
+  wall: 'Walls (L)',
+  vision: 'Vision (V)',
+  party: 'Party (M)',
+  token: 'Tokens (T)',
+  measure: 'Measure (U)',
 };
 
+const MEASURE_MODES: Array<{ value: MeasureMode; label: string }> = [
+  { value: 'ruler', label: 'Ruler' },
+  { value: 'cone', label: 'Cone' },
+  { value: 'sphere', label: 'Sphere' },
+  { value: 'line', label: 'Line' },
+  { value: 'cube', label: 'Cube' },
+];
+
+type MeasureSettings = Pick<ToolState, 'measureMode' | 'templateSizeFt' | 'templateColor' | 'distanceRule'>;
+
 interface ToolbarProps {
   toolState: ToolState;
   onBrushSizeChange: (size: number) => void;
   onDrawColorChange: (color: string) => void;
   onLaserColorChange: (color: string) => void;
   onBlockColorChange: (color: string) => void;
+  onSightRadiusChange: (radius: number) => void;
+  onMeasureSettingsChange: (settings: Partial<MeasureSettings>) => void;
+  onClearTemplates: () => void;
   onLoadMap: () => void;
   onOpenPlayerWindow: () => void;
   onClearDrawings: () => void;
@@ -30,6 +49,9 @@ export function Toolbar({
   onDrawColorChange,
   onLaserColorChange,
   onBlockColorChange,
+  onSightRadiusChange,
+  onMeasureSettingsChange,
+  onClearTemplates,
   onLoadMap,
   onOpenPlayerWindow,
   onClearDrawings,
@@ -97,6 +119,77 @@ export function Toolbar({
         </div>
       )}
 
+      {toolState.activeTool === 'wall' && (
+        <div className="toolbar-section">
+          <span className="toolbar-label">Drag: wall · Shift+drag: door · Click door: open/close · Alt+click: delete</span>
+        </div>
+      )}
+
+      {toolState.activeTool === 'party' && (
+        <div className="toolbar-section">
+          <span className="toolbar-label">Click next hex: move party · Shift+click: place anywhere</span>
+        </div>
+      )}
+
+      {toolState.activeTool === 'token' && (
+        <div className="toolbar-section">
+          <span className="toolbar-label">Click: place token · Drag: move · Shift+click: hide/show · Alt+click: delete</span>
+        </div>
+      )}
+
+      {toolState.activeTool === 'measure' && (
+        <div className="toolbar-section">
+          <select
+            value={toolState.measureMode}
+            onChange={(e) => onMeasureSettingsChange({ measureMode: e.target.value as MeasureMode })}
+          >
+            {MEASURE_MODES.map(({ value, label }) => <option key={value} value={value}>{label}</option>)}
+          </select>
+          {toolState.measureMode !== 'ruler' && (
+            <>
+              <input
+                type="number"
+                className="toolbar-number"
+                min={5}
+                step={5}
+                value={toolState.templateSizeFt}
+                onChange={(e) => onMeasureSettingsChange({ templateSizeFt: Math.max(0, parseFloat(e.target.value) || 0) })}
+                title="Radius (sphere), length (cone, line) or side (cube)"
+              />
+              <span>ft</span>
+              <input
+                type="color"
+                value={toolState.templateColor}
+                onChange={(e) => onMeasureSettingsChange({ templateColor: e.target.value })}
+              />
+            </>
+          )}
+          <select
+            value={toolState.distanceRule}
+            onChange={(e) => onMeasureSettingsChange({ distanceRule: e.target.value as DistanceRule })}
+            title="How diagonals count on square grids (hex maps count hexes)"
+          >
+            {DISTANCE_RULES.map(({ value, label }) => <option key={value} value={value}>{label}</option>)}
+          </select>
+          <span className="toolbar-label">Drag: measure / aim · Alt+click: remove template</span>
+          <button onClick={onClearTemplates}>Clear Templates</button>
+        </div>
+      )}
+
+      {toolState.activeTool === 'vision' && (
+        <div className="toolbar-section">
+          <span className="toolbar-label">Sight:</span>
+          <input
+            type="range"
+            min="0"
+            max="24"
+            value={toolState.sightRadius}
+            onChange={(e) => onSightRadiusChange(parseInt(e.target.value))}
+          />
+          <span>{toolState.sightRadius === 0 ? '∞' : toolState.sightRadius}</span>
+        </div>
+      )}
+
       <div className="toolbar-section">
         <button onClick={onClearDrawings}>Clear Drawings</button>
         <button onClick={onResetFog}>Reset Fog</button>
@@ -0,0 +1,89 @@
+import { useState } from 'react';
+import { planHexTravel, parseHexCoord, formatHexCoord, HexCoord, TravelPlan } from '../hexcrawl';
+
+interface TravelPlannerProps {
+  mapPath: string; // the loaded `.hexm`
+  partyPosition: HexCoord | null; // used when From is left blank
+}
+
+export function TravelPlanner({ mapPath, partyPosition }: TravelPlannerProps) {
+  const [from, setFrom] = useState('');
+  const [to, setTo] = useState('');
+  const [movement, setMovement] = useState(120);
+  const [plan, setPlan] = useState<TravelPlan | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  const submit = async () => {
+    const start = from.trim() ? parseHexCoord(from) : partyPosition;
+    const end = parseHexCoord(to);
+    if (!start || !end) {
+      setError('Enter hexes as CC.RR, e.g. 03.07');
+      return;
+    }
+    try {
+      setPlan(await planHexTravel(mapPath, start, end, movement));
+      setError(null);
+    } catch (err) {
+      setPlan(null);
+      setError(String(err));
+    }
+  };
+
+  return (
+    <div className="settings-panel">
+      <h3>Travel</h3>
+      <p className="settings-help">
+        Fastest route between two hexes. Roads and rivers move at ×3/2 and skip the navigation throw.
+      </p>
+
+      <div className="setting-row">
+        <label>From:</label>
+        <input
+          type="text"
+          className="travel-hex-input"
+          placeholder={partyPosition ? formatHexCoord(partyPosition) : 'CC.RR'}
+          value={from}
+          onChange={(e) => setFrom(e.target.value)}
+        />
+        <label>To:</label>
+        <input
+          type="text"
+          className="travel-hex-input"
+          placeholder="CC.RR"
+          value={to}
+          onChange={(e) => setTo(e.target.value)}
+          onKeyDown={(e) => e.key === 'Enter' && submit()}
+        />
+      </div>
+      <div className="setting-row">
+        <label>Movement (ft):</label>
+        <input
+          type="number"
+          min="5"
+          step="5"
+          value={movement}
+          onChange={(e) => setMovement(parseInt(e.target.value) || 120)}
+        />
+        <button onClick={submit}>Plan</button>
+      </div>
+
+      {error && <p className="setting-info travel-error">{error}</p>}
+      {plan && (
+        <div className="setting-info">
+          <p>
+            {plan.path.length - 1} hexes · {plan.miles} mi · {plan.days.toFixed(1)} days
+          </p>
+          {plan.dayPlans.map((d) => (
+            <p key={d.day} className="travel-day">
+              <strong>Day {d.day}</strong>: {d.entered.map(formatHexCoord).join(' → ') || 'no new hex'}
+              {' · '}{d.miles.toFixed(1)} mi
+              {' · '}{d.navigation === null ? 'no nav throw' : `nav ${d.navigation}+`}
+              {' · '}{d.travelChecks} enc check{d.travelChecks === 1 ? '' : 's'}
+              {' · '}camp {formatHexCoord(d.camp)}{d.territory ? ` (${d.territory})` : ''}: {d.campChecks} night check{d.campChecks === 1 ? '' : 's'}
+            </p>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,70 @@
+import { invoke } from '@tauri-apps/api/core';
+import { onRemoteEvent, sendRemote } from './remote';
+import type { RollRecord } from './types';
+
+// Dice are rolled in Rust (`src-tauri/src/dice.rs`): `2d8+1d6+3`, `4d6kh3`,
+// `4d6dl1`, `d20adv` / `1d20+5 dis`, exploding `3d6!`, fudge `4dF`, `d%`.
+
+export interface DieRoll {
+  value: number;
+  kept: boolean; // false for dropped dice and the losing half of advantage
+  exploded: boolean; // rolled because the previous die exploded
+}
+
+export interface TermRoll {
+  sign: 1 | -1;
+  notation: string;
+  dice: DieRoll[]; // empty for a constant
+  subtotal: number; // before the sign
+}
+
+export interface DiceRoll {
+  expression: string; // normalised, e.g. "1d20adv+5"
+  total: number;
+  terms: TermRoll[];
+  breakdown: string; // e.g. "4d6kh3 [6, 5, 3, (1)] + 3 = 17"
+}
+
+export function rollDice(expression: string): Promise<DiceRoll> {
+  return invoke<DiceRoll>('roll_dice', { expression });
+}
+
+// The normalised expression; rejects with the reason it can't be read.
+export function checkDice(expression: string): Promise<string> {
+  return invoke<string>('check_dice', { expression });
+}
+
+// From the LAN server to the DM window: a `PlayerRoll` (`src-tauri/src/lan.rs`).
+export const PLAYER_ROLL_EVENT = 'vtt-player-roll';
+const ROLL_ERROR_EVENT = 'vtt-roll-error';
+
+export interface PlayerRoll {
+  by: string;
+  roll: DiceRoll;
+}
+
+// Oldest rolls are dropped past this, so the history (and the player sync)
+// stays small over a long session.
+export const ROLL_HISTORY_LIMIT = 200;
+
+let rollCounter = 0;
+
+export function makeRollRecord(roll: DiceRoll, by: string, secret: boolean): RollRecord {
+  rollCounter += 1;
+  return { ...roll, id: `roll-${Date.now().toString(36)}-${rollCounter}`, by, secret, at: Date.now() };
+}
+
+export function appendRoll(rolls: RollRecord[], record: RollRecord): RollRecord[] {
+  return [...rolls, record].slice(-ROLL_HISTORY_LIMIT);
+}
+
+// A LAN player's roll: rolled by the server and added to the DM's history,
+// so it comes back like any other public roll.
+export function sendPlayerRoll(expression: string, by: string) {
+  sendRemote({ type: 'roll', expression, by });
+}
+
+// Why the server couldn't roll this player's last expression.
+export function onPlayerRollError(callback: (reason: string) => void): () => void {
+  return onRemoteEvent(ROLL_ERROR_EVENT, callback);
+}
@@ -0,0 +1,72 @@
+import { invoke } from '@tauri-apps/api/core';
+import { DrawingPoint, FogState, MapState, WallState } from './types';
+
+// Fog shape operations, done on the Rust side (`src-tauri/src/fog.rs`). Fog
+// goes both ways unpacked; only save files hold it packed, and packing is
+// Rust's job too.
+
+// Reveal (or hide) every cell within `radius` cells of `center`: the square
+// `modifyFog` paints, for callers that don't need it per pointer move.
+export async function fogBrush(
+  fog: FogState,
+  center: { row: number; col: number },
+  radius: number,
+  reveal: boolean
+): Promise<FogState> {
+  return await invoke<FogState>('fog_brush', {
+    fog,
+    center: [center.row, center.col],
+    radius,
+    reveal,
+  });
+}
+
+// Reveal (or hide) every cell between two corner cells, inclusive.
+export async function fogRect(
+  fog: FogState,
+  from: { row: number; col: number },
+  to: { row: number; col: number },
+  reveal: boolean
+): Promise<FogState> {
+  return await invoke<FogState>('fog_rect', {
+    fog,
+    from: [from.row, from.col],
+    to: [to.row, to.col],
+    reveal,
+  });
+}
+
+// Reveal (or hide) every cell whose centre is inside the polygon (map pixels).
+export async function fogPolygon(
+  fog: FogState,
+  points: DrawingPoint[],
+  map: Pick<MapState, 'gridSize' | 'gridOffsetX' | 'gridOffsetY'>,
+  reveal: boolean
+): Promise<FogState> {
+  return await invoke<FogState>('fog_polygon', {
+    fog,
+    points,
+    gridSize: map.gridSize,
+    gridOffsetX: map.gridOffsetX,
+    gridOffsetY: map.gridOffsetY,
+    reveal,
+  });
+}
+
+// Reveal everything the light sources can see past the walls. Only ever
+// clears fog; cells the party has already seen stay revealed.
+export async function revealLineOfSight(
+  fog: FogState,
+  walls: WallState,
+  map: Pick<MapState, 'gridSize' | 'gridOffsetX' | 'gridOffsetY' | 'imageWidth' | 'imageHeight'>
+): Promise<FogState> {
+  return await invoke<FogState>('reveal_line_of_sight', {
+    fog,
+    walls,
+    gridSize: map.gridSize,
+    gridOffsetX: map.gridOffsetX,
+    gridOffsetY: map.gridOffsetY,
+    width: map.imageWidth,
+    height: map.imageHeight,
+  });
+}
@@ -0,0 +1,72 @@
+import { invoke } from '@tauri-apps/api/core';
+import { PartyState } from './types';
+
+// Hex-crawl travel planning over a `.hexm` map, done in Rust
+// (`src-tauri/src/hexcrawl.rs`). Hexes are 1-based [col, row].
+
+export type HexCoord = [number, number];
+
+export interface TravelDay {
+  day: number;
+  entered: HexCoord[];
+  miles: number;
+  navigation: number | null; // d20 target; null when the day stayed on roads/rivers
+  travelChecks: number;
+  camp: HexCoord;
+  territory?: string;
+  campChecks: number;
+}
+
+export interface TravelPlan {
+  path: HexCoord[];
+  miles: number;
+  days: number; // fractional for the last part-day
+  dayPlans: TravelDay[];
+}
+
+export function planHexTravel(
+  path: string,
+  from: HexCoord,
+  to: HexCoord,
+  movement: number,
+  hexMiles?: number,
+): Promise<TravelPlan> {
+  return invoke<TravelPlan>('plan_hex_travel', { path, request: { from, to, movement, hexMiles } });
+}
+
+// Write the party's travel log as Markdown (hexes named from the `.hexm`).
+export async function exportTravelLog(mapPath: string, party: PartyState, path: string): Promise<void> {
+  await invoke('export_travel_log', { mapPath, party, path });
+}
+
+// A random encounter rolled by `roll_encounter` (`src-tauri/src/encounters.rs`)
+// on the tables in the `encounters` folder next to the map.
+export interface EncounterRoll {
+  hex: HexCoord;
+  terrain?: string;
+  territory?: string;
+  table: string;
+  chance?: { roll: number; target: number; sides: number };
+  encounter: {
+    name: string;
+    count?: number;
+    countDice?: string;
+    tables: string[]; // outermost first
+    note?: string;
+  } | null; // null when the chance roll came up empty
+  summary: string; // one line for the travel log
+}
+
+export function rollEncounter(mapPath: string, hex: HexCoord): Promise<EncounterRoll> {
+  return invoke<EncounterRoll>('roll_encounter', { mapPath, hex });
+}
+
+// "CC.RR" (as hex keys print them) or "C,R" -> [col, row].
+export function parseHexCoord(text: string): HexCoord | null {
+  const m = text.trim().match(/^(\d+)\s*[.,]\s*(\d+)$/);
+  return m ? [parseInt(m[1], 10), parseInt(m[2], 10)] : null;
+}
+
+export function formatHexCoord([col, row]: HexCoord): string {
+  return `${String(col).padStart(2, '0')}.${String(row).padStart(2, '0')}`;
+}
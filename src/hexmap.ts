@@ -1,4 +1,4 @@
-import { readFile } from '@tauri-apps/plugin-fs';
+import { invoke } from '@tauri-apps/api/core';
 
 // A `.hexm` file: hex-map metadata used to render a color-coded-by-terrain
 // hex map. Each hex may carry a link that opens its key in Obsidian (or any
@@ -68,32 +68,23 @@ const DEFAULT_RADIUS = 40;
 const DEFAULT_PAD = 6;
 const SQRT3 = Math.sqrt(3);
 
-function isHexMapFile(v: unknown): v is HexMapFile {
-  const f = v as Partial<HexMapFile> | null;
-  return (
-    !!f &&
-    f.format === 'vtt-hexmap' &&
-    typeof f.cols === 'number' &&
-    typeof f.rows === 'number' &&
-    !!f.terrains &&
-    Array.isArray(f.hexes)
-  );
+// A problem found in a `.hexm` file by the Rust parser (`src-tauri/src/hexm.rs`).
+export interface HexDiagnostic {
+  severity: 'error' | 'warning';
+  line: number; // 1-based
+  message: string;
 }
 
-// Read + validate a `.hexm` file. Throws on malformed input (matching
-// `loadCartographerBundle`) so problems surface instead of failing silently.
-export async function loadHexMapFile(path: string): Promise<HexMapFile> {
-  const text = new TextDecoder().decode(await readFile(path));
-  let parsed: unknown;
-  try {
-    parsed = JSON.parse(text);
-  } catch {
-    throw new Error('File is not valid JSON');
+// Read + check a `.hexm` file. Throws (matching `loadCartographerBundle`)
+// only when it can't be read as a hex map at all; problems inside a readable
+// file come back as `diagnostics` for the DM view to show.
+export async function loadHexMapFile(path: string): Promise<{ file: HexMapFile; diagnostics: HexDiagnostic[] }> {
+  const report = await invoke<{ map: HexMapFile | null; diagnostics: HexDiagnostic[] }>('load_hexmap', { path });
+  if (!report.map) {
+    const first = report.diagnostics[0];
+    throw new Error(first ? `line ${first.line}: ${first.message}` : 'Not a vtt-hexmap file');
   }
-  if (!isHexMapFile(parsed)) {
-    throw new Error('Not a vtt-hexmap file (need format/cols/rows/terrains/hexes)');
-  }
-  return parsed;
+  return { file: report.map, diagnostics: report.diagnostics };
 }
 
 // --- Geometry ---------------------------------------------------------------
@@ -229,6 +220,24 @@ export function hexAtPoint(meta: HexMapMeta, x: number, y: number): { col: numbe
   return null;
 }
 
+// The (up to 6) hexes next to 1-based (col, row): those whose centers are one
+// hex width away, which holds for both offset layouts.
+export function hexNeighbors(meta: HexMapMeta, col: number, row: number): Array<{ col: number; row: number }> {
+  const { cx, cy } = hexCenter(meta, col, row);
+  const spacing = SQRT3 * meta.hexRadius;
+  const out: Array<{ col: number; row: number }> = [];
+  for (let c = col - 1; c <= col + 1; c++) {
+    for (let r = row - 1; r <= row + 1; r++) {
+      if ((c === col && r === row) || c < 1 || r < 1 || c > meta.cols || r > meta.rows) continue;
+      const other = hexCenter(meta, c, r);
+      if (Math.abs(Math.hypot(other.cx - cx, other.cy - cy) - spacing) < 0.01 * spacing) {
+        out.push({ col: c, row: r });
+      }
+    }
+  }
+  return out;
+}
+
 function pointInPolygon(x: number, y: number, poly: Array<[number, number]>): boolean {
   let inside = false;
   for (let i = 0, j = poly.length - 1; i < poly.length; j = i++) {
@@ -246,82 +255,21 @@ export function linkForHex(meta: HexMapMeta, col: number, row: number): string |
   return (entry && entry.url) || meta.defaultUrl;
 }
 
-// Render the hex map to an SVG document string (used as the map image).
-// `showLabels` draws hex labels (DM view); the player view can hide them.
-export function renderHexMapSvg(file: HexMapFile, opts: { showLabels?: boolean } = {}): string {
-  const meta = buildHexMapMeta(file);
-  const { width, height, hexRadius: R, orientation } = meta;
-  const bg = file.background || '#14110d';
-  const defaultTerrain = file.defaultTerrain;
-  const showLabels = opts.showLabels !== false;
-
-  const byCoord = new Map<string, HexCell>();
-  for (const cell of file.hexes) byCoord.set(`${cell.col},${cell.row}`, cell);
-
-  const fillFor = (cell: HexCell | undefined): string => {
-    const key = cell?.terrain ?? defaultTerrain;
-    return (key && file.terrains[key]) || '#3a352c';
-  };
-
-  const parts: string[] = [];
-  parts.push(
-    `<svg xmlns="http://www.w3.org/2000/svg" width="${width}" height="${height}" viewBox="0 0 ${width} ${height}">`,
-  );
-  parts.push(`<rect width="${width}" height="${height}" fill="${bg}"/>`);
-
-  const labelSize = Math.max(8, Math.round(R * 0.42));
-  const labels: string[] = []; // drawn last so roads don't cover them
-  for (let col = 1; col <= file.cols; col++) {
-    for (let row = 1; row <= file.rows; row++) {
-      const cell = byCoord.get(`${col},${row}`);
-      const { cx, cy } = hexCenter(meta, col, row);
-      const pts = hexVertices(orientation, R, cx, cy)
-        .map(([px, py]) => `${px.toFixed(1)},${py.toFixed(1)}`)
-        .join(' ');
-      const fill = fillFor(cell);
-      parts.push(
-        `<polygon points="${pts}" fill="${fill}" stroke="#0008" stroke-width="1"/>`,
-      );
-      if (showLabels && cell?.label) {
-        labels.push(
-          `<text x="${cx.toFixed(1)}" y="${(cy + labelSize * 0.35).toFixed(1)}" font-family="sans-serif" font-size="${labelSize}" font-weight="bold" fill="#fff" text-anchor="middle" paint-order="stroke" stroke="#0009" stroke-width="2">${escapeXml(cell.label)}</text>`,
-        );
-      }
-    }
-  }
-
-  // Roads & rivers — polylines through hex centers, over terrain / under labels.
-  for (const route of file.roads ?? []) {
-    if (!route.path || route.path.length < 2) continue;
-    const poly = route.path
-      .map(([c, r]) => { const p = hexCenter(meta, c, r); return `${p.cx.toFixed(1)},${p.cy.toFixed(1)}`; })
-      .join(' ');
-    if (route.type === 'river') {
-      parts.push(`<polyline points="${poly}" fill="none" stroke="#2f5a86" stroke-width="${(R * 0.22).toFixed(1)}" stroke-linecap="round" stroke-linejoin="round" opacity="0.9"/>`);
-    } else {
-      // dark casing + dashed tan road for a legible "road" look
-      parts.push(`<polyline points="${poly}" fill="none" stroke="#2c2415" stroke-width="${(R * 0.30).toFixed(1)}" stroke-linecap="round" stroke-linejoin="round"/>`);
-      parts.push(`<polyline points="${poly}" fill="none" stroke="#decfa0" stroke-width="${(R * 0.15).toFixed(1)}" stroke-linecap="round" stroke-linejoin="round" stroke-dasharray="${(R * 0.5).toFixed(1)} ${(R * 0.3).toFixed(1)}"/>`);
-    }
-  }
-
-  parts.push(...labels);
-
-  if (file.title) {
-    parts.push(
-      `<text x="${meta.pad + 4}" y="${meta.pad + 18}" font-family="serif" font-size="18" fill="#eee">${escapeXml(file.title)}</text>`,
-    );
-  }
-  parts.push('</svg>');
-  return parts.join('');
-}
-
-function escapeXml(s: string): string {
-  return s
-    .replace(/&/g, '&amp;')
-    .replace(/</g, '&lt;')
-    .replace(/>/g, '&gt;')
-    .replace(/"/g, '&quot;');
+// Render the hex map (in Rust, `src-tauri/src/hexrender.rs`) to an object
+// URL for the image pipeline. `showLabels` draws hex labels (DM view); the
+// player view hides them.
+export async function renderHexMap(
+  path: string,
+  opts: { showLabels?: boolean; legend?: boolean; scale?: number; format?: 'svg' | 'png' } = {},
+): Promise<string> {
+  const format = opts.format ?? 'svg';
+  const bytes = await invoke<ArrayBuffer>('render_hexmap', {
+    path,
+    format,
+    options: { showLabels: opts.showLabels !== false, legend: !!opts.legend, scale: opts.scale ?? 1 },
+  });
+  const type = format === 'svg' ? 'image/svg+xml' : 'image/png';
+  return URL.createObjectURL(new Blob([bytes], { type }));
 }
 
 // --- Hover tooltip data (wilderness-travel rules) ---------------------------
@@ -1,4 +1,5 @@
-import { AppState, FogState, BlockState, Drawing } from './types';
+import { AppState, FogState, BlockState, Drawing, InitiativeEntity, InitiativeState, Token } from './types';
+import { removeEntities } from './initiative';
 
 // Base interface for undoable operations
 export interface Operation {
@@ -104,6 +105,54 @@ export function createBlockChangeOperation(previousBlocks: BlockState, newBlocks
   };
 }
 
+// Tokens placed, moved, edited or removed, along with any initiative entries
+// dropped because their token was
+export interface TokensChangeOperation extends Operation {
+  type: 'tokensChange';
+  previousTokens: Token[];
+  removedEntries: InitiativeEntity[];
+}
+
+export function createTokensChangeOperation(
+  previousTokens: Token[],
+  newTokens: Token[],
+  removedEntries: InitiativeEntity[] = []
+): TokensChangeOperation {
+  const removedIds = new Set(removedEntries.map(e => e.id));
+  // Where the roster stood before, to undo a turn that moved on because the
+  // removed entry had it.
+  let turnBefore: Pick<InitiativeState, 'turn' | 'round'> | null = null;
+  let turnAfter: Pick<InitiativeState, 'turn' | 'round'> | null = null;
+  return {
+    type: 'tokensChange',
+    previousTokens,
+    removedEntries,
+    apply(state: AppState): AppState {
+      if (removedIds.size === 0) return { ...state, tokens: newTokens };
+      const initiative = removeEntities(state.initiative, removedIds);
+      turnBefore = { turn: state.initiative.turn, round: state.initiative.round };
+      turnAfter = { turn: initiative.turn, round: initiative.round };
+      return { ...state, tokens: newTokens, initiative };
+    },
+    unapply(state: AppState): AppState {
+      if (removedIds.size === 0) return { ...state, tokens: this.previousTokens };
+      const present = new Set(state.initiative.entities.map(e => e.id));
+      const entities = [
+        ...state.initiative.entities,
+        ...this.removedEntries.filter(e => !present.has(e.id)),
+      ];
+      const unmoved = turnAfter !== null
+        && state.initiative.turn === turnAfter.turn
+        && state.initiative.round === turnAfter.round;
+      return {
+        ...state,
+        tokens: this.previousTokens,
+        initiative: { ...state.initiative, entities, ...(unmoved && turnBefore ? turnBefore : {}) },
+      };
+    },
+  };
+}
+
 // Fog reset (all cells fogged)
 export interface FogResetOperation extends Operation {
   type: 'fogReset';
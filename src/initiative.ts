@@ -1,29 +1,9 @@
-import { InitiativeEntity } from './types';
+import { Condition, InitiativeEntity, InitiativeState } from './types';
+import { rollDice } from './dice';
 
 // Default dice for a new entity when none is specified.
 export const DEFAULT_DICE = '1d6';
 
-const DICE_PATTERN = /^\s*(\d*)\s*d\s*(\d+)\s*([+-]\s*\d+)?\s*$/i;
-
-// Validate a dice spec like "1d6", "d20", "2d8+3", "1d20-1".
-export function isValidDiceSpec(spec: string): boolean {
-  return DICE_PATTERN.test(spec);
-}
-
-// Roll a dice spec. Returns 0 for an unparseable spec.
-export function rollDice(spec: string): number {
-  const match = DICE_PATTERN.exec(spec);
-  if (!match) return 0;
-  const count = match[1] ? parseInt(match[1], 10) : 1;
-  const sides = parseInt(match[2], 10);
-  const modifier = match[3] ? parseInt(match[3].replace(/\s/g, ''), 10) : 0;
-  let total = modifier;
-  for (let i = 0; i < count; i++) {
-    total += Math.floor(Math.random() * sides) + 1;
-  }
-  return total;
-}
-
 let idCounter = 0;
 function nextId(): string {
   idCounter += 1;
@@ -31,35 +11,150 @@ function nextId(): string {
 }
 
 // Build entities for a (possibly batched) add. A count > 1 produces numbered
-// entries: "bandit" x5 -> "bandit 1" ... "bandit 5". Each entry is rolled.
-export function createEntities(name: string, count: number, dice: string, isPc: boolean): InitiativeEntity[] {
+// entries: "bandit" x5 -> "bandit 1" ... "bandit 5". Each entry is rolled;
+// rejects if `dice` can't be read.
+export async function createEntities(
+  name: string,
+  count: number,
+  dice: string,
+  isPc: boolean,
+  dexMod = 0,
+): Promise<InitiativeEntity[]> {
   const trimmed = name.trim() || 'Entity';
-  const safeDice = isValidDiceSpec(dice) ? dice.trim() : DEFAULT_DICE;
+  const spec = dice.trim() || DEFAULT_DICE;
   const n = Math.max(1, Math.floor(count) || 1);
   const entities: InitiativeEntity[] = [];
   for (let i = 1; i <= n; i++) {
     entities.push({
       id: nextId(),
       name: n > 1 ? `${trimmed} ${i}` : trimmed,
-      dice: safeDice,
-      roll: rollDice(safeDice),
+      dice: spec,
+      roll: (await rollDice(spec)).total,
       isPc,
+      dexMod,
+      hp: null,
+      maxHp: null,
+      conditions: [],
     });
   }
   return entities;
 }
 
-// Re-roll initiative for every entity (start of a new battle or round).
-export function rerollAll(entities: InitiativeEntity[]): InitiativeEntity[] {
-  return entities.map((e) => ({ ...e, roll: rollDice(e.dice) }));
+// Re-roll initiative for every entity (start of a new battle or round). An
+// entity whose dice can't be read (e.g. from an old save) keeps no roll.
+export async function rerollAll(entities: InitiativeEntity[]): Promise<InitiativeEntity[]> {
+  return Promise.all(entities.map(async (e) => ({
+    ...e,
+    roll: await rollDice(e.dice).then((r) => r.total, () => null),
+  })));
 }
 
-// Sort a copy of the entities by initiative, highest first. Unrolled entries
-// (roll === null) sink to the bottom. Ties keep their relative order.
+// Sort a copy of the entities by initiative, highest first; ties go to the
+// higher dexterity modifier. Unrolled entries (roll === null) sink to the
+// bottom. Remaining ties keep their relative order.
 export function sortedByInitiative(entities: InitiativeEntity[]): InitiativeEntity[] {
   return [...entities].sort((a, b) => {
     const ra = a.roll ?? -Infinity;
     const rb = b.roll ?? -Infinity;
-    return rb - ra;
+    if (ra !== rb) return rb - ra;
+    return (b.dexMod ?? 0) - (a.dexMod ?? 0);
   });
 }
+
+// Turn order: only entities that have rolled take turns.
+function turnOrder(entities: InitiativeEntity[]): InitiativeEntity[] {
+  return sortedByInitiative(entities).filter((e) => e.roll !== null);
+}
+
+// Count an entity's conditions down by a round, dropping the ones that ran out.
+function tickConditions(entity: InitiativeEntity): InitiativeEntity {
+  const conditions = entity.conditions ?? [];
+  if (!conditions.some((c) => c.rounds !== null)) return entity;
+  return {
+    ...entity,
+    conditions: conditions
+      .map((c) => (c.rounds === null ? c : { ...c, rounds: c.rounds - 1 }))
+      .filter((c) => c.rounds === null || c.rounds > 0),
+  };
+}
+
+// Pass the turn on. Outside combat this starts round 1 at the top of the
+// order; otherwise the current entity's conditions tick down and the next
+// one goes, starting a new round after the last.
+export function nextTurn(initiative: InitiativeState): InitiativeState {
+  const order = turnOrder(initiative.entities);
+  if (order.length === 0) return initiative;
+  const index = order.findIndex((e) => e.id === initiative.turn);
+  if (initiative.turn === null || index < 0) {
+    return { ...initiative, turn: order[0].id, round: Math.max(1, initiative.round) };
+  }
+  const ending = initiative.turn;
+  const entities = initiative.entities.map((e) => (e.id === ending ? tickConditions(e) : e));
+  const wrapped = index === order.length - 1;
+  return {
+    ...initiative,
+    entities,
+    turn: order[wrapped ? 0 : index + 1].id,
+    round: initiative.round + (wrapped ? 1 : 0),
+  };
+}
+
+// Step back a turn (a mistake at the table). Conditions that already ticked
+// down stay as they are.
+export function previousTurn(initiative: InitiativeState): InitiativeState {
+  const order = turnOrder(initiative.entities);
+  const index = order.findIndex((e) => e.id === initiative.turn);
+  if (index < 0) return initiative;
+  if (index > 0) return { ...initiative, turn: order[index - 1].id };
+  if (initiative.round <= 1) return initiative;
+  return { ...initiative, turn: order[order.length - 1].id, round: initiative.round - 1 };
+}
+
+export function endCombat(initiative: InitiativeState): InitiativeState {
+  return { ...initiative, turn: null, round: 0 };
+}
+
+// Remove entities; if one of them had the turn, it passes to the next one
+// still standing (without ticking anything).
+export function removeEntities(initiative: InitiativeState, ids: Set<string>): InitiativeState {
+  const entities = initiative.entities.filter((e) => !ids.has(e.id));
+  if (initiative.turn === null || !ids.has(initiative.turn)) return { ...initiative, entities };
+  const order = turnOrder(initiative.entities);
+  const index = order.findIndex((e) => e.id === initiative.turn);
+  const after = [...order.slice(index + 1), ...order.slice(0, index)].find((e) => !ids.has(e.id));
+  if (!after) return { ...initiative, entities, turn: null, round: 0 };
+  const wrapped = order.indexOf(after) < index;
+  return { ...initiative, entities, turn: after.id, round: initiative.round + (wrapped ? 1 : 0) };
+}
+
+// HP as typed into the tracker: "12" sets it, "-7" / "+5" adjust it and
+// "12/30" sets current and max. Anything else leaves the entity unchanged.
+export function applyHpInput(entity: InitiativeEntity, input: string): InitiativeEntity {
+  const text = input.replace(/\s/g, '');
+  if (text === '') return { ...entity, hp: null, maxHp: null };
+  const full = /^(\d+)\/(\d+)$/.exec(text);
+  if (full) return { ...entity, hp: parseInt(full[1], 10), maxHp: parseInt(full[2], 10) };
+  const delta = /^([+-])(\d+)$/.exec(text);
+  if (delta) {
+    if (entity.hp == null) return entity;
+    const change = parseInt(delta[2], 10) * (delta[1] === '-' ? -1 : 1);
+    const hp = Math.max(0, entity.hp + change);
+    return { ...entity, hp: entity.maxHp != null ? Math.min(entity.maxHp, hp) : hp };
+  }
+  if (/^\d+$/.test(text)) return { ...entity, hp: parseInt(text, 10) };
+  return entity;
+}
+
+// A condition as typed: "Stunned 2" lasts two rounds, "Prone" until removed.
+export function parseCondition(input: string): Condition | null {
+  const match = /^(.*?)(?:\s+(\d+))?$/.exec(input.trim());
+  const name = match?.[1].trim();
+  if (!match || !name) return null;
+  const rounds = match[2] ? parseInt(match[2], 10) : null;
+  return { name, rounds: rounds && rounds > 0 ? rounds : null };
+}
+
+export function formatHp(entity: InitiativeEntity): string {
+  if (entity.hp == null) return '';
+  return entity.maxHp != null ? `${entity.hp}/${entity.maxHp}` : String(entity.hp);
+}
@@ -0,0 +1,114 @@
+import { invoke } from '@tauri-apps/api/core';
+import { readFile } from '@tauri-apps/plugin-fs';
+import { UvttMap, WallState } from './types';
+import { loadCartographerBundle, bundleSrcToObjectUrl } from './cartographer';
+import {
+  loadHexMapFile,
+  buildHexMapMeta,
+  renderHexMap,
+  buildHexLookup,
+  type HexMapMeta,
+  type HexCellData,
+  type HexDiagnostic,
+} from './hexmap';
+import { loadMapTiles, overviewUrl, type TileSet } from './tiles';
+
+// Emitted by `src-tauri/src/watch.rs` (payload: the path) when the loaded
+// map file changes on disk.
+export const MAP_CHANGED_EVENT = 'vtt-map-changed';
+
+// Plain image files, which the Rust side can decode (grid detection, tiles).
+// Bundles and `.dd2vtt` maps carry their grid, and `.hexm` maps have none.
+export function isRasterMap(path: string | null): boolean {
+  return !!path && /\.(png|jpe?g|webp|gif)$/i.test(path);
+}
+
+// Everything the DM view needs from a map file on disk, whatever its kind.
+// Used by Load Map and again when the watched file changes (`watch.rs`).
+export interface LoadedMapFile {
+  imageUrl: string;
+  playerImageUrl: string | null; // separate player image, when the file has one
+  imageWidth: number;
+  imageHeight: number;
+  gridSize: number | null; // set when the file dictates the grid
+  ftPerCell: number | null; // set when the file says how far a cell is
+  hexmap: HexMapMeta | null;
+  hexLookup: Record<string, HexCellData> | null; // hover tooltip data (DM-only)
+  hexDiagnostics: HexDiagnostic[];
+  walls: WallState | null; // imported walls/doors/lights (UVTT, or a bundle converted from one)
+  tiles: TileSet | null; // tile pyramid of a plain image file
+}
+
+export async function loadMapFile(path: string): Promise<LoadedMapFile> {
+  const lower = path.toLowerCase();
+  const loaded: Omit<LoadedMapFile, 'imageWidth' | 'imageHeight'> = {
+    imageUrl: '',
+    playerImageUrl: null,
+    gridSize: null,
+    ftPerCell: null,
+    hexmap: null,
+    hexLookup: null,
+    hexDiagnostics: [],
+    walls: null,
+    tiles: null,
+  };
+  let imageKind = 'map';
+
+  if (lower.endsWith('.hexm')) {
+    // A `.hexm` file is hex-map metadata: rendered to an SVG (color-coded by
+    // terrain) for the visual, keeping the geometry + per-hex links so
+    // clicking a hex opens its key (e.g. in Obsidian). The DM SVG shows hex
+    // labels; the player SVG hides them.
+    const { file, diagnostics } = await loadHexMapFile(path);
+    loaded.hexDiagnostics = diagnostics;
+    loaded.hexmap = buildHexMapMeta(file);
+    loaded.imageUrl = await renderHexMap(path, { showLabels: true });
+    loaded.playerImageUrl = await renderHexMap(path, { showLabels: false });
+    // Hex maps don't use the square grid; size fog cells to the hex.
+    loaded.gridSize = loaded.hexmap.hexRadius;
+    loaded.hexLookup = buildHexLookup(file);
+  } else if (lower.endsWith('.dd2vtt') || lower.endsWith('.uvtt')) {
+    // A Universal VTT export embeds the image with its grid size, walls,
+    // doors and lights; parsed on the Rust side.
+    const uvtt = await invoke<UvttMap>('load_uvtt', { path });
+    loaded.imageUrl = bundleSrcToObjectUrl(uvtt.image);
+    loaded.gridSize = uvtt.gridSize;
+    loaded.walls = uvtt.walls;
+  } else if (lower.endsWith('.json')) {
+    // A cartographer "both views" bundle carries a GM and a player image:
+    // the DM sees the GM image, the player window the player image.
+    const bundle = await loadCartographerBundle(path);
+    loaded.imageUrl = bundleSrcToObjectUrl(bundle.gm);
+    loaded.playerImageUrl = bundleSrcToObjectUrl(bundle.player);
+    // The bundle's cell size is authoritative — align the VTT grid to it.
+    loaded.gridSize = bundle.grid?.cell_size ?? null;
+    loaded.ftPerCell = bundle.grid?.ft_per_cell ?? null;
+    loaded.walls = bundle.walls ?? null;
+    imageKind = 'GM';
+  } else {
+    // The views draw plain images from tiles (see `tiles.ts`), so the file
+    // itself is only read here when it can't be tiled: an image the Rust
+    // side can't decode is left to the webview.
+    const tiles = isRasterMap(path)
+      ? await loadMapTiles(path).catch((err) => {
+          console.error('Failed to tile map image:', err);
+          return null;
+        })
+      : null;
+    if (tiles) {
+      // The size comes with the tiles, so the webview never decodes it whole.
+      const size = { imageWidth: tiles.width, imageHeight: tiles.height };
+      return { ...loaded, ...size, tiles, imageUrl: overviewUrl(tiles) };
+    }
+    loaded.imageUrl = URL.createObjectURL(new Blob([await readFile(path)]));
+  }
+
+  // Image dimensions (works for raster and SVG alike)
+  const { width, height } = await new Promise<{ width: number; height: number }>((resolve, reject) => {
+    const img = new Image();
+    img.onload = () => resolve({ width: img.width, height: img.height });
+    img.onerror = () => reject(new Error(`Failed to decode the ${imageKind} image`));
+    img.src = loaded.imageUrl;
+  });
+  return { ...loaded, imageWidth: width, imageHeight: height };
+}
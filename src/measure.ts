@@ -0,0 +1,88 @@
+import { invoke } from '@tauri-apps/api/core';
+import { DrawingPoint, FogState, MapState } from './types';
+import { hexCenter, hexVertices } from './hexmap';
+
+// The ruler and area-of-effect templates. Distances and covered cells are
+// worked out in Rust (`src-tauri/src/measure.rs`); this side only draws them.
+
+export type DistanceRule = 'alternating' | 'euclidean' | 'chebyshev'; // alternating = 5-10-5
+export type TemplateShape = 'cone' | 'sphere' | 'line' | 'cube';
+export type MeasureMode = 'ruler' | TemplateShape;
+
+export const DEFAULT_FT_PER_CELL = 5;
+
+export const DISTANCE_RULES: Array<{ value: DistanceRule; label: string }> = [
+  { value: 'alternating', label: '5-10-5' },
+  { value: 'euclidean', label: 'Euclidean' },
+  { value: 'chebyshev', label: 'Chebyshev' },
+];
+
+// `Board` in `measure.rs`: the square grid, or the hex grid on `.hexm` maps.
+type Board =
+  | { kind: 'square'; size: number; offsetX: number; offsetY: number; cols: number; rows: number }
+  | { kind: 'hex'; orientation: 'flat' | 'pointy'; radius: number; cols: number; rows: number };
+
+export interface TemplateArea {
+  origin: DrawingPoint; // snapped to the grid
+  cells: Array<[number, number]>; // [col, row]; 0-based squares, 1-based hexes
+}
+
+function boardFor(map: MapState, fog: FogState): Board {
+  const hex = map.hexmap;
+  if (hex) return { kind: 'hex', orientation: hex.orientation, radius: hex.hexRadius, cols: hex.cols, rows: hex.rows };
+  return {
+    kind: 'square',
+    size: map.gridSize || 50,
+    offsetX: map.gridOffsetX,
+    offsetY: map.gridOffsetY,
+    cols: fog.cols,
+    rows: fog.rows,
+  };
+}
+
+export async function measureDistance(
+  map: MapState,
+  fog: FogState,
+  rule: DistanceRule,
+  from: DrawingPoint,
+  to: DrawingPoint,
+): Promise<number> {
+  const { feet } = await invoke<{ cells: number; feet: number }>('measure_distance', {
+    board: boardFor(map, fog),
+    rule,
+    ftPerCell: map.ftPerCell,
+    from,
+    to,
+  });
+  return feet;
+}
+
+export async function templateArea(
+  map: MapState,
+  fog: FogState,
+  rule: DistanceRule,
+  template: { shape: TemplateShape; origin: DrawingPoint; toward: DrawingPoint; sizeFt: number },
+): Promise<TemplateArea> {
+  return invoke<TemplateArea>('template_area', {
+    board: boardFor(map, fog),
+    rule,
+    ftPerCell: map.ftPerCell,
+    template,
+  });
+}
+
+export function formatFeet(feet: number): string {
+  return `${Number.isInteger(feet) ? feet : feet.toFixed(1)} ft`;
+}
+
+// Outline of a template cell in map pixels, as flat [x, y, ...] points.
+export function cellOutline(map: MapState, [col, row]: [number, number]): number[] {
+  if (map.hexmap) {
+    const { cx, cy } = hexCenter(map.hexmap, col, row);
+    return hexVertices(map.hexmap.orientation, map.hexmap.hexRadius, cx, cy).flat();
+  }
+  const size = map.gridSize || 50;
+  const x = map.gridOffsetX + col * size;
+  const y = map.gridOffsetY + row * size;
+  return [x, y, x + size, y, x + size, y + size, x, y + size];
+}
@@ -1,20 +1,22 @@
 import { readTextFile, writeTextFile, mkdir, exists } from '@tauri-apps/plugin-fs';
 import { appDataDir, join } from '@tauri-apps/api/path';
-import { AppState, SavedMapState, InitiativeEntity } from './types';
-
-// Generate a hash from the map file path for unique save file naming
-function hashPath(path: string): string {
-  // Simple hash function for creating a unique filename from the path
-  let hash = 0;
-  for (let i = 0; i < path.length; i++) {
-    const char = path.charCodeAt(i);
-    hash = ((hash << 5) - hash) + char;
-    hash = hash & hash; // Convert to 32-bit integer
+import { invoke } from '@tauri-apps/api/core';
+import { AppState, SavedMapState, InitiativeState, RollRecord, SnapshotMeta } from './types';
+import { ROLL_HISTORY_LIMIT } from './dice';
+
+// Error returned by the Rust persistence commands (`PersistError` in
+// `src-tauri/src/persistence.rs`).
+export interface PersistError {
+  kind: 'appDataDir' | 'io' | 'corrupt' | 'migration' | 'notFound';
+  message: string;
+}
+
+// Human-readable message for a rejected persistence command.
+export function describePersistError(err: unknown): string {
+  if (err && typeof err === 'object' && 'message' in err) {
+    return String((err as PersistError).message);
   }
-  // Also include the filename for readability
-  const filename = path.split('/').pop()?.replace(/\.[^/.]+$/, '') || 'map';
-  const safeName = filename.replace(/[^a-zA-Z0-9-_]/g, '_').slice(0, 50);
-  return `${safeName}_${Math.abs(hash).toString(16)}`;
+  return String(err);
 }
 
 // Get the save directory path
@@ -23,13 +25,6 @@ async function getSaveDir(): Promise<string> {
   return await join(appData, 'maps');
 }
 
-// Generate the save file path for a map (stored in app data directory)
-async function getSaveFilePath(mapFilePath: string): Promise<string> {
-  const saveDir = await getSaveDir();
-  const hash = hashPath(mapFilePath);
-  return await join(saveDir, `${hash}.json`);
-}
-
 // Convert app state to saved state
 function toSavedState(state: AppState): SavedMapState | null {
   if (!state.map.filePath) return null;
@@ -44,9 +39,12 @@ function toSavedState(state: AppState): SavedMapState | null {
     gridOpacity: state.map.gridOpacity,
     gridOffsetX: state.map.gridOffsetX,
     gridOffsetY: state.map.gridOffsetY,
-    fog: state.fog,
+    fog: state.fog, // packed on the Rust side
     drawings: state.drawings,
     blocks: state.blocks,
+    walls: state.walls,
+    party: state.party,
+    tokens: state.tokens,
     view: state.view,
     playerViewOffset: state.playerViewOffset,
     calibration: state.calibration,
@@ -62,73 +60,137 @@ async function ensureSaveDir(): Promise<void> {
   }
 }
 
-// Save state to disk
+// Save state to disk. The Rust side writes atomically (temp file + rename)
+// and keeps rotated backups; failures reject with a `PersistError`.
 export async function saveMapState(state: AppState): Promise<void> {
   const savedState = toSavedState(state);
-  if (!savedState) {
-    console.log('Cannot save: no filePath in state');
-    return;
-  }
+  if (!savedState) return;
+  await invoke('save_map_state', { state: savedState });
+}
 
-  try {
-    await ensureSaveDir();
-    const saveFilePath = await getSaveFilePath(savedState.filePath);
-    const json = JSON.stringify(savedState, null, 2);
+// A loaded save (`LoadedSave` in `persistence.rs`). `recovered` says what was
+// wrong with the save file when the state came from one of its backups.
+export interface LoadedSave {
+  state: SavedMapState;
+  recovered: string | null;
+}
 
-    console.log('Attempting to save state to:', saveFilePath);
+// Load saved state from disk. Older save files are upgraded to the current
+// schema on the Rust side (`migrate.rs`), so the result always has every
+// field. Resolves to null when the map has never been saved; rejects with a
+// `PersistError` if the save (and every backup) is bad.
+export async function loadMapState(mapFilePath: string): Promise<LoadedSave | null> {
+  return await invoke<LoadedSave | null>('load_map_state', { mapFilePath });
+}
 
-    await writeTextFile(saveFilePath, json);
-    console.log('Successfully saved state to:', saveFilePath);
-  } catch (err) {
-    console.error('Failed to save state:', err);
-    console.error('Error details:', JSON.stringify(err, Object.getOwnPropertyNames(err)));
-  }
+// What a map file is known as (`map_identity` in `lib.rs`): its content
+// fingerprint, which follows it through moves and renames, and the save file
+// it resolves to (null if it has never been saved).
+export interface MapIdentity {
+  identity: { sha256: string; size: number };
+  saveFile: string | null;
 }
 
-// Load saved state from disk
-export async function loadMapState(mapFilePath: string): Promise<SavedMapState | null> {
-  try {
-    const saveFilePath = await getSaveFilePath(mapFilePath);
-    console.log('Attempting to load state from:', saveFilePath);
+export async function mapIdentity(path: string): Promise<MapIdentity> {
+  return await invoke<MapIdentity>('map_identity', { path });
+}
 
-    const json = await readTextFile(saveFilePath);
-    const savedState = JSON.parse(json) as SavedMapState;
-    console.log('Loaded saved state from:', saveFilePath);
-    return savedState;
-  } catch (err) {
-    // File doesn't exist or couldn't be read - that's fine, just means no saved state
-    console.log('No saved state found for:', mapFilePath);
-    return null;
-  }
+// --- Named snapshots (per map) ---
+// Snapshots live next to the map's save file on the Rust side
+// (`snapshots.rs`); all of these reject with a `PersistError`.
+
+export async function listSnapshots(mapFilePath: string): Promise<SnapshotMeta[]> {
+  return await invoke<SnapshotMeta[]>('list_snapshots', { mapFilePath });
+}
+
+export async function createSnapshot(state: AppState, name: string, thumbnail: string | null): Promise<SnapshotMeta | null> {
+  const savedState = toSavedState(state);
+  if (!savedState) return null;
+  return await invoke<SnapshotMeta>('create_snapshot', { state: savedState, name, thumbnail });
+}
+
+export async function renameSnapshot(mapFilePath: string, id: string, name: string): Promise<SnapshotMeta> {
+  return await invoke<SnapshotMeta>('rename_snapshot', { mapFilePath, id, name });
+}
+
+export async function duplicateSnapshot(mapFilePath: string, id: string, name: string): Promise<SnapshotMeta> {
+  return await invoke<SnapshotMeta>('duplicate_snapshot', { mapFilePath, id, name });
+}
+
+export async function deleteSnapshot(mapFilePath: string, id: string): Promise<void> {
+  await invoke('delete_snapshot', { mapFilePath, id });
+}
+
+// The snapshot's state (migrated like a normal save), to pass to
+// `applySavedState`.
+export async function restoreSnapshot(mapFilePath: string, id: string): Promise<SavedMapState> {
+  return await invoke<SavedMapState>('restore_snapshot', { mapFilePath, id });
 }
 
 // --- Initiative roster (global, not per-map) ---
 // The initiative roster persists between sessions independent of which map is
-// loaded, so it lives in its own file in the app data directory.
+// loaded, so it lives in its own file in the app data directory, along with
+// whose turn it is so a fight survives a restart.
+
+export type SavedInitiative = Pick<InitiativeState, 'entities' | 'turn' | 'round'>;
 
 async function getInitiativeFilePath(): Promise<string> {
   const appData = await appDataDir();
   return await join(appData, 'initiative.json');
 }
 
-export async function saveInitiative(entities: InitiativeEntity[]): Promise<void> {
+export async function saveInitiative({ entities, turn, round }: SavedInitiative): Promise<void> {
   try {
     await ensureSaveDir(); // also ensures the app data dir exists
     const filePath = await getInitiativeFilePath();
-    await writeTextFile(filePath, JSON.stringify({ entities }, null, 2));
+    await writeTextFile(filePath, JSON.stringify({ entities, turn, round }, null, 2));
   } catch (err) {
     console.error('Failed to save initiative:', err);
   }
 }
 
-export async function loadInitiative(): Promise<InitiativeEntity[]> {
+export async function loadInitiative(): Promise<SavedInitiative> {
   try {
     const filePath = await getInitiativeFilePath();
     const json = await readTextFile(filePath);
-    const parsed = JSON.parse(json) as { entities?: InitiativeEntity[] };
-    return Array.isArray(parsed.entities) ? parsed.entities : [];
+    const parsed = JSON.parse(json) as Partial<SavedInitiative>;
+    const entities = Array.isArray(parsed.entities) ? parsed.entities : [];
+    // Rosters saved before turn tracking have no turn; a stale one is dropped.
+    const turn = entities.some((e) => e.id === parsed.turn) ? parsed.turn ?? null : null;
+    return { entities, turn, round: turn ? parsed.round ?? 1 : 0 };
   } catch (err) {
     // No saved roster yet — start empty.
+    return { entities: [], turn: null, round: 0 };
+  }
+}
+
+// --- Dice roll history (global, per session) ---
+// Like the roster, the tray's history outlives any one map; it lasts until
+// the DM starts a new session (clears it).
+
+async function getRollsFilePath(): Promise<string> {
+  const appData = await appDataDir();
+  return await join(appData, 'dice-rolls.json');
+}
+
+// Written by the Rust side (`save_rolls`), atomically and with backups like
+// a map save, trimmed to the newest `ROLL_HISTORY_LIMIT` rolls.
+export async function saveRolls(rolls: RollRecord[]): Promise<void> {
+  try {
+    await invoke('save_rolls', { rolls });
+  } catch (err) {
+    console.error('Failed to save dice rolls:', describePersistError(err));
+  }
+}
+
+export async function loadRolls(): Promise<RollRecord[]> {
+  try {
+    const filePath = await getRollsFilePath();
+    const json = await readTextFile(filePath);
+    const parsed = JSON.parse(json) as { rolls?: RollRecord[] };
+    return Array.isArray(parsed.rolls) ? parsed.rolls.slice(-ROLL_HISTORY_LIMIT) : [];
+  } catch (err) {
+    // No history yet — start empty.
     return [];
   }
 }
@@ -152,24 +214,25 @@ export function applySavedState(
       gridVisible: savedState.gridVisible,
       gridColor: savedState.gridColor,
       gridOpacity: savedState.gridOpacity,
-      gridOffsetX: savedState.gridOffsetX ?? 0,
-      gridOffsetY: savedState.gridOffsetY ?? 0,
+      gridOffsetX: savedState.gridOffsetX,
+      gridOffsetY: savedState.gridOffsetY,
       // Re-derived from the `.hexm` file by the loader on each open; the
       // caller (DMView) overrides this after applying saved state.
       hexmap: null,
+      ftPerCell: currentState.map.ftPerCell, // also comes from the map file
+      tiles: currentState.map.tiles, // likewise
     },
     fog: savedState.fog,
     drawings: savedState.drawings,
-    // Handle both new BlockState format and legacy array format
-    blocks: savedState.blocks && 'cells' in savedState.blocks
-      ? savedState.blocks
-      : { cells: {} },
+    blocks: savedState.blocks,
+    walls: savedState.walls ?? { walls: [], lights: [] },
+    party: savedState.party ?? { position: null, day: 1, log: [], encounters: [] },
+    tokens: savedState.tokens ?? [],
     laserPoints: [], // Laser is temporary, never persisted
+    ruler: null,
+    templates: [],
     view: savedState.view,
     playerViewOffset: savedState.playerViewOffset,
-    calibration: {
-      pixelsPerInch: savedState.calibration.pixelsPerInch,
-      savedPixelsPerInch: savedState.calibration.savedPixelsPerInch ?? savedState.calibration.pixelsPerInch,
-    },
+    calibration: savedState.calibration,
   };
 }
@@ -0,0 +1,68 @@
+// Client for the LAN player server (`src-tauri/src/lan.rs`). A browser on
+// another device loads the app from that server and receives the same
+// full-state, delta and viewport events the player window gets, wrapped as
+// `{ event, payload }` messages over a WebSocket.
+
+type Handler = (payload: unknown) => void;
+
+const RECONNECT_DELAY_MS = 2000;
+
+const handlers = new Map<string, Set<Handler>>();
+let socket: WebSocket | null = null;
+
+// The join code from `?code=` (the URL shown in the DM view includes it),
+// asking once if it's missing.
+function joinCode(): string {
+  const params = new URLSearchParams(window.location.search);
+  let code = params.get('code');
+  if (!code) {
+    code = window.prompt('Join code (shown in the DM view):')?.trim().toUpperCase() ?? '';
+    params.set('code', code);
+    window.history.replaceState(null, '', `${window.location.pathname}?${params}`);
+  }
+  return code;
+}
+
+// `path` on the LAN server, with the join code it asks for.
+export function lanUrl(path: string): string {
+  return `${path}?code=${encodeURIComponent(joinCode())}`;
+}
+
+function connect() {
+  const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+  const url = `${protocol}//${window.location.host}/ws?code=${encodeURIComponent(joinCode())}`;
+  socket = new WebSocket(url);
+
+  socket.onmessage = (message) => {
+    try {
+      const { event, payload } = JSON.parse(message.data) as { event: string; payload: unknown };
+      handlers.get(event)?.forEach((handler) => handler(payload));
+    } catch (err) {
+      console.error('Bad message from LAN server:', err);
+    }
+  };
+
+  // Covers Wi-Fi drops and the DM restarting the server. A wrong join code
+  // also lands here, so it keeps retrying until the code is fixed in the URL.
+  socket.onclose = () => {
+    socket = null;
+    window.setTimeout(connect, RECONNECT_DELAY_MS);
+  };
+}
+
+// Send a message to the server (e.g. `{ type: 'resync' }`). Dropped while
+// disconnected; the server sends the full state on reconnect anyway.
+export function sendRemote(message: object) {
+  if (socket?.readyState === WebSocket.OPEN) socket.send(JSON.stringify(message));
+}
+
+// Subscribe to an event from the DM; connects on first use.
+export function onRemoteEvent<T>(event: string, callback: (payload: T) => void): () => void {
+  const handler = callback as Handler;
+  if (!handlers.has(event)) handlers.set(event, new Set());
+  handlers.get(event)!.add(handler);
+  if (!socket) connect();
+  return () => {
+    handlers.get(event)?.delete(handler);
+  };
+}
@@ -1,9 +1,19 @@
 import { emit, listen } from '@tauri-apps/api/event';
+import { invoke, isTauri } from '@tauri-apps/api/core';
+import { onRemoteEvent, sendRemote } from './remote';
+import { diffState, applyPatches, FullState, StateDelta } from './sync';
 import { AppState, FogState, BlockState, ToolState, PlayerViewport } from './types';
+import { DEFAULT_TOKEN_TEMPLATE } from './tokens';
+import { DEFAULT_FT_PER_CELL } from './measure';
 
 const SYNC_EVENT = 'vtt-state-sync';
+const DELTA_EVENT = 'vtt-state-delta';
 const VIEWPORT_SYNC_EVENT = 'vtt-viewport-sync';
 
+// True in a browser connected to the LAN player server rather than a Tauri
+// window; sync events then arrive over its WebSocket instead of Tauri events.
+export const isRemotePlayer = !isTauri();
+
 // Default state
 export const createDefaultState = (): AppState => ({
   map: {
@@ -19,6 +29,8 @@ export const createDefaultState = (): AppState => ({
     gridOffsetX: 0,
     gridOffsetY: 0,
     hexmap: null,
+    ftPerCell: DEFAULT_FT_PER_CELL,
+    tiles: null,
   },
   fog: {
     cells: [],
@@ -45,7 +57,15 @@ export const createDefaultState = (): AppState => ({
   initiative: {
     visible: false,
     entities: [],
+    turn: null,
+    round: 0,
   },
+  walls: { walls: [], lights: [] },
+  party: { position: null, day: 1, log: [], encounters: [] },
+  tokens: [],
+  ruler: null,
+  templates: [],
+  rolls: [],
 });
 
 export const createDefaultToolState = (): ToolState => ({
@@ -55,6 +75,12 @@ export const createDefaultToolState = (): ToolState => ({
   drawStrokeWidth: 3,
   laserColor: '#ff0000', // Red
   blockColor: '#ffffff', // White
+  sightRadius: 0, // Unlimited
+  tokenTemplate: DEFAULT_TOKEN_TEMPLATE,
+  measureMode: 'ruler',
+  templateSizeFt: 20,
+  templateColor: '#ff9800', // Orange
+  distanceRule: 'alternating',
 });
 
 // Initialize fog grid based on map dimensions
@@ -66,33 +92,107 @@ export function initializeFog(imageWidth: number, imageHeight: number, gridSize:
   return { cells, cols, rows };
 }
 
-// Sync state to other windows
+// Fit existing fog to a map whose image changed size (a reloaded file):
+// cells that still exist keep their state, new ones start fogged.
+export function resizeFog(fog: FogState, imageWidth: number, imageHeight: number, gridSize: number): FogState {
+  const sized = initializeFog(imageWidth, imageHeight, gridSize);
+  if (sized.cols === fog.cols && sized.rows === fog.rows) return fog;
+  const cells = sized.cells.map((row, r) => row.map((fogged, c) => fog.cells[r]?.[c] ?? fogged));
+  return { cells, cols: sized.cols, rows: sized.rows };
+}
+
+// The state last sent to the sync hub; null until the first (full) sync.
+let lastSynced: AppState | null = null;
+
+// Sync state to the player window (and LAN clients) through the Rust sync
+// hub (`sync.rs`): a full state the first time, then only patches. The hub
+// strips what players mustn't see (`projection.rs`) before forwarding, so
+// the full DM state never reaches another webview.
 export async function syncState(state: AppState): Promise<void> {
-  await emit(SYNC_EVENT, state);
+  const prev = lastSynced;
+  lastSynced = state;
+  if (!prev) {
+    await invoke('sync_player_state', { state });
+    return;
+  }
+  const patches = diffState(prev, state);
+  if (patches.length === 0) return;
+  try {
+    await invoke('sync_player_patches', { patches });
+  } catch (err) {
+    // The hub has nothing to patch (e.g. the first full sync failed).
+    console.warn('Delta sync failed, sending full state:', err);
+    await invoke('sync_player_state', { state });
+  }
 }
 
-// Listen for state updates from other windows
-export function onStateSync(callback: (state: AppState) => void): () => void {
-  let unlisten: (() => void) | null = null;
+// Follow the DM's (player-safe) state: full states replace, numbered deltas
+// patch, and a gap in the numbering asks the hub for a full resync.
+export function followPlayerState(callback: (state: AppState) => void): () => void {
+  let state: AppState | null = null;
+  let seq = 0;
 
-  listen<AppState>(SYNC_EVENT, (event) => {
-    callback(event.payload);
-  }).then((fn) => {
-    unlisten = fn;
+  const onFull = (full: FullState) => {
+    state = full.state;
+    seq = full.seq;
+    callback(state);
+  };
+  const onDelta = (delta: StateDelta) => {
+    if (delta.seq <= seq) return; // already covered by a newer full state
+    if (!state || delta.seq !== seq + 1) {
+      resync();
+      return;
+    }
+    state = applyPatches(state, delta.patches);
+    seq = delta.seq;
+    callback(state);
+  };
+  const resync = () => {
+    if (isRemotePlayer) {
+      sendRemote({ type: 'resync' });
+    } else {
+      invoke<FullState | null>('player_state').then((full) => full && onFull(full));
+    }
+  };
+
+  if (isRemotePlayer) {
+    // The LAN server sends the full state as soon as the socket connects.
+    const offFull = onRemoteEvent(SYNC_EVENT, onFull);
+    const offDelta = onRemoteEvent(DELTA_EVENT, onDelta);
+    return () => {
+      offFull();
+      offDelta();
+    };
+  }
+
+  const unlisteners: Array<() => void> = [];
+  let disposed = false;
+  Promise.all([
+    listen<FullState>(SYNC_EVENT, (event) => onFull(event.payload)),
+    listen<StateDelta>(DELTA_EVENT, (event) => onDelta(event.payload)),
+  ]).then((fns) => {
+    if (disposed) fns.forEach((fn) => fn());
+    else unlisteners.push(...fns);
+    resync(); // pick up whatever the DM already has
   });
 
   return () => {
-    if (unlisten) unlisten();
+    disposed = true;
+    unlisteners.forEach((fn) => fn());
   };
 }
 
 // Sync player viewport to DM
 export async function syncPlayerViewport(viewport: PlayerViewport): Promise<void> {
+  // Only the player window drives the DM's viewport outline.
+  if (isRemotePlayer) return;
   await emit(VIEWPORT_SYNC_EVENT, viewport);
 }
 
 // Listen for player viewport updates
 export function onViewportSync(callback: (viewport: PlayerViewport) => void): () => void {
+  if (isRemotePlayer) return onRemoteEvent(VIEWPORT_SYNC_EVENT, callback);
+
   let unlisten: (() => void) | null = null;
 
   listen<PlayerViewport>(VIEWPORT_SYNC_EVENT, (event) => {
@@ -0,0 +1,170 @@
+import { AppState, Drawing, DrawingPoint, FogState, RollRecord, Token } from './types';
+import { appendRoll, ROLL_HISTORY_LIMIT } from './dice';
+
+// Typed state patches, mirroring `StatePatch` in `src-tauri/src/sync.rs`.
+// The DM window sends the patches between its last synced state and the
+// current one; the player side applies the (projected) patches it gets back.
+
+export interface FogCell {
+  row: number;
+  col: number;
+  fogged: boolean;
+}
+
+export type StatePatch =
+  | { type: 'fogCells'; cells: FogCell[] }
+  | { type: 'drawingAdded'; drawing: Drawing }
+  | { type: 'drawingRemoved'; id: string }
+  | { type: 'laser'; points: DrawingPoint[] }
+  | { type: 'initiative'; initiative: AppState['initiative'] }
+  | { type: 'rollAdded'; roll: RollRecord }
+  | { type: 'tokenUpserted'; token: Token }
+  | { type: 'tokenRemoved'; id: string }
+  | { type: 'set'; field: string; value: unknown };
+
+export interface StateDelta {
+  seq: number;
+  patches: StatePatch[];
+}
+
+export interface FullState {
+  seq: number;
+  state: AppState;
+}
+
+// Changed fog cells, or null when the grid was resized (or so much changed
+// that resending the whole fog is smaller). Rows modifyFog didn't touch are
+// still copies, so every row is compared.
+function diffFog(prev: FogState, next: FogState): FogCell[] | null {
+  if (prev.rows !== next.rows || prev.cols !== next.cols) return null;
+  const cells: FogCell[] = [];
+  for (let row = 0; row < next.rows; row++) {
+    const a = prev.cells[row];
+    const b = next.cells[row];
+    if (a === b) continue;
+    for (let col = 0; col < next.cols; col++) {
+      if (a?.[col] !== b?.[col]) cells.push({ row, col, fogged: !!b?.[col] });
+    }
+  }
+  // A changed cell costs ~6x a plain `true`/`false` on the wire.
+  return cells.length * 6 <= next.rows * next.cols ? cells : null;
+}
+
+function diffDrawings(prev: Drawing[], next: Drawing[]): StatePatch[] | null {
+  if (next.length > prev.length && prev.every((d, i) => d === next[i])) {
+    return next.slice(prev.length).map((drawing) => ({ type: 'drawingAdded', drawing }));
+  }
+  if (next.length + 1 === prev.length) {
+    const removed = prev.find((d) => !next.includes(d));
+    if (removed && prev.filter((d) => d !== removed).every((d, i) => d === next[i])) {
+      return [{ type: 'drawingRemoved', id: removed.id }];
+    }
+  }
+  return null;
+}
+
+// Rolls are only appended, the oldest dropping off once there are
+// `ROLL_HISTORY_LIMIT` (`appendRoll`); clearing the history is a full `set`.
+function diffRolls(prev: RollRecord[], next: RollRecord[]): StatePatch[] | null {
+  const kept = prev.length === 0 ? 0 : next.indexOf(prev[prev.length - 1]) + 1;
+  const dropped = prev.length - kept;
+  if (
+    kept === next.length ||
+    (prev.length > 0 && kept === 0) ||
+    !next.slice(0, kept).every((r, i) => r === prev[dropped + i]) ||
+    next.length !== Math.min(prev.length + next.length - kept, ROLL_HISTORY_LIMIT)
+  ) {
+    return null;
+  }
+  return next.slice(kept).map((roll) => ({ type: 'rollAdded', roll }));
+}
+
+// Tokens by id, so moving one doesn't resend every token's image. Edits
+// replace the token object, so identity says what changed.
+function diffTokens(prev: Token[], next: Token[]): StatePatch[] {
+  const patches: StatePatch[] = next
+    .filter((token) => !prev.includes(token))
+    .map((token) => ({ type: 'tokenUpserted', token }));
+  const ids = new Set(next.map((t) => t.id));
+  for (const token of prev) {
+    if (!ids.has(token.id)) patches.push({ type: 'tokenRemoved', id: token.id });
+  }
+  return patches;
+}
+
+// Patches turning `prev` into `next`. Relies on state updates being
+// immutable: untouched fields keep their identity and are skipped.
+export function diffState(prev: AppState, next: AppState): StatePatch[] {
+  const patches: StatePatch[] = [];
+  for (const field of Object.keys(next) as Array<keyof AppState>) {
+    if (prev[field] === next[field]) continue;
+    let fieldPatches: StatePatch[] | null = null;
+    switch (field) {
+      case 'fog': {
+        const cells = diffFog(prev.fog, next.fog);
+        fieldPatches = cells && [{ type: 'fogCells', cells }];
+        break;
+      }
+      case 'drawings':
+        fieldPatches = diffDrawings(prev.drawings, next.drawings);
+        break;
+      case 'laserPoints':
+        fieldPatches = [{ type: 'laser', points: next.laserPoints }];
+        break;
+      case 'initiative':
+        fieldPatches = [{ type: 'initiative', initiative: next.initiative }];
+        break;
+      case 'rolls':
+        fieldPatches = diffRolls(prev.rolls, next.rolls);
+        break;
+      case 'tokens':
+        fieldPatches = diffTokens(prev.tokens, next.tokens);
+        break;
+    }
+    patches.push(...(fieldPatches ?? [{ type: 'set', field, value: next[field] }]));
+  }
+  return patches;
+}
+
+export function applyPatches(state: AppState, patches: StatePatch[]): AppState {
+  return patches.reduce<AppState>((s, patch) => {
+    switch (patch.type) {
+      case 'fogCells': {
+        const cells = [...s.fog.cells];
+        for (const { row, col, fogged } of patch.cells) {
+          if (!cells[row] || col >= cells[row].length) continue;
+          if (cells[row] === s.fog.cells[row]) cells[row] = [...cells[row]];
+          cells[row][col] = fogged;
+        }
+        return { ...s, fog: { ...s.fog, cells } };
+      }
+      case 'drawingAdded':
+        return s.drawings.some((d) => d.id === patch.drawing.id)
+          ? s
+          : { ...s, drawings: [...s.drawings, patch.drawing] };
+      case 'drawingRemoved':
+        return { ...s, drawings: s.drawings.filter((d) => d.id !== patch.id) };
+      case 'laser':
+        return { ...s, laserPoints: patch.points };
+      case 'initiative':
+        return { ...s, initiative: patch.initiative };
+      case 'rollAdded':
+        return s.rolls.some((r) => r.id === patch.roll.id)
+          ? s
+          : { ...s, rolls: appendRoll(s.rolls, patch.roll) };
+      case 'tokenUpserted': {
+        const exists = s.tokens.some((t) => t.id === patch.token.id);
+        return {
+          ...s,
+          tokens: exists
+            ? s.tokens.map((t) => (t.id === patch.token.id ? patch.token : t))
+            : [...s.tokens, patch.token],
+        };
+      }
+      case 'tokenRemoved':
+        return { ...s, tokens: s.tokens.filter((t) => t.id !== patch.id) };
+      case 'set':
+        return { ...s, [patch.field]: patch.value };
+    }
+  }, state);
+}
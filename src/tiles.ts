@@ -0,0 +1,79 @@
+import { convertFileSrc, invoke, isTauri } from '@tauri-apps/api/core';
+import { lanUrl } from './remote';
+
+// Tiled image maps. The pyramid is cut and cached in Rust
+// (`src-tauri/src/tiles.rs`) and served on the `tiles://` scheme; this side
+// picks the level and the tiles in view.
+
+// `TileSet` in `tiles.rs`. Level 0 is the image at full size; each level
+// after it is half the size of the one before.
+export interface TileSet {
+  id: string;
+  width: number;
+  height: number;
+  tileSize: number;
+  levels: number;
+}
+
+export interface Tile {
+  key: string;
+  url: string;
+  // Where the tile goes, in map pixels.
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export function loadMapTiles(path: string): Promise<TileSet> {
+  return invoke<TileSet>('load_map_tiles', { path });
+}
+
+// `tiles://localhost/...`, or `http://tiles.localhost/...` on Windows. LAN
+// clients get the same tiles from the LAN server (`lan.rs`).
+function tileUrl(tiles: TileSet, level: number, col: number, row: number): string {
+  const path = `${tiles.id}/${level}/${col}/${row}`;
+  return isTauri() ? `${convertFileSrc('', 'tiles')}${path}` : lanUrl(`/tiles/${path}`);
+}
+
+// The last level: the whole map in one tile. A tiled map's `imageUrl`, so
+// the full image is never read into the webview.
+export function overviewUrl(tiles: TileSet): string {
+  return tileUrl(tiles, tiles.levels - 1, 0, 0);
+}
+
+// The smallest level that still has a pixel for every screen pixel at
+// `scale` (screen pixels per map pixel).
+export function levelForScale(tiles: TileSet, scale: number): number {
+  const level = Math.floor(Math.log2(1 / (scale * window.devicePixelRatio)));
+  return Math.max(0, Math.min(tiles.levels - 1, level));
+}
+
+// The tiles of `level` that overlap `view` (map pixels).
+export function visibleTiles(
+  tiles: TileSet,
+  level: number,
+  view: { x: number; y: number; width: number; height: number },
+): Tile[] {
+  const span = tiles.tileSize * 2 ** level; // map pixels per tile
+  const firstCol = Math.max(0, Math.floor(view.x / span));
+  const firstRow = Math.max(0, Math.floor(view.y / span));
+  const lastCol = Math.min(Math.ceil(tiles.width / span), Math.ceil((view.x + view.width) / span)) - 1;
+  const lastRow = Math.min(Math.ceil(tiles.height / span), Math.ceil((view.y + view.height) / span)) - 1;
+  const result: Tile[] = [];
+  for (let row = firstRow; row <= lastRow; row++) {
+    for (let col = firstCol; col <= lastCol; col++) {
+      const x = col * span;
+      const y = row * span;
+      result.push({
+        key: `${level}/${col}/${row}`,
+        url: tileUrl(tiles, level, col, row),
+        x,
+        y,
+        width: Math.min(span, tiles.width - x),
+        height: Math.min(span, tiles.height - y),
+      });
+    }
+  }
+  return result;
+}
@@ -0,0 +1,103 @@
+import { readFile } from '@tauri-apps/plugin-fs';
+import { MapState, Token } from './types';
+import { hexAtPoint, hexCenter } from './hexmap';
+
+// Creature tokens (`Token` in `src-tauri/src/types.rs`). Positions are token
+// centres in map pixels, snapped so a token covers whole grid squares — or
+// sits on a hex centre on `.hexm` maps.
+
+// What the Token tool places; edited in the sidebar's Tokens panel.
+export interface TokenTemplate {
+  name: string;
+  image: string | null;
+  color: string;
+  size: number; // grid squares
+  owner: string;
+  hidden: boolean;
+}
+
+export const DEFAULT_TOKEN_TEMPLATE: TokenTemplate = {
+  name: 'Token',
+  image: null,
+  color: '#c62828',
+  size: 1,
+  owner: '',
+  hidden: false,
+};
+
+// Token images are scaled down to this (px, longest side) before they go
+// into the state, which is saved and synced to every player screen.
+const TOKEN_IMAGE_SIZE = 256;
+
+export function snapToken(map: MapState, size: number, x: number, y: number): { x: number; y: number } {
+  if (map.hexmap) {
+    const hex = hexAtPoint(map.hexmap, x, y);
+    if (!hex) return { x, y };
+    const { cx, cy } = hexCenter(map.hexmap, hex.col, hex.row);
+    return { x: cx, y: cy };
+  }
+  const grid = map.gridSize || 50;
+  const half = (size * grid) / 2;
+  // Odd sizes centre on a cell, even sizes on a grid corner.
+  return {
+    x: Math.round((x - half - map.gridOffsetX) / grid) * grid + map.gridOffsetX + half,
+    y: Math.round((y - half - map.gridOffsetY) / grid) * grid + map.gridOffsetY + half,
+  };
+}
+
+// Radius a token is drawn at, in map pixels.
+export function tokenRadius(map: MapState, token: Pick<Token, 'size'>): number {
+  const cell = map.hexmap ? map.hexmap.hexRadius * Math.sqrt(3) : map.gridSize || 50;
+  return (token.size * cell) / 2;
+}
+
+// The topmost token under a point, if any.
+export function tokenAt(tokens: Token[], map: MapState, x: number, y: number): Token | null {
+  for (let i = tokens.length - 1; i >= 0; i--) {
+    const token = tokens[i];
+    if (Math.hypot(token.x - x, token.y - y) <= tokenRadius(map, token)) return token;
+  }
+  return null;
+}
+
+// "Goblin" -> "Goblin 2" when there's already a Goblin on the map.
+export function uniqueTokenName(tokens: Token[], name: string): string {
+  const base = name.trim() || 'Token';
+  const names = new Set(tokens.map((t) => t.name));
+  if (!names.has(base)) return base;
+  let n = 2;
+  while (names.has(`${base} ${n}`)) n++;
+  return `${base} ${n}`;
+}
+
+let tokenCounter = 0;
+
+export function createToken(tokens: Token[], template: TokenTemplate, x: number, y: number): Token {
+  tokenCounter += 1;
+  return {
+    id: `token-${Date.now().toString(36)}-${tokenCounter}`,
+    name: uniqueTokenName(tokens, template.name),
+    image: template.image,
+    color: template.color,
+    size: template.size,
+    owner: template.owner.trim(),
+    hidden: template.hidden,
+    x,
+    y,
+  };
+}
+
+// An image file as a small PNG data: URL for a token.
+export async function readTokenImage(path: string): Promise<string> {
+  const bytes = await readFile(path);
+  const bitmap = await createImageBitmap(new Blob([bytes]));
+  const scale = Math.min(1, TOKEN_IMAGE_SIZE / Math.max(bitmap.width, bitmap.height));
+  const canvas = document.createElement('canvas');
+  canvas.width = Math.max(1, Math.round(bitmap.width * scale));
+  canvas.height = Math.max(1, Math.round(bitmap.height * scale));
+  const ctx = canvas.getContext('2d');
+  if (!ctx) throw new Error('No 2D canvas available');
+  ctx.drawImage(bitmap, 0, 0, canvas.width, canvas.height);
+  bitmap.close();
+  return canvas.toDataURL('image/png');
+}
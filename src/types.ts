@@ -1,4 +1,8 @@
 import type { HexMapMeta } from './hexmap';
+import type { DiceRoll } from './dice';
+import type { TokenTemplate } from './tokens';
+import type { DistanceRule, MeasureMode, TemplateShape } from './measure';
+import type { TileSet } from './tiles';
 
 export interface Point {
   x: number;
@@ -25,6 +29,11 @@ export interface MapState {
   // Null for image/cartographer maps. The visual itself is the rendered SVG
   // in `imageUrl`, so this stays purely interactive metadata.
   hexmap: HexMapMeta | null;
+  ftPerCell: number; // feet per grid cell (a cartographer bundle's `grid.ft_per_cell`, 5 otherwise)
+  // Tile pyramid of the map file, for plain image files; the views draw these
+  // instead of the whole image, and `imageUrl` is just the pyramid's
+  // overview tile. Null for other maps (and LAN clients).
+  tiles: TileSet | null;
 }
 
 export interface FogState {
@@ -53,6 +62,42 @@ export interface BlockState {
   cells: Record<string, string>;
 }
 
+// Line-of-sight walls, DM-only (see `src-tauri/src/visibility.rs`). All
+// coordinates are map pixels.
+export interface Wall {
+  id: string;
+  x1: number;
+  y1: number;
+  x2: number;
+  y2: number;
+  door?: boolean;
+  open?: boolean; // Open doors don't block sight
+}
+
+export interface LightSource {
+  id: string;
+  x: number;
+  y: number;
+  radius?: number | null; // Sight radius in pixels; unset = unlimited
+}
+
+export interface WallState {
+  walls: Wall[];
+  lights: LightSource[];
+  // Lights baked into an imported map (e.g. a .dd2vtt's torches); shown to
+  // the DM but, unlike `lights`, they don't reveal fog.
+  mapLights?: LightSource[];
+}
+
+// A `.dd2vtt` / `.uvtt` map parsed by `load_uvtt` (`src-tauri/src/uvtt.rs`).
+export interface UvttMap {
+  image: string; // data: URL
+  gridSize: number;
+  width: number;
+  height: number;
+  walls: WallState;
+}
+
 export interface ViewState {
   scale: number;
   offsetX: number;
@@ -78,17 +123,97 @@ export interface PlayerViewOffset {
   y: number;
 }
 
+// A condition on a combatant. `rounds` counts down at the end of each of
+// its turns; null lasts until removed.
+export interface Condition {
+  name: string;
+  rounds: number | null;
+}
+
 export interface InitiativeEntity {
   id: string;
   name: string;
   dice: string; // How this entity rolls initiative, e.g. "1d6", "1d20", "1d20+3"
   roll: number | null; // Current rolled initiative (null until rolled)
   isPc: boolean; // Player characters are kept by "Clear" (only NPCs are removed)
+  // The fields below are missing from rosters saved before turn tracking.
+  dexMod?: number; // Breaks initiative ties, higher first
+  hp?: number | null; // NPC hit points never reach players (`projection.rs`)
+  maxHp?: number | null;
+  conditions?: Condition[];
+  tokenId?: string | null; // Token on the current map this entry is linked to
 }
 
 export interface InitiativeState {
   visible: boolean; // Whether the tracker is shown (in both DM and player windows)
   entities: InitiativeEntity[];
+  turn: string | null; // Id of the entity whose turn it is; null outside combat
+  round: number; // 1 on the first round of combat, 0 outside combat
+}
+
+// The party marker on a `.hexm` map and where it has been. Hexes are 1-based
+// [col, row]; the log never reaches players (`projection.rs`).
+export interface TravelLogEntry {
+  from: [number, number] | null; // null when the marker was placed, not moved
+  to: [number, number];
+  day: number; // in-game day of the move
+  note: string;
+}
+
+// An encounter rolled for the party's hex (`roll_encounter`).
+export interface EncounterLogEntry {
+  hex: [number, number];
+  day: number;
+  summary: string;
+}
+
+export interface PartyState {
+  position: [number, number] | null;
+  day: number;
+  log: TravelLogEntry[];
+  encounters: EncounterLogEntry[];
+}
+
+// A creature token (see `src/tokens.ts`). Hidden tokens, and tokens under
+// fog, never reach players (`projection.rs`).
+export interface Token {
+  id: string;
+  name: string;
+  image: string | null; // small data: URL; null draws a disc with the initial
+  color: string;
+  size: number; // in grid squares
+  owner: string; // player who controls it; '' for the DM's creatures
+  hidden: boolean;
+  x: number; // centre, map pixels
+  y: number;
+}
+
+// The ruler while it's being dragged (cleared on release, like the laser).
+export interface RulerState {
+  from: DrawingPoint;
+  to: DrawingPoint;
+  feet: number;
+}
+
+// An area-of-effect template. `cells` come from `template_area`
+// (`src-tauri/src/measure.rs`) so both views draw exactly what's covered.
+export interface AoeTemplate {
+  id: string;
+  shape: TemplateShape;
+  origin: DrawingPoint; // snapped to the grid
+  toward: DrawingPoint;
+  sizeFt: number;
+  color: string;
+  cells: Array<[number, number]>;
+}
+
+// A roll from the dice tray (`RollRecord` in `src-tauri/src/types.rs`).
+// Secret rolls never reach players.
+export interface RollRecord extends DiceRoll {
+  id: string;
+  by: string; // "DM" or the LAN player's name
+  secret: boolean;
+  at: number; // ms since epoch
 }
 
 export interface AppState {
@@ -102,10 +227,19 @@ export interface AppState {
   playerViewOffset: PlayerViewOffset;
   calibration: CalibrationState;
   initiative: InitiativeState; // Initiative tracker (synced to player view)
+  walls: WallState; // Walls, doors and light sources (never sent to players)
+  party: PartyState; // Party marker and travel log (hex maps)
+  tokens: Token[]; // Creature tokens, later ones drawn on top
+  ruler: RulerState | null; // Ruler being dragged (synced to player view)
+  templates: AoeTemplate[]; // Spell areas for this session (synced, not saved)
+  rolls: RollRecord[]; // Dice tray history for the session, oldest first
 }
 
 // Persisted state for a map (saved to disk)
 export interface SavedMapState {
+  // Save-file schema version, stamped by the Rust side on save. Older files
+  // are migrated to the current version on load.
+  schemaVersion?: number;
   filePath: string;
   imageWidth: number;
   imageHeight: number;
@@ -115,16 +249,35 @@ export interface SavedMapState {
   gridOpacity: number;
   gridOffsetX: number;
   gridOffsetY: number;
-  fog: FogState;
+  fog: FogState; // packed in the file itself; see `src-tauri/src/fog.rs`
   drawings: Drawing[];
   blocks: BlockState;
+  walls?: WallState; // Missing in saves from before walls existed
+  party?: PartyState; // Missing in saves from before the party marker
+  tokens?: Token[]; // Missing in saves from before tokens
   view: ViewState;
   playerViewOffset: PlayerViewOffset;
   calibration: CalibrationState;
   savedAt: string; // ISO timestamp
 }
 
-export type Tool = 'pan' | 'fogReveal' | 'fogHide' | 'draw' | 'laser' | 'block';
+// A named snapshot of a map's saved state (e.g. "Tuesday group"). The state
+// itself stays on disk until restored.
+export interface SnapshotMeta {
+  id: string;
+  name: string;
+  createdAt: number; // ms since epoch
+  updatedAt: number;
+  thumbnail?: string; // small PNG data URL of the DM canvas
+}
+
+// A fog shape dragged out with a fog tool: Shift+drag for a rectangle of
+// cells, Alt+drag to lasso a polygon (points in map pixels).
+export type FogShape =
+  | { kind: 'rect'; from: { row: number; col: number }; to: { row: number; col: number } }
+  | { kind: 'polygon'; points: DrawingPoint[] };
+
+export type Tool = 'pan' | 'fogReveal' | 'fogHide' | 'draw' | 'laser' | 'block' | 'wall' | 'vision' | 'party' | 'token' | 'measure';
 
 export interface ToolState {
   activeTool: Tool;
@@ -133,4 +286,25 @@ export interface ToolState {
   drawStrokeWidth: number;
   laserColor: string;
   blockColor: string;
+  sightRadius: number; // in grid squares, for new light sources (0 = unlimited)
+  tokenTemplate: TokenTemplate; // what the Token tool places
+  measureMode: MeasureMode; // ruler, or the template shape the Measure tool places
+  templateSizeFt: number; // radius (sphere), length (cone, line) or side (cube)
+  templateColor: string;
+  distanceRule: DistanceRule; // how square-grid diagonals count
+}
+
+// A grid found in an image map (`GridEstimate` in `src-tauri/src/griddetect.rs`)
+export interface GridEstimate {
+  size: number;
+  offsetX: number; // nearest grid line to the top-left corner, within half a cell
+  offsetY: number;
+  confidence: number; // 0-1
+}
+
+// LAN player server status (`LanInfo` in `src-tauri/src/lan.rs`)
+export interface LanInfo {
+  port: number;
+  joinCode: string;
+  urls: string[]; // Addresses to open on another device, join code included
 }
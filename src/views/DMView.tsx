@@ -1,18 +1,54 @@
 import { useState, useEffect, useCallback, useRef, useMemo } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { open } from '@tauri-apps/plugin-dialog';
-import { readFile } from '@tauri-apps/plugin-fs';
+import { ask, open, save } from '@tauri-apps/plugin-dialog';
+import { listen } from '@tauri-apps/api/event';
 import { MapCanvas } from '../components/MapCanvas';
 import { Toolbar } from '../components/Toolbar';
 import { GridSettings } from '../components/GridSettings';
 import { CalibrationSettings } from '../components/CalibrationSettings';
 import { InitiativeTracker } from '../components/InitiativeTracker';
-import { AppState, ToolState, PlayerViewport, FogState, BlockState, Drawing } from '../types';
-import { createDefaultState, createDefaultToolState, initializeFog, syncState, onViewportSync } from '../store';
-import { saveMapState, loadMapState, applySavedState, saveInitiative, loadInitiative } from '../persistence';
-import { createEntities, rerollAll } from '../initiative';
-import { loadCartographerBundle, bundleSrcToObjectUrl } from '../cartographer';
-import { loadHexMapFile, buildHexMapMeta, renderHexMapSvg, buildHexLookup, describeHex, type HexMapMeta, type HexCellData } from '../hexmap';
+import { SnapshotSettings } from '../components/SnapshotSettings';
+import { LanSettings } from '../components/LanSettings';
+import { TravelPlanner } from '../components/TravelPlanner';
+import { PartyLog } from '../components/PartyLog';
+import { DiceTray } from '../components/DiceTray';
+import { TokenPanel } from '../components/TokenPanel';
+import Konva from 'konva';
+import { AppState, ToolState, PlayerViewport, FogState, BlockState, Drawing, SnapshotMeta, SavedMapState, LanInfo, FogShape, TravelLogEntry, Token, AoeTemplate, DrawingPoint, GridEstimate } from '../types';
+import { createDefaultState, createDefaultToolState, initializeFog, resizeFog, syncState, onViewportSync } from '../store';
+import { isRasterMap, loadMapFile, MAP_CHANGED_EVENT } from '../mapfile';
+import { fogRect, fogPolygon, revealLineOfSight } from '../fog';
+import {
+  saveMapState,
+  loadMapState,
+  applySavedState,
+  saveInitiative,
+  loadInitiative,
+  saveRolls,
+  loadRolls,
+  describePersistError,
+  listSnapshots,
+  createSnapshot,
+  renameSnapshot,
+  duplicateSnapshot,
+  deleteSnapshot,
+  restoreSnapshot,
+} from '../persistence';
+import {
+  createEntities,
+  rerollAll,
+  nextTurn,
+  previousTurn,
+  endCombat,
+  removeEntities,
+  applyHpInput,
+  parseCondition,
+} from '../initiative';
+import { appendRoll, makeRollRecord, rollDice, PLAYER_ROLL_EVENT, ROLL_HISTORY_LIMIT, type PlayerRoll } from '../dice';
+import { describeHex, hexCenter, hexNeighbors, hexVertices, type HexCellData, type HexDiagnostic } from '../hexmap';
+import { exportTravelLog, rollEncounter } from '../hexcrawl';
+import { readTokenImage, type TokenTemplate } from '../tokens';
+import { measureDistance, templateArea, DEFAULT_FT_PER_CELL } from '../measure';
 import {
   HistoryManager,
   createFogChangeOperation,
@@ -21,17 +57,26 @@ import {
   createFogResetOperation,
   createFogClearOperation,
   createBlockChangeOperation,
+  createTokensChangeOperation,
 } from '../history';
 
+// Below this the detected grid is more likely noise than lines.
+const MIN_GRID_CONFIDENCE = 0.3;
+
 export function DMView() {
   const [state, setState] = useState<AppState>(createDefaultState);
   const [toolState, setToolState] = useState<ToolState>(createDefaultToolState);
   const [windowSize, setWindowSize] = useState({ width: 800, height: 600 });
   const [showSettings, setShowSettings] = useState(false);
+  const [templateDraft, setTemplateDraft] = useState<AoeTemplate | null>(null); // Measure tool, while aiming
+  const [detectingGrid, setDetectingGrid] = useState(false);
+  const [gridDetectStatus, setGridDetectStatus] = useState<string | null>(null); // Last "Detect Grid" result
   const [playerViewport, setPlayerViewport] = useState<PlayerViewport | null>(null);
   // `.hexm` hover tooltip: per-hex data (not synced — DM-only) + hovered hex.
   const [hexLookup, setHexLookup] = useState<Record<string, HexCellData> | null>(null);
   const [hoveredHex, setHoveredHex] = useState<{ col: number; row: number } | null>(null);
+  // Problems found in the loaded `.hexm` (empty for other maps); dismissable.
+  const [hexDiagnostics, setHexDiagnostics] = useState<HexDiagnostic[]>([]);
 
   // Grid calibration binary search state
   const [gridCalibration, setGridCalibration] = useState<{
@@ -46,6 +91,13 @@ export function DMView() {
   const [, forceUpdate] = useState(0); // To trigger re-render after undo/redo
   const [loadError, setLoadError] = useState<string | null>(null);
 
+  // Named snapshots of the current map (settings sidebar)
+  const [snapshots, setSnapshots] = useState<SnapshotMeta[]>([]);
+  const stageRef = useRef<Konva.Stage | null>(null);
+
+  // LAN player server status (settings sidebar); null while stopped
+  const [lanInfo, setLanInfo] = useState<LanInfo | null>(null);
+
   // Track fog state before operation starts (for computing diff)
   const fogBeforeOperation = useRef<FogState | null>(null);
 
@@ -136,7 +188,10 @@ export function DMView() {
     }
 
     saveTimeoutRef.current = window.setTimeout(() => {
-      saveMapState(state);
+      saveMapState(state).catch((err) => {
+        console.error('Failed to save state:', err);
+        setLoadError(`Failed to save map: ${describePersistError(err)}`);
+      });
     }, 1000); // Save 1 second after last change
 
     return () => {
@@ -149,10 +204,10 @@ export function DMView() {
   // Load the persistent initiative roster on startup (global, not per-map).
   const initiativeLoadedRef = useRef(false);
   useEffect(() => {
-    loadInitiative().then((entities) => {
+    loadInitiative().then((saved) => {
       initiativeLoadedRef.current = true;
-      if (entities.length > 0) {
-        setState(prev => ({ ...prev, initiative: { ...prev.initiative, entities } }));
+      if (saved.entities.length > 0) {
+        setState(prev => ({ ...prev, initiative: { ...prev.initiative, ...saved } }));
       }
     });
   }, []);
@@ -163,12 +218,87 @@ export function DMView() {
     if (!initiativeLoadedRef.current) return; // Don't overwrite the roster before the initial load finishes
     if (initiativeSaveTimeoutRef.current) clearTimeout(initiativeSaveTimeoutRef.current);
     initiativeSaveTimeoutRef.current = window.setTimeout(() => {
-      saveInitiative(state.initiative.entities);
+      saveInitiative(state.initiative);
     }, 1000);
     return () => {
       if (initiativeSaveTimeoutRef.current) clearTimeout(initiativeSaveTimeoutRef.current);
     };
-  }, [state.initiative.entities]);
+  }, [state.initiative.entities, state.initiative.turn, state.initiative.round]);
+
+  // Load the dice roll history on startup (global, kept until a new session).
+  const rollsLoadedRef = useRef(false);
+  useEffect(() => {
+    loadRolls().then((rolls) => {
+      rollsLoadedRef.current = true;
+      if (rolls.length > 0) {
+        setState(prev => ({ ...prev, rolls: [...rolls, ...prev.rolls].slice(-ROLL_HISTORY_LIMIT) }));
+      }
+    });
+  }, []);
+
+  // Persist the roll history when it changes (debounced).
+  const rollsSaveTimeoutRef = useRef<number | null>(null);
+  useEffect(() => {
+    if (!rollsLoadedRef.current) return;
+    if (rollsSaveTimeoutRef.current) clearTimeout(rollsSaveTimeoutRef.current);
+    rollsSaveTimeoutRef.current = window.setTimeout(() => {
+      saveRolls(state.rolls);
+    }, 1000);
+    return () => {
+      if (rollsSaveTimeoutRef.current) clearTimeout(rollsSaveTimeoutRef.current);
+    };
+  }, [state.rolls]);
+
+  // LAN players' rolls, already rolled by the server (`lan.rs`).
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+    listen<PlayerRoll>(PLAYER_ROLL_EVENT, (event) => {
+      const record = makeRollRecord(event.payload.roll, event.payload.by, false);
+      setState(prev => ({ ...prev, rolls: appendRoll(prev.rolls, record) }));
+    }).then((fn) => {
+      unlisten = fn;
+    });
+    return () => {
+      if (unlisten) unlisten();
+    };
+  }, []);
+
+  // Refresh the snapshot list whenever a different map is loaded.
+  const refreshSnapshots = useCallback(async (mapFilePath: string | null) => {
+    if (!mapFilePath) {
+      setSnapshots([]);
+      return;
+    }
+    try {
+      setSnapshots(await listSnapshots(mapFilePath));
+    } catch (err) {
+      console.error('Failed to list snapshots:', err);
+      setLoadError(`Failed to list snapshots: ${describePersistError(err)}`);
+    }
+  }, []);
+  useEffect(() => {
+    refreshSnapshots(state.map.filePath);
+  }, [state.map.filePath, refreshSnapshots]);
+
+  // The LAN server outlives a webview reload, so ask whether it's running.
+  useEffect(() => {
+    invoke<LanInfo | null>('lan_server_info').then(setLanInfo);
+  }, []);
+
+  // LAN clients can't load the webview's blob URLs, so hand the server the
+  // image players see; it then points clients at the new copy. A tiled map's
+  // tiles are served by the LAN server itself.
+  const lanImageUrl = state.map.playerImageUrl ?? (state.map.tiles ? null : state.map.imageUrl);
+  useEffect(() => {
+    if (!lanInfo || !lanImageUrl) return;
+    (async () => {
+      const response = await fetch(lanImageUrl);
+      const bytes = await response.arrayBuffer();
+      await invoke('lan_set_map_image', bytes, {
+        headers: { 'content-type': response.headers.get('content-type') ?? 'application/octet-stream' },
+      });
+    })().catch((err) => console.error('Failed to send map image to LAN server:', err));
+  }, [lanInfo, lanImageUrl]);
 
   // Track previous viewport dimensions to detect resize
   const prevViewportRef = useRef<PlayerViewport | null>(null);
@@ -257,6 +387,26 @@ export function DMView() {
             e.preventDefault();
             setToolState(prev => ({ ...prev, activeTool: 'block' }));
             return;
+          case 'l': // (l)ine-of-sight walls and doors
+            e.preventDefault();
+            setToolState(prev => ({ ...prev, activeTool: 'wall' }));
+            return;
+          case 'v': // (v)ision — place light sources
+            e.preventDefault();
+            setToolState(prev => ({ ...prev, activeTool: 'vision' }));
+            return;
+          case 'm': // party (m)arker — move it hex by hex on .hexm maps
+            e.preventDefault();
+            setToolState(prev => ({ ...prev, activeTool: 'party' }));
+            return;
+          case 't': // (t)okens — place, move, hide and delete creature tokens
+            e.preventDefault();
+            setToolState(prev => ({ ...prev, activeTool: 'token' }));
+            return;
+          case 'u': // r(u)ler — measure distances and place spell templates
+            e.preventDefault();
+            setToolState(prev => ({ ...prev, activeTool: 'measure' }));
+            return;
           case 'p': // (p)an — back to pan/select (also opens hex links on .hexm maps)
             e.preventDefault();
             setToolState(prev => ({ ...prev, activeTool: 'pan' }));
@@ -265,9 +415,19 @@ export function DMView() {
             e.preventDefault();
             setState(prev => ({ ...prev, initiative: { ...prev.initiative, visible: !prev.initiative.visible } }));
             return;
+          case 'n': // (n)ext turn
+            e.preventDefault();
+            setState(prev => ({ ...prev, initiative: nextTurn(prev.initiative) }));
+            return;
         }
       }
 
+      if (e.shiftKey && !e.ctrlKey && !e.metaKey && !e.altKey && key === 'n') {
+        e.preventDefault();
+        setState(prev => ({ ...prev, initiative: previousTurn(prev.initiative) }));
+        return;
+      }
+
       // Grid calibration mode controls (no shift required when active)
       if (gridCalibration?.active) {
         if (e.key === '[') {
@@ -478,117 +638,152 @@ export function DMView() {
       const selected = await open({
         multiple: false,
         filters: [
-          { name: 'Maps', extensions: ['jpg', 'jpeg', 'png', 'gif', 'webp', 'json', 'hexm'] },
+          { name: 'Maps', extensions: ['jpg', 'jpeg', 'png', 'gif', 'webp', 'json', 'hexm', 'dd2vtt', 'uvtt'] },
           { name: 'Hex map', extensions: ['hexm'] },
+          { name: 'Universal VTT', extensions: ['dd2vtt', 'uvtt'] },
           { name: 'Cartographer bundle', extensions: ['json'] },
           { name: 'Images', extensions: ['jpg', 'jpeg', 'png', 'gif', 'webp'] },
         ],
       });
       if (!selected) return;
       setLoadError(null);
-
-      const lower = selected.toLowerCase();
-
-      // A `.hexm` file is hex-map metadata: we render it to an SVG (color-
-      // coded by terrain) for the visual, and keep the geometry + per-hex
-      // links so clicking a hex opens its key (e.g. in Obsidian). The DM SVG
-      // shows hex labels; the player SVG hides them.
-      const isHexm = lower.endsWith('.hexm');
-
-      // A cartographer "both views" bundle (`.json`) carries a GM and a
-      // player image. Load both: the DM sees the GM image, the player window
-      // sees the player image. Anything else is a plain image map.
-      const bundle = lower.endsWith('.json')
-        ? await loadCartographerBundle(selected)
-        : null;
-
-      let imageUrl: string;
-      let playerImageUrl: string | null;
-      let gridSizeOverride: number | null = null;
-      let hexmapMeta: HexMapMeta | null = null;
       setHoveredHex(null);
-      if (isHexm) {
-        const hexFile = await loadHexMapFile(selected);
-        hexmapMeta = buildHexMapMeta(hexFile);
-        imageUrl = bundleSrcToObjectUrl(renderHexMapSvg(hexFile, { showLabels: true }));
-        playerImageUrl = bundleSrcToObjectUrl(renderHexMapSvg(hexFile, { showLabels: false }));
-        // Hex maps don't use the square grid; size fog cells to the hex and
-        // hide the grid overlay (set below when building state).
-        gridSizeOverride = hexmapMeta.hexRadius;
-        // Per-hex data for the hover tooltip (DM-only; not synced to player).
-        setHexLookup(buildHexLookup(hexFile));
-      } else if (bundle) {
-        setHexLookup(null);
-        imageUrl = bundleSrcToObjectUrl(bundle.gm);
-        playerImageUrl = bundleSrcToObjectUrl(bundle.player);
-        // The bundle's cell size is authoritative — align the VTT grid to it.
-        gridSizeOverride = bundle.grid?.cell_size ?? null;
-      } else {
-        const fileData = await readFile(selected);
-        imageUrl = URL.createObjectURL(new Blob([fileData]));
-        playerImageUrl = null;
-        setHexLookup(null);
+      const loaded = await loadMapFile(selected);
+      setHexLookup(loaded.hexLookup);
+      setHexDiagnostics(loaded.hexDiagnostics);
+      const { imageUrl, playerImageUrl } = loaded;
+
+      // Check for saved state. A save that can't be read (and has no usable
+      // backup) is reported but doesn't block opening the map fresh; the bad
+      // file is rotated into the backups on the next save rather than lost.
+      const loadedSave = await loadMapState(selected).catch((err) => {
+        console.error('Failed to load saved state:', err);
+        setLoadError(`Could not restore saved state: ${describePersistError(err)}`);
+        return null;
+      });
+      if (loadedSave?.recovered) {
+        setLoadError(`Save file was damaged, so an earlier version was restored: ${loadedSave.recovered}`);
       }
-
-      // Check for saved state
-      const savedState = await loadMapState(selected);
-
-      // Get image dimensions (works for raster and SVG data URLs alike)
-      const img = new Image();
-      img.onload = () => {
-        let newState: AppState;
-        if (savedState) {
-          // Restore saved state, re-attaching freshly generated image URLs.
-          newState = applySavedState(state, savedState, imageUrl, playerImageUrl);
-        } else {
-          // Initialize fresh state
-          const gridSize = gridSizeOverride ?? state.map.gridSize;
-          const fog = initializeFog(img.width, img.height, gridSize);
-          newState = {
-            ...state,
-            map: {
-              ...state.map,
-              imageUrl,
-              playerImageUrl,
-              filePath: selected,
-              imageWidth: img.width,
-              imageHeight: img.height,
-              gridSize,
-              gridOffsetX: 0,
-              gridOffsetY: 0,
-            },
-            fog,
-            drawings: [],
-            laserPoints: [],
-            view: { scale: 1, offsetX: 0, offsetY: 0 },
-            playerViewOffset: { x: 0, y: 0 },
-          };
-        }
-        // Attach the interactive hex metadata (re-derived from the `.hexm`
-        // on every load, so it survives the saved-state path too) and hide
-        // the square grid, which is meaningless over hexes.
-        if (hexmapMeta) {
-          newState = {
-            ...newState,
-            map: { ...newState.map, hexmap: hexmapMeta, gridVisible: false },
-          };
+      const savedState = loadedSave?.state ?? null;
+
+      let newState: AppState;
+      if (savedState) {
+        // Restore saved state, re-attaching freshly generated image URLs.
+        newState = applySavedState(state, savedState, imageUrl, playerImageUrl);
+        // Saves from before walls existed pick up the file's walls.
+        if (loaded.walls && !savedState.walls) {
+          newState = { ...newState, walls: loaded.walls };
         }
-        setState(newState);
-        // Clear history when loading a new map
-        historyManager.clear();
-      };
-      img.onerror = () => {
-        const msg = `Failed to decode the ${bundle ? 'GM' : 'map'} image`;
-        console.error(msg, selected);
-        setLoadError(msg);
+      } else {
+        // Initialize fresh state
+        const gridSize = loaded.gridSize ?? state.map.gridSize;
+        const fog = initializeFog(loaded.imageWidth, loaded.imageHeight, gridSize);
+        newState = {
+          ...state,
+          map: {
+            ...state.map,
+            imageUrl,
+            playerImageUrl,
+            filePath: selected,
+            imageWidth: loaded.imageWidth,
+            imageHeight: loaded.imageHeight,
+            gridSize,
+            gridOffsetX: 0,
+            gridOffsetY: 0,
+          },
+          fog,
+          drawings: [],
+          walls: loaded.walls ?? { walls: [], lights: [] },
+          party: { position: null, day: 1, log: [], encounters: [] },
+          tokens: [],
+          laserPoints: [],
+          view: { scale: 1, offsetX: 0, offsetY: 0 },
+          playerViewOffset: { x: 0, y: 0 },
+        };
+      }
+      // Attach the interactive hex metadata (re-derived from the `.hexm`
+      // on every load, so it survives the saved-state path too) and hide
+      // the square grid, which is meaningless over hexes.
+      if (loaded.hexmap) {
+        newState = {
+          ...newState,
+          map: { ...newState.map, hexmap: loaded.hexmap, gridVisible: false },
+        };
+      }
+      newState = {
+        ...newState,
+        map: { ...newState.map, ftPerCell: loaded.ftPerCell ?? DEFAULT_FT_PER_CELL, tiles: loaded.tiles },
+        ruler: null,
+        templates: [],
       };
-      img.src = imageUrl;
+      setState(newState);
+      setGridDetectStatus(null);
+      // Clear history when loading a new map
+      historyManager.clear();
     } catch (err) {
       console.error('Failed to load map:', err);
       setLoadError(`Failed to load map: ${err instanceof Error ? err.message : String(err)}`);
     }
   }, [state]);
 
+  // Watch the loaded map file; edits made in other tools come back as
+  // `vtt-map-changed` (see `src-tauri/src/watch.rs`).
+  // Stopped when another map is loaded or the view goes away.
+  useEffect(() => {
+    const path = state.map.filePath;
+    if (!path) return;
+    invoke('watch_map', { path }).catch((err) => {
+      console.error('Failed to watch map file:', err);
+    });
+    return () => {
+      invoke('unwatch_map', { path }).catch((err) => {
+        console.error('Failed to stop watching map file:', err);
+      });
+    };
+  }, [state.map.filePath]);
+
+  // Re-render the changed map in place. Fog, drawings, blocks, walls and the
+  // player viewport all stay; the fog grid only grows or shrinks if the
+  // image changed size.
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+    listen<string>(MAP_CHANGED_EVENT, async (event) => {
+      const path = event.payload;
+      try {
+        const loaded = await loadMapFile(path);
+        setHexLookup(loaded.hexLookup);
+        setHexDiagnostics(loaded.hexDiagnostics);
+        setState(prev => {
+          if (prev.map.filePath !== path) return prev; // another map was loaded meanwhile
+          const gridSize = loaded.hexmap ? loaded.hexmap.hexRadius : prev.map.gridSize;
+          return {
+            ...prev,
+            map: {
+              ...prev.map,
+              imageUrl: loaded.imageUrl,
+              playerImageUrl: loaded.playerImageUrl,
+              imageWidth: loaded.imageWidth,
+              imageHeight: loaded.imageHeight,
+              gridSize,
+              hexmap: loaded.hexmap,
+              ftPerCell: loaded.ftPerCell ?? DEFAULT_FT_PER_CELL,
+              tiles: loaded.tiles,
+            },
+            fog: resizeFog(prev.fog, loaded.imageWidth, loaded.imageHeight, gridSize),
+          };
+        });
+      } catch (err) {
+        console.error('Failed to reload map:', err);
+        setLoadError(`Failed to reload map: ${err instanceof Error ? err.message : String(err)}`);
+      }
+    }).then((fn) => {
+      unlisten = fn;
+    });
+    return () => {
+      if (unlisten) unlisten();
+    };
+  }, []);
+
   // Open player window
   const handleOpenPlayerWindow = useCallback(async () => {
     try {
@@ -598,6 +793,64 @@ export function DMView() {
     }
   }, []);
 
+  const handleStartLan = async () => {
+    try {
+      setLanInfo(await invoke<LanInfo>('start_lan_server'));
+    } catch (err) {
+      console.error('Failed to start LAN server:', err);
+      setLoadError(String(err));
+    }
+  };
+
+  const handleStopLan = async () => {
+    await invoke('stop_lan_server');
+    setLanInfo(null);
+  };
+
+  // Snapshot handlers. Every mutation re-lists from disk so the sidebar
+  // always reflects what's actually saved.
+  const runSnapshotAction = async (action: () => Promise<unknown>) => {
+    try {
+      await action();
+    } catch (err) {
+      console.error('Snapshot action failed:', err);
+      setLoadError(`Snapshot failed: ${describePersistError(err)}`);
+    }
+    await refreshSnapshots(state.map.filePath);
+  };
+
+  const handleCreateSnapshot = (name: string) => runSnapshotAction(() => {
+    // ~160px-wide PNG of the DM canvas as the thumbnail
+    const stage = stageRef.current;
+    const thumbnail = stage ? stage.toDataURL({ pixelRatio: 160 / stage.width() }) : null;
+    return createSnapshot(state, name, thumbnail);
+  });
+
+  const handleRenameSnapshot = (id: string, name: string) => runSnapshotAction(() =>
+    renameSnapshot(state.map.filePath!, id, name));
+
+  const handleDuplicateSnapshot = (id: string) => runSnapshotAction(() => {
+    const source = snapshots.find(s => s.id === id);
+    return duplicateSnapshot(state.map.filePath!, id, `${source?.name ?? 'Snapshot'} (copy)`);
+  });
+
+  const handleDeleteSnapshot = (id: string) => runSnapshotAction(() =>
+    deleteSnapshot(state.map.filePath!, id));
+
+  // Replace the live session with a snapshot, keeping the loaded images and
+  // hex metadata (a snapshot is always of the map that's currently open).
+  const handleRestoreSnapshot = (id: string) => runSnapshotAction(async () => {
+    const saved: SavedMapState = await restoreSnapshot(state.map.filePath!, id);
+    setState(prev => {
+      const next = applySavedState(prev, saved, prev.map.imageUrl ?? '', prev.map.playerImageUrl);
+      return prev.map.hexmap
+        ? { ...next, map: { ...next.map, hexmap: prev.map.hexmap, gridVisible: false } }
+        : next;
+    });
+    historyManager.clear();
+    forceUpdate(n => n + 1);
+  });
+
   // Tool handlers
   const handleBrushSizeChange = (size: number) => {
     setToolState(prev => ({ ...prev, brushSize: size }));
@@ -615,6 +868,51 @@ export function DMView() {
     setToolState(prev => ({ ...prev, blockColor: color }));
   };
 
+  const handleSightRadiusChange = (radius: number) => {
+    setToolState(prev => ({ ...prev, sightRadius: radius }));
+  };
+
+  const handleMeasureSettingsChange = (settings: Partial<ToolState>) => {
+    setToolState(prev => ({ ...prev, ...settings }));
+  };
+
+  const handleClearTemplates = () => {
+    setState(prev => ({ ...prev, templates: [] }));
+  };
+
+  // Measure tool drags: the ruler (shown to players while it's held) or a
+  // template, placed for everyone on release. Distances and covered cells
+  // come from Rust, so replies to older drags are dropped.
+  const measureSeq = useRef(0);
+  const handleMeasure = useCallback(async (from: DrawingPoint, to: DrawingPoint, done: boolean) => {
+    const seq = ++measureSeq.current;
+    const { measureMode: mode, distanceRule, templateSizeFt, templateColor } = toolState;
+    if (mode === 'ruler' && done) {
+      setState(prev => ({ ...prev, ruler: null }));
+      return;
+    }
+    try {
+      if (mode === 'ruler') {
+        const feet = await measureDistance(state.map, state.fog, distanceRule, from, to);
+        if (seq === measureSeq.current) setState(prev => ({ ...prev, ruler: { from, to, feet } }));
+        return;
+      }
+      const template = { shape: mode, origin: from, toward: to, sizeFt: templateSizeFt };
+      const area = await templateArea(state.map, state.fog, distanceRule, template);
+      if (seq !== measureSeq.current) return;
+      const placed: AoeTemplate = { id: Date.now().toString(), ...template, ...area, color: templateColor };
+      if (done) {
+        setTemplateDraft(null);
+        setState(prev => ({ ...prev, templates: [...prev.templates, placed] }));
+      } else {
+        setTemplateDraft(placed);
+      }
+    } catch (err) {
+      console.error('Measure failed:', err);
+      setLoadError(`Measure failed: ${err}`);
+    }
+  }, [toolState, state.map, state.fog]);
+
   // State handlers
   const handleStateChange = (newState: AppState) => {
     // Update fog ref immediately (before async React render) for undo/redo tracking
@@ -657,6 +955,193 @@ export function DMView() {
     fogBeforeOperation.current = null;
   }, [historyManager]);
 
+  // Rectangle / lasso reveal (Shift/Alt+drag with a fog tool), computed on
+  // the Rust side; recorded for undo like a brush stroke.
+  const handleFogShape = useCallback(async (shape: FogShape, reveal: boolean) => {
+    handleFogOperationStart();
+    try {
+      const fog = shape.kind === 'rect'
+        ? await fogRect(latestFogRef.current, shape.from, shape.to, reveal)
+        : await fogPolygon(latestFogRef.current, shape.points, state.map, reveal);
+      latestFogRef.current = fog;
+      setState(prev => ({ ...prev, fog }));
+      handleFogOperationEnd();
+    } catch (err) {
+      console.error('Fog shape failed:', err);
+      setLoadError(`Fog shape failed: ${err}`);
+    }
+  }, [handleFogOperationStart, handleFogOperationEnd, state.map]);
+
+  // Whenever walls, doors or lights change, reveal what the lights can now
+  // see (an opened door, a light moved down the corridor). One undo step each.
+  useEffect(() => {
+    if (state.walls.lights.length === 0 || state.map.imageWidth === 0) return;
+    let cancelled = false;
+    handleFogOperationStart();
+    revealLineOfSight(latestFogRef.current, state.walls, state.map)
+      .then((fog) => {
+        if (cancelled) return;
+        latestFogRef.current = fog;
+        setState(prev => ({ ...prev, fog }));
+        handleFogOperationEnd();
+      })
+      .catch((err) => {
+        console.error('Line of sight failed:', err);
+        setLoadError(`Line of sight failed: ${err}`);
+      });
+    return () => {
+      cancelled = true;
+    };
+    // Only wall edits trigger a reveal, not every fog or map change.
+  }, [state.walls]);
+
+  // Party marker (hex maps). Without Shift the marker only steps to a
+  // neighboring hex; Shift+click (or the first click) places it anywhere.
+  // The new hex and its neighbors are revealed as one undo step.
+  const handlePartyMove = useCallback(async (hex: { col: number; row: number }, place: boolean) => {
+    const meta = state.map.hexmap;
+    if (!meta) return;
+    const from = state.party.position;
+    if (from && from[0] === hex.col && from[1] === hex.row) return;
+    const neighbors = hexNeighbors(meta, hex.col, hex.row);
+    const adjacent = !!from && neighbors.some(n => n.col === from[0] && n.row === from[1]);
+    if (from && !place && !adjacent) return;
+    const entry: TravelLogEntry = { from: adjacent && !place ? from : null, to: [hex.col, hex.row], day: state.party.day, note: '' };
+    setState(prev => ({ ...prev, party: { ...prev.party, position: entry.to, log: [...prev.party.log, entry] } }));
+
+    handleFogOperationStart();
+    try {
+      let fog = latestFogRef.current;
+      for (const { col, row } of [hex, ...neighbors]) {
+        const { cx, cy } = hexCenter(meta, col, row);
+        const points = hexVertices(meta.orientation, meta.hexRadius, cx, cy).map(([x, y]) => ({ x, y }));
+        fog = await fogPolygon(fog, points, state.map, true);
+      }
+      latestFogRef.current = fog;
+      setState(prev => ({ ...prev, fog }));
+      handleFogOperationEnd();
+    } catch (err) {
+      console.error('Party reveal failed:', err);
+      setLoadError(`Party reveal failed: ${err}`);
+    }
+  }, [state.map, state.party, handleFogOperationStart, handleFogOperationEnd]);
+
+  const handlePartyDayChange = (day: number) => {
+    setState(prev => ({ ...prev, party: { ...prev.party, day } }));
+  };
+
+  const handlePartyNoteChange = (index: number, note: string) => {
+    setState(prev => ({
+      ...prev,
+      party: { ...prev.party, log: prev.party.log.map((entry, i) => (i === index ? { ...entry, note } : entry)) },
+    }));
+  };
+
+  // Drops the last log entry and puts the marker back where it was. Fog it
+  // revealed stays revealed (Ctrl+Z undoes that separately).
+  const handlePartyUndo = () => {
+    setState(prev => {
+      const log = prev.party.log.slice(0, -1);
+      const last = prev.party.log[prev.party.log.length - 1];
+      const position = last?.from ?? log[log.length - 1]?.to ?? null;
+      return { ...prev, party: { ...prev.party, position, log } };
+    });
+  };
+
+  const handleExportTravelLog = async () => {
+    const mapPath = state.map.filePath;
+    if (!mapPath) return;
+    const path = await save({
+      defaultPath: `${mapPath.replace(/\.[^./\\]+$/, '')}-travel-log.md`,
+      filters: [{ name: 'Markdown', extensions: ['md'] }],
+    });
+    if (!path) return;
+    try {
+      await exportTravelLog(mapPath, state.party, path);
+    } catch (err) {
+      console.error('Travel log export failed:', err);
+      setLoadError(`Travel log export failed: ${err}`);
+    }
+  };
+
+  // Rolls on the campaign's encounter tables for the party's hex and logs
+  // the result under the current day.
+  const handleRollEncounter = async () => {
+    const mapPath = state.map.filePath;
+    const hex = state.party.position;
+    if (!mapPath || !hex) return;
+    try {
+      const { summary } = await rollEncounter(mapPath, hex);
+      setState(prev => ({
+        ...prev,
+        party: { ...prev.party, encounters: [...prev.party.encounters, { hex, day: prev.party.day, summary }] },
+      }));
+    } catch (err) {
+      console.error('Encounter roll failed:', err);
+      setLoadError(`Encounter roll failed: ${err}`);
+    }
+  };
+
+  // Dice tray: public rolls go out to the player view, secret ones stay here.
+  const handleDiceRoll = async (expression: string, secret: boolean) => {
+    try {
+      const record = makeRollRecord(await rollDice(expression), 'DM', secret);
+      setState(prev => ({ ...prev, rolls: appendRoll(prev.rolls, record) }));
+    } catch (err) {
+      setLoadError(`Dice: ${err}`);
+    }
+  };
+
+  const handleNewDiceSession = () => {
+    setState(prev => ({ ...prev, rolls: [] }));
+  };
+
+  // Every token change (place, move, hide, delete) is one undo step.
+  // Deleting a token that's in the initiative roster (a defeated creature)
+  // offers to drop it from the roster too, in the same step, so undoing the
+  // deletion brings the roster entry back with it.
+  const handleTokensChange = useCallback(async (tokens: Token[]) => {
+    const kept = new Set(tokens.map(t => t.id));
+    const removed = state.tokens.filter(t => !kept.has(t.id)).map(t => t.id);
+    const linked = state.initiative.entities.filter(e => e.tokenId && removed.includes(e.tokenId));
+    const dropLinked = linked.length > 0 && await ask(
+      `Remove ${linked.map(e => e.name).join(', ')} from initiative too?`,
+      { title: 'Token removed', kind: 'warning', okLabel: 'Remove', cancelLabel: 'Keep' },
+    );
+
+    const operation = createTokensChangeOperation(state.tokens, tokens, dropLinked ? linked : []);
+    historyManager.push(operation);
+    setState(prev => operation.apply(prev));
+    forceUpdate(n => n + 1);
+  }, [state.tokens, state.initiative.entities, historyManager]);
+
+  const handleTokenTemplateChange = (tokenTemplate: TokenTemplate) => {
+    setToolState(prev => ({ ...prev, tokenTemplate }));
+  };
+
+  const handlePickTokenImage = async () => {
+    try {
+      const selected = await open({
+        multiple: false,
+        filters: [{ name: 'Images', extensions: ['jpg', 'jpeg', 'png', 'gif', 'webp'] }],
+      });
+      if (!selected || typeof selected !== 'string') return;
+      const image = await readTokenImage(selected);
+      setToolState(prev => ({ ...prev, tokenTemplate: { ...prev.tokenTemplate, image } }));
+    } catch (err) {
+      console.error('Failed to load token image:', err);
+      setLoadError(`Token image: ${err}`);
+    }
+  };
+
+  const handleToggleTokenHidden = (id: string) => {
+    handleTokensChange(state.tokens.map(t => (t.id === id ? { ...t, hidden: !t.hidden } : t)));
+  };
+
+  const handleRemoveToken = (id: string) => {
+    handleTokensChange(state.tokens.filter(t => t.id !== id));
+  };
+
   // Drawing added callback
   const handleDrawingAdded = useCallback((drawing: Drawing) => {
     const operation = createDrawingAddOperation(drawing);
@@ -740,6 +1225,34 @@ export function DMView() {
     });
   };
 
+  // Fill in the grid from the image itself (`griddetect.rs`). A weak match
+  // is reported but not applied. Like a size change, this resets the fog.
+  const handleDetectGrid = async () => {
+    const path = state.map.filePath;
+    if (!path) return;
+    setDetectingGrid(true);
+    try {
+      const grid = await invoke<GridEstimate>('detect_grid', { path });
+      const confidence = `${Math.round(grid.confidence * 100)}% sure`;
+      if (grid.confidence < MIN_GRID_CONFIDENCE) {
+        setGridDetectStatus(`No clear grid found (${confidence})`);
+        return;
+      }
+      setState(prev => ({
+        ...prev,
+        map: { ...prev.map, gridSize: grid.size, gridOffsetX: grid.offsetX, gridOffsetY: grid.offsetY },
+        fog: initializeFog(prev.map.imageWidth, prev.map.imageHeight, grid.size),
+      }));
+      setGridDetectStatus(`${grid.size} px, ${confidence}`);
+    } catch (err) {
+      console.error('Grid detection failed:', err);
+      setGridDetectStatus(null);
+      setLoadError(`Grid detection failed: ${err}`);
+    } finally {
+      setDetectingGrid(false);
+    }
+  };
+
   const handleGridVisibleChange = (visible: boolean) => {
     setState(prev => ({ ...prev, map: { ...prev.map, gridVisible: visible } }));
   };
@@ -778,8 +1291,14 @@ export function DMView() {
   };
 
   // Initiative tracker handlers
-  const handleInitiativeAdd = (name: string, count: number, dice: string, isPc: boolean) => {
-    const created = createEntities(name, count, dice, isPc);
+  const handleInitiativeAdd = async (name: string, count: number, dice: string, isPc: boolean, dexMod: number) => {
+    let created;
+    try {
+      created = await createEntities(name, count, dice, isPc, dexMod);
+    } catch (err) {
+      setLoadError(`Initiative: ${err}`);
+      return;
+    }
     setState(prev => ({
       ...prev,
       initiative: { ...prev.initiative, entities: [...prev.initiative.entities, ...created] },
@@ -787,16 +1306,45 @@ export function DMView() {
   };
 
   const handleInitiativeRemove = (id: string) => {
+    setState(prev => ({ ...prev, initiative: removeEntities(prev.initiative, new Set([id])) }));
+  };
+
+  const handleInitiativeLinkToken = (id: string, tokenId: string | null) => {
     setState(prev => ({
       ...prev,
-      initiative: { ...prev.initiative, entities: prev.initiative.entities.filter(e => e.id !== id) },
+      initiative: {
+        ...prev.initiative,
+        entities: prev.initiative.entities.map(e => (e.id === id ? { ...e, tokenId } : e)),
+      },
     }));
   };
 
-  const handleInitiativeReroll = () => {
+  // Centre the DM view on an entry's linked token.
+  const handleInitiativeSelect = (id: string) => {
+    const tokenId = state.initiative.entities.find(e => e.id === id)?.tokenId;
+    const token = state.tokens.find(t => t.id === tokenId);
+    if (!token) return;
+    const width = showSettings ? windowSize.width - 300 : windowSize.width;
     setState(prev => ({
       ...prev,
-      initiative: { ...prev.initiative, entities: rerollAll(prev.initiative.entities) },
+      view: {
+        ...prev.view,
+        offsetX: width / 2 - token.x * prev.view.scale,
+        offsetY: windowSize.height / 2 - token.y * prev.view.scale,
+      },
+    }));
+  };
+
+  // A reroll starts a new fight. Rolls happen in Rust, so merge by id:
+  // entities added or removed while the rolls were in flight are left alone.
+  const handleInitiativeReroll = async () => {
+    const rolled = new Map((await rerollAll(state.initiative.entities)).map(e => [e.id, e.roll]));
+    setState(prev => ({
+      ...prev,
+      initiative: endCombat({
+        ...prev.initiative,
+        entities: prev.initiative.entities.map(e => rolled.has(e.id) ? { ...e, roll: rolled.get(e.id) ?? null } : e),
+      }),
     }));
   };
 
@@ -804,7 +1352,53 @@ export function DMView() {
   const handleInitiativeClear = () => {
     setState(prev => ({
       ...prev,
-      initiative: { ...prev.initiative, entities: prev.initiative.entities.filter(e => e.isPc) },
+      initiative: endCombat({ ...prev.initiative, entities: prev.initiative.entities.filter(e => e.isPc) }),
+    }));
+  };
+
+  const handleNextTurn = () => {
+    setState(prev => ({ ...prev, initiative: nextTurn(prev.initiative) }));
+  };
+
+  const handlePreviousTurn = () => {
+    setState(prev => ({ ...prev, initiative: previousTurn(prev.initiative) }));
+  };
+
+  const handleEndCombat = () => {
+    setState(prev => ({ ...prev, initiative: endCombat(prev.initiative) }));
+  };
+
+  const handleInitiativeHp = (id: string, input: string) => {
+    setState(prev => ({
+      ...prev,
+      initiative: {
+        ...prev.initiative,
+        entities: prev.initiative.entities.map(e => (e.id === id ? applyHpInput(e, input) : e)),
+      },
+    }));
+  };
+
+  const handleAddCondition = (id: string, input: string) => {
+    const condition = parseCondition(input);
+    if (!condition) return;
+    setState(prev => ({
+      ...prev,
+      initiative: {
+        ...prev.initiative,
+        entities: prev.initiative.entities.map(e =>
+          e.id === id ? { ...e, conditions: [...(e.conditions ?? []), condition] } : e),
+      },
+    }));
+  };
+
+  const handleRemoveCondition = (id: string, index: number) => {
+    setState(prev => ({
+      ...prev,
+      initiative: {
+        ...prev.initiative,
+        entities: prev.initiative.entities.map(e =>
+          e.id === id ? { ...e, conditions: (e.conditions ?? []).filter((_, i) => i !== index) } : e),
+      },
     }));
   };
 
@@ -833,6 +1427,26 @@ export function DMView() {
         </div>
       )}
 
+      {/* Problems in the loaded .hexm file, by line */}
+      {hexDiagnostics.length > 0 && (
+        <div
+          style={{
+            position: 'absolute', bottom: 12, left: 12, zIndex: 1000,
+            background: 'rgba(20,17,13,0.94)', color: '#eee', padding: '8px 12px',
+            borderRadius: 6, fontSize: 12, boxShadow: '0 2px 8px rgba(0,0,0,0.4)',
+            maxWidth: 480, maxHeight: 200, overflowY: 'auto',
+          }}
+          onClick={() => setHexDiagnostics([])}
+          title="Click to dismiss"
+        >
+          {hexDiagnostics.map((d, i) => (
+            <div key={i} style={{ color: d.severity === 'error' ? '#ff6b6b' : '#ffc857' }}>
+              Line {d.line}: {d.message}
+            </div>
+          ))}
+        </div>
+      )}
+
       {/* Terrain legend removed — terrain type + travel rate now live in the hex hover tooltip. */}
 
       {/* Hex hover tooltip — wilderness-travel data for the hovered hex */}
@@ -887,6 +1501,9 @@ export function DMView() {
         onDrawColorChange={handleDrawColorChange}
         onLaserColorChange={handleLaserColorChange}
         onBlockColorChange={handleBlockColorChange}
+        onSightRadiusChange={handleSightRadiusChange}
+        onMeasureSettingsChange={handleMeasureSettingsChange}
+        onClearTemplates={handleClearTemplates}
         onLoadMap={handleLoadMap}
         onOpenPlayerWindow={handleOpenPlayerWindow}
         onClearDrawings={handleClearDrawings}
@@ -899,10 +1516,19 @@ export function DMView() {
       <InitiativeTracker
         initiative={state.initiative}
         editable={true}
+        tokens={state.tokens}
+        onLinkToken={handleInitiativeLinkToken}
+        onSelect={handleInitiativeSelect}
         onAdd={handleInitiativeAdd}
         onRemove={handleInitiativeRemove}
         onReroll={handleInitiativeReroll}
         onClear={handleInitiativeClear}
+        onNextTurn={handleNextTurn}
+        onPreviousTurn={handlePreviousTurn}
+        onEndCombat={handleEndCombat}
+        onHpChange={handleInitiativeHp}
+        onAddCondition={handleAddCondition}
+        onRemoveCondition={handleRemoveCondition}
       />
 
       <div className="main-content">
@@ -914,6 +1540,7 @@ export function DMView() {
             onStateChange={handleStateChange}
             onFogOperationStart={handleFogOperationStart}
             onFogOperationEnd={handleFogOperationEnd}
+            onFogShape={handleFogShape}
             onDrawingAdded={handleDrawingAdded}
             onBlockOperationStart={handleBlockOperationStart}
             onBlockOperationEnd={handleBlockOperationEnd}
@@ -921,6 +1548,11 @@ export function DMView() {
             height={windowSize.height}
             playerViewport={playerViewport}
             onHexHover={setHoveredHex}
+            onPartyMove={handlePartyMove}
+            onTokensChange={handleTokensChange}
+            onMeasure={handleMeasure}
+            templateDraft={templateDraft}
+            stageRef={stageRef}
           />
         </div>
 
@@ -932,6 +1564,9 @@ export function DMView() {
               onGridVisibleChange={handleGridVisibleChange}
               onGridColorChange={handleGridColorChange}
               onGridOpacityChange={handleGridOpacityChange}
+              onDetectGrid={isRasterMap(state.map.filePath) ? handleDetectGrid : undefined}
+              detecting={detectingGrid}
+              detectStatus={gridDetectStatus}
             />
             <CalibrationSettings
               calibration={state.calibration}
@@ -940,6 +1575,38 @@ export function DMView() {
               onSaveCalibration={handleSaveCalibration}
               onResetCalibration={handleResetCalibration}
             />
+            <SnapshotSettings
+              snapshots={snapshots}
+              enabled={!!state.map.filePath}
+              onCreate={handleCreateSnapshot}
+              onRestore={handleRestoreSnapshot}
+              onRename={handleRenameSnapshot}
+              onDuplicate={handleDuplicateSnapshot}
+              onDelete={handleDeleteSnapshot}
+            />
+            {state.map.hexmap && state.map.filePath && (
+              <>
+                <PartyLog
+                  party={state.party}
+                  onDayChange={handlePartyDayChange}
+                  onNoteChange={handlePartyNoteChange}
+                  onUndo={handlePartyUndo}
+                  onExport={handleExportTravelLog}
+                  onRollEncounter={handleRollEncounter}
+                />
+                <TravelPlanner mapPath={state.map.filePath} partyPosition={state.party.position} />
+              </>
+            )}
+            <TokenPanel
+              tokens={state.tokens}
+              template={toolState.tokenTemplate}
+              onTemplateChange={handleTokenTemplateChange}
+              onPickImage={handlePickTokenImage}
+              onToggleHidden={handleToggleTokenHidden}
+              onRemove={handleRemoveToken}
+            />
+            <DiceTray rolls={state.rolls} onRoll={handleDiceRoll} onNewSession={handleNewDiceSession} />
+            <LanSettings info={lanInfo} onStart={handleStartLan} onStop={handleStopLan} />
           </div>
         )}
       </div>
@@ -1,12 +1,29 @@
-import { useState, useEffect } from 'react';
+import { useState, useEffect, useMemo } from 'react';
 import { MapCanvas } from '../components/MapCanvas';
 import { InitiativeTracker } from '../components/InitiativeTracker';
-import { AppState } from '../types';
-import { createDefaultState, onStateSync, syncPlayerViewport } from '../store';
+import { DiceOverlay } from '../components/DiceOverlay';
+import { PlayerDice } from '../components/PlayerDice';
+import { AppState, PlayerViewport } from '../types';
+import { createDefaultState, followPlayerState, onViewportSync, syncPlayerViewport, isRemotePlayer } from '../store';
+
+// A LAN browser's screen isn't the calibrated TV, so it shows the same map
+// area as the player window, scaled to fit (and centred).
+function fitToViewport(state: AppState, viewport: PlayerViewport, width: number, height: number): AppState {
+  const scale = Math.min(width / viewport.width, height / viewport.height);
+  return {
+    ...state,
+    calibration: { ...state.calibration, pixelsPerInch: scale * state.map.gridSize },
+    playerViewOffset: {
+      x: viewport.x + viewport.width / 2 - width / scale / 2,
+      y: viewport.y + viewport.height / 2 - height / scale / 2,
+    },
+  };
+}
 
 export function PlayerView() {
   const [state, setState] = useState<AppState>(createDefaultState);
   const [windowSize, setWindowSize] = useState({ width: 800, height: 600 });
+  const [remoteViewport, setRemoteViewport] = useState<PlayerViewport | null>(null);
 
   // Handle window resize
   useEffect(() => {
@@ -24,13 +41,24 @@ export function PlayerView() {
 
   // Listen for state updates from DM window
   useEffect(() => {
-    const unlisten = onStateSync((newState) => {
+    const unlisten = followPlayerState((newState) => {
       setState(newState);
     });
 
     return unlisten;
   }, []);
 
+  // LAN browsers follow the player window's viewport
+  useEffect(() => {
+    if (!isRemotePlayer) return;
+    return onViewportSync(setRemoteViewport);
+  }, []);
+
+  const displayState = useMemo(() => {
+    if (!remoteViewport || state.map.gridSize === 0) return state;
+    return fitToViewport(state, remoteViewport, windowSize.width, windowSize.height);
+  }, [state, remoteViewport, windowSize]);
+
   // Calculate and sync viewport to DM
   useEffect(() => {
     if (!state.map.imageUrl || state.map.gridSize === 0) return;
@@ -54,7 +82,7 @@ export function PlayerView() {
     <div className="player-view">
       {state.map.imageUrl ? (
         <MapCanvas
-          state={state}
+          state={displayState}
           isPlayerView={true}
           width={windowSize.width}
           height={windowSize.height}
@@ -65,6 +93,8 @@ export function PlayerView() {
         </div>
       )}
       <InitiativeTracker initiative={state.initiative} editable={false} />
+      <DiceOverlay rolls={state.rolls ?? []} />
+      {isRemotePlayer && <PlayerDice />}
     </div>
   );
 }